@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use tus_client::manager::{JobId, JobStatus, UploadJob, UploadManager};
+use tus_client::metadata::{relative_path, RELATIVE_PATH_KEY};
+use tus_client::{state, Error, SharedClient};
+
+/// How often the status of the uploads is polled, to update the manifest.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Describes what a batch uploads.
+pub struct Batch {
+    pub endpoint: String,
+    /// Directories, files or glob patterns.
+    pub sources: Vec<String>,
+    pub metadata: HashMap<String, String>,
+    pub concurrency: usize,
+    pub manifest: PathBuf,
+}
+
+/// A file found in one of the sources of a batch.
+struct SourceFile {
+    path: PathBuf,
+    relative_path: String,
+}
+
+/// The journal of a batch, recording the upload of each file so a later run can skip or resume it.
+///
+/// Files are keyed by their canonical path.
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    files: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct ManifestEntry {
+    relative_path: String,
+    endpoint: String,
+    size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    upload_url: Option<String>,
+    status: FileStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Pending,
+    Uploading,
+    Completed,
+    /// The file was uploaded by an earlier run.
+    Skipped,
+    Failed,
+}
+
+/// The summary of a batch.
+#[derive(Serialize)]
+pub struct Report {
+    pub endpoint: String,
+    pub manifest: PathBuf,
+    pub total: usize,
+    pub completed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub bytes_uploaded: u64,
+    pub duration_secs: f64,
+    pub files: Vec<ReportFile>,
+}
+
+#[derive(Serialize)]
+pub struct ReportFile {
+    pub path: String,
+    pub size: u64,
+    pub status: FileStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upload_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Batch {
+    /// Uploads all files of the batch, updating the manifest as each upload progresses.
+    pub fn run(&self, client: SharedClient, show_progress: bool) -> Result<Report, Error> {
+        let started = Instant::now();
+        let mut manifest: Manifest = state::load(&self.manifest)?;
+        state::save(&self.manifest, &manifest)?;
+        // The manifest may be in a directory which is uploaded, but is never uploaded itself
+        let manifest_key = fs::canonicalize(&self.manifest)?.display().to_string();
+
+        let mut files = Vec::new();
+        for source in &self.sources {
+            files.extend(find_files(source)?);
+        }
+
+        let manager = UploadManager::new(client, self.concurrency);
+        let mut jobs: Vec<(JobId, String)> = Vec::new();
+        let mut keys = Vec::new();
+        let mut seen = HashSet::new();
+        for file in files {
+            let key = fs::canonicalize(&file.path)?.display().to_string();
+            // A file may be found by several sources
+            if key == manifest_key || !seen.insert(key.clone()) {
+                continue;
+            }
+            keys.push(key.clone());
+
+            let size = file.path.metadata()?.len();
+            let previous = manifest.files.get(&key);
+
+            let is_uploaded = previous.is_some_and(|entry| {
+                entry.endpoint == self.endpoint
+                    && entry.size == size
+                    && matches!(entry.status, FileStatus::Completed | FileStatus::Skipped)
+            });
+            if is_uploaded {
+                let entry = manifest.files.get_mut(&key).unwrap();
+                entry.status = FileStatus::Skipped;
+                continue;
+            }
+
+            let mut job = UploadJob::new(&self.endpoint, &file.path);
+            job.metadata = self.metadata.clone();
+            job.metadata
+                .insert(RELATIVE_PATH_KEY.to_owned(), file.relative_path.clone());
+            job.upload_url = previous
+                .filter(|entry| entry.endpoint == self.endpoint)
+                .and_then(|entry| entry.upload_url.clone());
+
+            manifest.files.insert(
+                key.clone(),
+                ManifestEntry {
+                    relative_path: file.relative_path,
+                    endpoint: self.endpoint.clone(),
+                    size,
+                    upload_url: job.upload_url.clone(),
+                    status: FileStatus::Pending,
+                    error: None,
+                },
+            );
+            jobs.push((manager.submit(job)?, key));
+        }
+        state::save(&self.manifest, &manifest)?;
+
+        loop {
+            let mut changed = false;
+            for (id, key) in &jobs {
+                let entry = manifest.files.get_mut(key).unwrap();
+                let updated = match manager.status(*id) {
+                    Some(status) => entry.with_status(&status),
+                    None => continue,
+                };
+                if updated != *entry {
+                    *entry = updated;
+                    changed = true;
+                }
+            }
+            if changed {
+                state::save(&self.manifest, &manifest)?;
+            }
+
+            let progress = manager.progress();
+            if show_progress && io::stderr().is_terminal() {
+                eprint!(
+                    "\rUploaded {} of {} files, {} of {} bytes",
+                    progress.completed,
+                    jobs.len(),
+                    progress.bytes_uploaded,
+                    progress.total_size
+                );
+                let _ = io::stderr().flush();
+            }
+            if progress.queued == 0 && progress.running == 0 {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        if show_progress && io::stderr().is_terminal() {
+            eprintln!();
+        }
+
+        let report_files: Vec<ReportFile> = keys
+            .iter()
+            .map(|key| {
+                let entry = &manifest.files[key];
+                ReportFile {
+                    path: entry.relative_path.clone(),
+                    size: entry.size,
+                    status: entry.status,
+                    upload_url: entry.upload_url.clone(),
+                    error: entry.error.clone(),
+                }
+            })
+            .collect();
+
+        let count = |status| report_files.iter().filter(|f| f.status == status).count();
+        Ok(Report {
+            endpoint: self.endpoint.clone(),
+            manifest: self.manifest.clone(),
+            total: report_files.len(),
+            completed: count(FileStatus::Completed),
+            skipped: count(FileStatus::Skipped),
+            failed: count(FileStatus::Failed),
+            bytes_uploaded: report_files
+                .iter()
+                .filter(|f| f.status == FileStatus::Completed)
+                .map(|f| f.size)
+                .sum(),
+            duration_secs: started.elapsed().as_secs_f64(),
+            files: report_files,
+        })
+    }
+}
+
+impl ManifestEntry {
+    fn with_status(&self, status: &JobStatus) -> Self {
+        let mut entry = self.clone();
+        match status {
+            JobStatus::Queued => {}
+            JobStatus::Running { upload_url, .. } => {
+                entry.status = FileStatus::Uploading;
+                if upload_url.is_some() {
+                    entry.upload_url = upload_url.clone();
+                }
+            }
+            JobStatus::Completed { upload_url } => {
+                entry.status = FileStatus::Completed;
+                entry.upload_url = Some(upload_url.clone());
+                entry.error = None;
+            }
+            JobStatus::Failed(error) => {
+                entry.status = FileStatus::Failed;
+                entry.error = Some(error.to_string());
+            }
+            JobStatus::Cancelled => entry.status = FileStatus::Pending,
+        }
+        entry
+    }
+}
+
+/// Finds the files of a source, which is either a directory searched recursively, a file, or a glob pattern.
+fn find_files(source: &str) -> Result<Vec<SourceFile>, Error> {
+    let path = Path::new(source);
+    let mut files = Vec::new();
+    if path.is_dir() {
+        walk(path, path, &mut files)?;
+    } else if path.is_file() {
+        files.push(SourceFile {
+            path: path.to_owned(),
+            relative_path: relative_path(path.parent().unwrap_or(path), path),
+        });
+    } else {
+        let paths = glob::glob(source)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        let base = glob_base(source);
+        for path in paths {
+            let path = path.map_err(io::Error::from)?;
+            if path.is_file() {
+                files.push(SourceFile {
+                    relative_path: relative_path(&base, &path),
+                    path,
+                });
+            }
+        }
+        if files.is_empty() {
+            return Err(Error::IoError(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No files match {}", source),
+            )));
+        }
+    }
+    Ok(files)
+}
+
+fn walk(base: &Path, directory: &Path, files: &mut Vec<SourceFile>) -> io::Result<()> {
+    let mut entries = fs::read_dir(directory)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            walk(base, &path, files)?;
+        } else if path.is_file() {
+            files.push(SourceFile {
+                relative_path: relative_path(base, &path),
+                path,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Get the directory a glob pattern is relative to, which is the part of it before the first wildcard.
+fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| {
+            !component
+                .as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '['])
+        })
+        .collect()
+}
@@ -0,0 +1,434 @@
+//! A command-line client for tus servers, built on `tus_client::Client`. Requires the `cli` feature.
+
+mod batch;
+mod output;
+mod resume;
+
+use self::batch::Batch;
+use self::output::Output;
+use self::resume::ResumeState;
+use clap::{Parser, Subcommand};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+use tus_client::watch::{AfterUpload, WatchConfig, WatchEvent, Watcher};
+use tus_client::{ClientBuilder, Error, RetryPolicy, SharedClient};
+
+/// Uploads files to tus servers, and manages the uploads.
+#[derive(Parser)]
+#[command(name = "tus", version, after_help = output::EXIT_CODES)]
+struct Cli {
+    /// Print the result as JSON instead of text. Errors are printed to stderr as JSON.
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// A header sent with every request, like `Authorization: Bearer abc123`. May be repeated.
+    #[arg(short = 'H', long = "header", value_name = "HEADER", global = true, value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// How many times a failed request is retried.
+    #[arg(long, value_name = "COUNT", default_value_t = 3, global = true)]
+    retries: usize,
+
+    /// The timeout of each request, in seconds.
+    #[arg(long, value_name = "SECONDS", global = true)]
+    timeout: Option<u64>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Upload a file, creating the upload on the server.
+    Upload {
+        /// The creation endpoint of the server, like `https://tus.example.com/files/`.
+        endpoint: String,
+        /// The file to upload.
+        file: PathBuf,
+        /// Metadata of the upload, as `key=value`. May be repeated.
+        #[arg(short, long = "metadata", value_name = "KEY=VALUE", value_parser = parse_metadata)]
+        metadata: Vec<(String, String)>,
+        /// The size of the data sent by each request, in bytes.
+        #[arg(long, value_name = "BYTES", default_value_t = 5 * 1024 * 1024)]
+        chunk_size: usize,
+        /// Resume an unfinished upload of the same file to the same endpoint, instead of starting over.
+        #[arg(long)]
+        resume: bool,
+        /// The file where the URLs of unfinished uploads are kept for `--resume`. Defaults to `~/.tus/uploads.json`.
+        #[arg(long, value_name = "PATH")]
+        state_file: Option<PathBuf>,
+    },
+    /// Upload many files, from directories searched recursively or glob patterns like `data/*.csv`.
+    ///
+    /// The path of each file relative to its directory, or to the part of the pattern before the first wildcard, is added to its metadata as `relativePath`. The upload of each file is recorded in a manifest, so running the same batch again skips the files which were uploaded, and resumes the unfinished uploads.
+    Batch {
+        /// The creation endpoint of the server.
+        endpoint: String,
+        /// Directories, files or glob patterns.
+        #[arg(required = true)]
+        sources: Vec<String>,
+        /// Metadata of every upload, as `key=value`. May be repeated.
+        #[arg(short, long = "metadata", value_name = "KEY=VALUE", value_parser = parse_metadata)]
+        metadata: Vec<(String, String)>,
+        /// The size of the data sent by each request, in bytes.
+        #[arg(long, value_name = "BYTES", default_value_t = 5 * 1024 * 1024)]
+        chunk_size: usize,
+        /// How many files are uploaded at once.
+        #[arg(short, long, default_value_t = 4)]
+        concurrency: usize,
+        /// The manifest recording the upload of each file.
+        #[arg(long, value_name = "PATH", default_value = ".tus-manifest.json")]
+        manifest: PathBuf,
+        /// Write a summary of the batch to this file as JSON.
+        #[arg(long, value_name = "PATH")]
+        report: Option<PathBuf>,
+    },
+    /// Watch a directory, uploading the files which appear in it once they stop changing. Runs until stopped.
+    ///
+    /// The uploads are recorded in a state file, so files which were uploaded aren't uploaded again after a restart.
+    Watch {
+        /// The creation endpoint of the server.
+        endpoint: String,
+        /// The directory to watch, including its subdirectories.
+        directory: PathBuf,
+        /// Metadata of every upload, as `key=value`. May be repeated.
+        #[arg(short, long = "metadata", value_name = "KEY=VALUE", value_parser = parse_metadata)]
+        metadata: Vec<(String, String)>,
+        /// The size of the data sent by each request, in bytes.
+        #[arg(long, value_name = "BYTES", default_value_t = 5 * 1024 * 1024)]
+        chunk_size: usize,
+        /// Upload a file once its size and modification time haven't changed for this many seconds.
+        #[arg(long, value_name = "SECONDS", default_value_t = 5.0)]
+        stable_for: f64,
+        /// Move uploaded files to this directory.
+        #[arg(long, value_name = "DIRECTORY", conflicts_with = "delete")]
+        move_to: Option<PathBuf>,
+        /// Delete uploaded files.
+        #[arg(long)]
+        delete: bool,
+        /// The file where the uploads are recorded. Defaults to `.tus-watch.json` in the watched directory.
+        #[arg(long, value_name = "PATH")]
+        state_file: Option<PathBuf>,
+    },
+    /// Show the progress and metadata of an upload.
+    Info {
+        /// The upload URL.
+        url: String,
+    },
+    /// Show the versions of the protocol, extensions and maximum upload size supported by a server.
+    ServerInfo {
+        /// The creation endpoint of the server.
+        endpoint: String,
+    },
+    /// Delete an upload.
+    Delete {
+        /// The upload URL.
+        url: String,
+    },
+    /// Download a finished upload. An earlier, interrupted download to the same file is resumed.
+    Download {
+        /// The upload URL.
+        url: String,
+        /// The file to save the upload to.
+        dest: PathBuf,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let output = if cli.json {
+        Output::Json
+    } else {
+        Output::Human
+    };
+
+    match run(cli, output) {
+        Ok(code) => process::exit(code),
+        Err(error) => process::exit(output.error(&error)),
+    }
+}
+
+/// Runs a command, returning the exit code.
+fn run(cli: Cli, output: Output) -> Result<i32, Error> {
+    match cli.command {
+        Command::Upload {
+            ref endpoint,
+            ref file,
+            ref metadata,
+            chunk_size,
+            resume,
+            ref state_file,
+        } => {
+            let client = create_client(&cli, chunk_size, output == Output::Human)?;
+            let metadata = metadata.iter().cloned().collect();
+            let state_file = match state_file {
+                Some(state_file) => state_file.clone(),
+                None => ResumeState::default_path(),
+            };
+            upload(
+                &client,
+                output,
+                endpoint,
+                file,
+                metadata,
+                resume,
+                &state_file,
+            )?;
+            Ok(0)
+        }
+        Command::Batch {
+            ref endpoint,
+            ref sources,
+            ref metadata,
+            chunk_size,
+            concurrency,
+            ref manifest,
+            ref report,
+        } => {
+            let client = create_client(&cli, chunk_size, false)?;
+            let batch = Batch {
+                endpoint: endpoint.clone(),
+                sources: sources.clone(),
+                metadata: metadata.iter().cloned().collect(),
+                concurrency,
+                manifest: manifest.clone(),
+            };
+            let summary = batch.run(client, output == Output::Human)?;
+
+            let json = serde_json::to_value(&summary).map_err(io::Error::from)?;
+            if let Some(report) = report {
+                std::fs::write(
+                    report,
+                    serde_json::to_vec_pretty(&json).map_err(io::Error::from)?,
+                )?;
+            }
+            let mut text = format!(
+                "Uploaded {} files ({} bytes), skipped {} uploaded earlier, {} failed",
+                summary.completed, summary.bytes_uploaded, summary.skipped, summary.failed
+            );
+            for file in summary.files.iter().filter(|file| file.error.is_some()) {
+                text.push_str(&format!(
+                    "\n  {}: {}",
+                    file.path,
+                    file.error.as_deref().unwrap_or_default()
+                ));
+            }
+            output.print(&text, json);
+
+            Ok(if summary.failed > 0 {
+                output::BATCH_FAILED
+            } else {
+                0
+            })
+        }
+        Command::Watch {
+            ref endpoint,
+            ref directory,
+            ref metadata,
+            chunk_size,
+            stable_for,
+            ref move_to,
+            delete,
+            ref state_file,
+        } => {
+            let client = create_client(&cli, chunk_size, false)?;
+            let mut config = WatchConfig::new(endpoint, directory);
+            config.metadata = metadata.iter().cloned().collect();
+            config.stable_for = Duration::from_secs_f64(stable_for.max(0.0));
+            config.after_upload = match move_to {
+                Some(move_to) => AfterUpload::MoveTo(move_to.clone()),
+                None if delete => AfterUpload::Delete,
+                None => AfterUpload::Keep,
+            };
+            if let Some(state_file) = state_file {
+                config.state_file = state_file.clone();
+            }
+
+            Watcher::new(client, config)?.run(|event| match event {
+                WatchEvent::Uploading { path } => output.print(
+                    &format!("Uploading {}", path.display()),
+                    json!({ "event": "uploading", "path": path }),
+                ),
+                WatchEvent::Uploaded { path, upload_url } => output.print(
+                    &format!("Uploaded {} to {}", path.display(), upload_url),
+                    json!({ "event": "uploaded", "path": path, "url": upload_url }),
+                ),
+                WatchEvent::Failed { path, error } => output.print(
+                    &format!("Failed to upload {}: {}", path.display(), error),
+                    json!({ "event": "failed", "path": path, "error": error.to_string() }),
+                ),
+            })?;
+            Ok(0)
+        }
+        Command::Info { ref url } => {
+            let info = create_client(&cli, 0, false)?.get_info(url)?;
+            let metadata = info.metadata.unwrap_or_default();
+            let mut text = format!(
+                "Offset: {}\nSize: {}",
+                info.bytes_uploaded,
+                info.total_size
+                    .map_or_else(|| "unknown".to_owned(), |size| size.to_string())
+            );
+            if !metadata.is_empty() {
+                text.push_str("\nMetadata:");
+                for (key, value) in output::sorted(&metadata) {
+                    text.push_str(&format!("\n  {}: {}", key, value));
+                }
+            }
+            output.print(
+                &text,
+                json!({
+                    "url": url,
+                    "offset": info.bytes_uploaded,
+                    "size": info.total_size,
+                    "metadata": metadata,
+                }),
+            );
+            Ok(0)
+        }
+        Command::ServerInfo { ref endpoint } => {
+            let info = create_client(&cli, 0, false)?.get_server_info(endpoint)?;
+            let extensions: Vec<&str> = info.extensions.iter().map(|e| e.as_str()).collect();
+            output.print(
+                &format!(
+                    "Versions: {}\nExtensions: {}\nMaximum size: {}",
+                    info.supported_versions.join(", "),
+                    extensions.join(", "),
+                    info.max_upload_size
+                        .map_or_else(|| "unlimited".to_owned(), |size| size.to_string())
+                ),
+                json!({
+                    "versions": info.supported_versions,
+                    "extensions": extensions,
+                    "max_size": info.max_upload_size,
+                }),
+            );
+            Ok(0)
+        }
+        Command::Delete { ref url } => {
+            create_client(&cli, 0, false)?.delete(url)?;
+            output.print(&format!("Deleted {}", url), json!({ "url": url }));
+            Ok(0)
+        }
+        Command::Download { ref url, ref dest } => {
+            create_client(&cli, 0, false)?.download(url, dest)?;
+            let size = dest.metadata()?.len();
+            output.print(
+                &format!("Downloaded {} bytes to {}", size, dest.display()),
+                json!({ "url": url, "path": dest, "size": size }),
+            );
+            Ok(0)
+        }
+    }
+}
+
+fn create_client(cli: &Cli, chunk_size: usize, show_progress: bool) -> Result<SharedClient, Error> {
+    let mut handler = reqwest::Client::builder();
+    if let Some(timeout) = cli.timeout {
+        handler = handler.timeout(Duration::from_secs(timeout));
+    }
+    let handler = handler
+        .build()
+        .map_err(|e| Error::HttpHandlerError(e.to_string()))?;
+
+    let mut builder =
+        ClientBuilder::new().retry_policy(RetryPolicy::new(cli.retries, Duration::from_secs(1)));
+    if chunk_size > 0 {
+        builder = builder.chunk_size(chunk_size);
+    }
+    for (key, value) in &cli.headers {
+        builder = builder.header(key, value);
+    }
+    if show_progress && io::stderr().is_terminal() {
+        builder = builder.on_progress(|uploaded, total| {
+            eprint!("\rUploaded {} of {} bytes", uploaded, total);
+            let _ = io::stderr().flush();
+        });
+    }
+    Ok(builder.build_shared(handler))
+}
+
+/// Uploads a file, keeping the upload URL in the state file until the upload has finished, so it can be resumed.
+fn upload(
+    client: &SharedClient,
+    output: Output,
+    endpoint: &str,
+    file: &Path,
+    metadata: HashMap<String, String>,
+    resume: bool,
+    state_file: &Path,
+) -> Result<(), Error> {
+    let size = file.metadata()?.len() as usize;
+    let mut state = ResumeState::load(state_file)?;
+    let key = ResumeState::key(endpoint, file)?;
+
+    let resumed_url = match state.get(&key) {
+        Some(url) if resume => match client.get_info(url) {
+            Ok(info) if info.total_size == Some(size) => Some(url.to_owned()),
+            // The upload has expired, or was created for an earlier version of the file
+            Ok(_) | Err(Error::NotFoundError) => None,
+            Err(e) => return Err(e),
+        },
+        _ => None,
+    };
+    let resumed = resumed_url.is_some();
+    let url = match resumed_url {
+        Some(url) => url,
+        None => {
+            client.connect(endpoint)?;
+            let url = client.create_with_metadata(endpoint, file, metadata)?;
+            state.insert(key.clone(), url.clone());
+            state.save()?;
+            url
+        }
+    };
+
+    let result = client.upload(&url, file);
+    if output == Output::Human && io::stderr().is_terminal() {
+        eprintln!();
+    }
+    result?;
+
+    state.remove(&key);
+    state.save()?;
+
+    output.print(
+        &format!(
+            "{} {} bytes to {}",
+            if resumed {
+                "Resumed and uploaded"
+            } else {
+                "Uploaded"
+            },
+            size,
+            url
+        ),
+        json!({ "url": url, "size": size, "resumed": resumed }),
+    );
+    Ok(())
+}
+
+fn parse_header(header: &str) -> Result<(String, String), String> {
+    match header.split_once(':') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_owned(), value.trim().to_owned()))
+        }
+        _ => Err("expected a header like `Name: value`".to_owned()),
+    }
+}
+
+fn parse_metadata(pair: &str) -> Result<(String, String), String> {
+    match pair.split_once('=') {
+        // Keys are separated from values by spaces in the `Upload-Metadata` header, and pairs by commas
+        Some((key, value)) if !key.is_empty() && !key.contains([' ', ',']) => {
+            Ok((key.to_owned(), value.to_owned()))
+        }
+        _ => Err(
+            "expected metadata like `key=value`, where the key has no spaces or commas".to_owned(),
+        ),
+    }
+}
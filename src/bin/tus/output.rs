@@ -0,0 +1,79 @@
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use tus_client::Error;
+
+/// Describes the exit codes, in the help of the command.
+pub const EXIT_CODES: &str = "Exit codes:
+  0  The command succeeded
+  1  An unexpected internal error occurred
+  2  The arguments or the metadata are invalid
+  3  The upload was not found, or has expired
+  4  A local file couldn't be read or written
+  5  The server couldn't be reached
+  6  The server responded with an unexpected status, or an invalid response
+  7  The size or offset of the upload doesn't match the local file, or the file was modified
+  8  The file is larger than the server allows
+  9  The server doesn't support the version of the protocol or an extension
+  10 Some files of a batch failed to upload";
+
+/// The exit code of a batch in which some files failed to upload.
+pub const BATCH_FAILED: i32 = 10;
+
+/// How the results of commands are printed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    Human,
+    Json,
+}
+
+impl Output {
+    /// Prints the result of a command to stdout.
+    pub fn print(&self, text: &str, json: Value) {
+        match self {
+            Output::Human => println!("{}", text),
+            Output::Json => println!("{}", json),
+        }
+    }
+
+    /// Prints an error to stderr, returning the exit code it maps to.
+    pub fn error(&self, error: &Error) -> i32 {
+        let (code, kind) = describe(error);
+        match self {
+            Output::Human => eprintln!("Error: {}", error),
+            Output::Json => eprintln!(
+                "{}",
+                json!({ "error": { "kind": kind, "message": error.to_string(), "exit_code": code } })
+            ),
+        }
+        code
+    }
+}
+
+/// Get the exit code an error maps to, and the name of its kind.
+fn describe(error: &Error) -> (i32, &'static str) {
+    match error {
+        Error::NotFoundError => (3, "not_found"),
+        Error::IoError(_) => (4, "io"),
+        Error::FileReadError => (4, "file_read"),
+        Error::HttpHandlerError(_) => (5, "http"),
+        Error::UnexpectedStatusCode(_) => (6, "unexpected_status_code"),
+        Error::MissingHeader(_) => (6, "missing_header"),
+        Error::ParsingError(_) => (6, "parsing"),
+        Error::UnequalSizeError => (7, "unequal_size"),
+        Error::WrongUploadOffsetError => (7, "wrong_upload_offset"),
+        Error::FileChanged => (7, "file_changed"),
+        Error::DigestMismatch => (7, "digest_mismatch"),
+        Error::InvalidMetadataKey(_) => (2, "invalid_metadata_key"),
+        Error::MetadataTooLarge(_) => (2, "metadata_too_large"),
+        Error::InvalidMetadata(_) => (2, "invalid_metadata"),
+        Error::Panicked(_) => (1, "panicked"),
+        Error::FileTooLarge => (8, "file_too_large"),
+        Error::UnsupportedExtension(_) => (9, "unsupported_extension"),
+        Error::UnsupportedVersion(_) => (9, "unsupported_version"),
+    }
+}
+
+/// Sorts metadata by key, so it's printed in a stable order.
+pub fn sorted(metadata: &HashMap<String, String>) -> BTreeMap<&String, &String> {
+    metadata.iter().collect()
+}
@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tus_client::state;
+
+/// The URLs of unfinished uploads, kept in a JSON file so they can be resumed by a later run.
+pub struct ResumeState {
+    path: PathBuf,
+    uploads: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct StateFile {
+    /// Maps the keys returned by `ResumeState::key` to upload URLs.
+    uploads: HashMap<String, String>,
+}
+
+impl ResumeState {
+    /// Get the default location of the state file, `~/.tus/uploads.json`.
+    pub fn default_path() -> PathBuf {
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        home.join(".tus").join("uploads.json")
+    }
+
+    /// Loads the state file at `path`. A missing file is treated as empty.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(ResumeState {
+            path: path.to_owned(),
+            uploads: state::load::<StateFile>(path)?.uploads,
+        })
+    }
+
+    /// Get the key identifying uploads of `file` to `endpoint`.
+    pub fn key(endpoint: &str, file: &Path) -> io::Result<String> {
+        Ok(format!(
+            "{} {}",
+            endpoint,
+            fs::canonicalize(file)?.display()
+        ))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.uploads.get(key).map(String::as_str)
+    }
+
+    pub fn insert(&mut self, key: String, url: String) {
+        self.uploads.insert(key, url);
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.uploads.remove(key);
+    }
+
+    /// Writes the state file, replacing it atomically so an interrupted write doesn't lose other uploads.
+    pub fn save(&self) -> io::Result<()> {
+        state::save(
+            &self.path,
+            &StateFile {
+                uploads: self.uploads.clone(),
+            },
+        )
+    }
+}
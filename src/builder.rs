@@ -0,0 +1,241 @@
+#[cfg(feature = "fingerprint")]
+use crate::fingerprint::FingerprintMode;
+use crate::http::{Headers, HttpHandler, HttpRequest};
+#[cfg(feature = "integrity")]
+use crate::integrity::DigestAlgorithm;
+use crate::metadata::MetadataBuilder;
+use crate::{Client, SharedClient};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+const DEFAULT_CHUNK_SIZE: usize = 5 * 1024 * 1024;
+
+/// The version of the tus protocol sent in the `Tus-Resumable` header by default.
+const DEFAULT_TUS_VERSION: &str = "1.0.0";
+
+type ProgressHook = dyn Fn(usize, usize) + Send + Sync;
+type RequestHook = dyn Fn(&mut HttpRequest) + Send + Sync;
+
+/// Used to configure and create a `Client`.
+///
+/// ```rust,no_run
+/// # #[cfg(feature = "reqwest")]
+/// # fn main() {
+/// use std::time::Duration;
+/// use tus_client::{ClientBuilder, RetryPolicy};
+///
+/// let client = ClientBuilder::new()
+///     .chunk_size(1024 * 1024)
+///     .header("Authorization", "Bearer abc123")
+///     .timeout(Duration::from_secs(30))
+///     .retry_policy(RetryPolicy::new(5, Duration::from_secs(1)))
+///     .build(reqwest::Client::new());
+/// # }
+/// # #[cfg(not(feature = "reqwest"))]
+/// # fn main() {}
+/// ```
+pub struct ClientBuilder {
+    config: Config,
+}
+
+/// The configuration of a `Client`, shared between all clones of it.
+pub(crate) struct Config {
+    pub(crate) use_method_override: bool,
+    pub(crate) chunk_size: usize,
+    pub(crate) headers: Headers,
+    pub(crate) tus_version: String,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) on_progress: Option<Box<ProgressHook>>,
+    pub(crate) before_request: Option<Box<RequestHook>>,
+    pub(crate) derive_metadata: Option<MetadataBuilder>,
+    #[cfg(feature = "fingerprint")]
+    pub(crate) fingerprint: Option<FingerprintMode>,
+    #[cfg(feature = "integrity")]
+    pub(crate) digest: Option<DigestAlgorithm>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            use_method_override: false,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            headers: Headers::new(),
+            tus_version: DEFAULT_TUS_VERSION.to_owned(),
+            timeout: None,
+            retry_policy: RetryPolicy::default(),
+            on_progress: None,
+            before_request: None,
+            derive_metadata: None,
+            #[cfg(feature = "fingerprint")]
+            fingerprint: None,
+            #[cfg(feature = "integrity")]
+            digest: None,
+        }
+    }
+}
+
+impl ClientBuilder {
+    /// Creates a `ClientBuilder` with the default configuration.
+    pub fn new() -> Self {
+        ClientBuilder {
+            config: Config::default(),
+        }
+    }
+
+    /// Some environments might not support using the HTTP methods `PATCH` and `DELETE`. Enable this to use the `X-HTTP-METHOD-OVERRIDE` header to specify these methods instead.
+    pub fn method_override(mut self, use_method_override: bool) -> Self {
+        self.config.use_method_override = use_method_override;
+        self
+    }
+
+    /// The chunk size used by `upload`. Defaults to 5 MiB.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is `0`.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "The chunk size must be greater than 0");
+        self.config.chunk_size = chunk_size;
+        self
+    }
+
+    /// Adds a header which is sent with every request.
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.config
+            .headers
+            .insert(key.to_lowercase(), value.to_owned());
+        self
+    }
+
+    /// The version of the tus protocol sent in the `Tus-Resumable` header. Defaults to `1.0.0`.
+    pub fn tus_version(mut self, version: &str) -> Self {
+        self.config.tus_version = version.to_owned();
+        self
+    }
+
+    /// The maximum time to wait for the response to each request, passed to the handler in `HttpRequest::timeout`. Requests which time out fail with `Error::HttpHandlerError`, and are retried according to the `RetryPolicy`. By default, requests wait as long as the handler does.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// How failed requests are retried. By default, requests are not retried.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.config.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets a hook which is called with the number of bytes uploaded and the total size of the file, after each chunk has been uploaded.
+    pub fn on_progress(mut self, hook: impl Fn(usize, usize) + Send + Sync + 'static) -> Self {
+        self.config.on_progress = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets a hook which is called with every request before it's passed to the handler. This may be used to modify the request, like adding authentication headers which need to be refreshed.
+    pub fn before_request(
+        mut self,
+        hook: impl Fn(&mut HttpRequest) + Send + Sync + 'static,
+    ) -> Self {
+        self.config.before_request = Some(Box::new(hook));
+        self
+    }
+
+    /// Derives the metadata of each upload from the file being uploaded when it's created, using `builder`. Metadata passed to `create_with_metadata` takes precedence over derived metadata.
+    pub fn derive_metadata(mut self, builder: MetadataBuilder) -> Self {
+        self.config.derive_metadata = Some(builder);
+        self
+    }
+
+    /// Stores the fingerprint of each file in the metadata of its upload when it's created. Before an upload is resumed, and before each chunk, the file is checked against the fingerprint, failing with `Error::FileChanged` if it was modified.
+    ///
+    /// Uploads created with a fingerprint are checked regardless of this setting.
+    #[cfg(feature = "fingerprint")]
+    pub fn fingerprint(mut self, mode: FingerprintMode) -> Self {
+        self.config.fingerprint = Some(mode);
+        self
+    }
+
+    /// Stores the digest of each file in the metadata of its upload when it's created, so the server, or whoever processes the upload, can check the data it received.
+    ///
+    /// Metadata can't be changed once an upload is created, so the whole file is read to compute the digest before the upload starts, and read again by the upload itself. `Client::upload_with_digest` compares the stored digest with the digest of the data it sent, which only notices a file modified in between: use `Client::verify_upload` to verify what the server received.
+    #[cfg(feature = "integrity")]
+    pub fn digest(mut self, algorithm: DigestAlgorithm) -> Self {
+        self.config.digest = Some(algorithm);
+        self
+    }
+
+    /// Creates a `Client` using `http_handler` and the current configuration.
+    pub fn build<'a>(self, http_handler: impl HttpHandler + 'a) -> Client<'a> {
+        Client {
+            config: Arc::new(self.config),
+            http_handler: Arc::new(http_handler),
+            server_info: Arc::new(RwLock::new(HashMap::new())),
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Creates a `SharedClient` using `http_handler` and the current configuration.
+    pub fn build_shared(
+        self,
+        http_handler: impl HttpHandler + Send + Sync + 'static,
+    ) -> SharedClient {
+        self.build_from_arc(Arc::new(http_handler))
+    }
+
+    /// Creates a `SharedClient` using a handler which is also used elsewhere, like a server which is inspected after uploading to it.
+    pub fn build_from_arc(self, http_handler: Arc<dyn HttpHandler + Send + Sync>) -> SharedClient {
+        Client {
+            config: Arc::new(self.config),
+            http_handler,
+            server_info: Arc::new(RwLock::new(HashMap::new())),
+            _lifetime: PhantomData,
+        }
+    }
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder::new()
+    }
+}
+
+/// Describes how a `Client` retries failed requests.
+///
+/// A request is retried when the handler returns an error, or the server responds with `423 Locked`, `429 Too Many Requests` or a `5xx` status code. Interrupted uploads are resumed from the offset reported by the server. Requests creating uploads aren't retried, as the upload may have been created even though the request failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of consecutive retries.
+    pub max_retries: usize,
+    /// The delay before the first retry. The delay is doubled for each consecutive retry.
+    pub initial_delay: Duration,
+    /// The upper limit of the delay between retries.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a `RetryPolicy` which retries up to `max_retries` times, starting with a delay of `initial_delay`.
+    pub fn new(max_retries: usize, initial_delay: Duration) -> Self {
+        RetryPolicy {
+            max_retries,
+            initial_delay,
+            max_delay: Duration::from_secs(60),
+        }
+    }
+
+    /// The delay before the given retry, starting at `0`.
+    pub(crate) fn delay(&self, retry: usize) -> Duration {
+        let factor = 1_u32.checked_shl(retry as u32).unwrap_or(u32::MAX);
+        self.initial_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(0, Duration::from_secs(1))
+    }
+}
@@ -0,0 +1,99 @@
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// The checksum algorithms supported when verifying the `Upload-Checksum` header, as advertised in the `Tus-Checksum-Algorithm` header.
+pub(crate) const SUPPORTED_ALGORITHMS: &str = "sha1,sha256";
+
+/// Enumerates the reasons an `Upload-Checksum` header can't be verified.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ChecksumError {
+    /// The header is malformed, or specifies an unsupported algorithm.
+    BadRequest,
+    /// The checksum doesn't match the received data.
+    Mismatch,
+}
+
+/// A checksum parsed from an `Upload-Checksum` header, which is computed incrementally over the received data.
+pub(crate) struct Checksum {
+    hasher: Hasher,
+    expected: Vec<u8>,
+}
+
+enum Hasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Checksum {
+    /// Parses the value of an `Upload-Checksum` header, which is the name of the algorithm and the base64 encoded checksum separated by a space.
+    pub(crate) fn parse(header: &str) -> Result<Self, ChecksumError> {
+        let mut parts = header.trim().splitn(2, ' ');
+        let hasher = match parts.next().map(str::to_lowercase).as_deref() {
+            Some("sha1") => Hasher::Sha1(Sha1::new()),
+            Some("sha256") => Hasher::Sha256(Sha256::new()),
+            _ => return Err(ChecksumError::BadRequest),
+        };
+        let expected = parts
+            .next()
+            .and_then(|checksum| base64::decode(checksum.trim()).ok())
+            .ok_or(ChecksumError::BadRequest)?;
+
+        Ok(Checksum { hasher, expected })
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match &mut self.hasher {
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    pub(crate) fn verify(self) -> Result<(), ChecksumError> {
+        let actual = match self.hasher {
+            Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+        };
+
+        if actual == self.expected {
+            Ok(())
+        } else {
+            Err(ChecksumError::Mismatch)
+        }
+    }
+}
+
+/// The algorithm of the `Repr-Digest` header, which is sent for finished uploads when it's requested with a `Want-Repr-Digest` header.
+const REPR_DIGEST_ALGORITHM: &str = "sha-256";
+
+/// Whether the value of a `Want-Repr-Digest` header asks for a digest computed with the supported algorithm. A preference of 0 means the algorithm isn't wanted.
+pub(crate) fn wants_repr_digest(header: &str) -> bool {
+    header.split(',').any(|member| {
+        member
+            .split_once('=')
+            .is_some_and(|(algorithm, preference)| {
+                algorithm.trim().eq_ignore_ascii_case(REPR_DIGEST_ALGORITHM)
+                    && preference.trim() != "0"
+            })
+    })
+}
+
+/// Computes the value of the `Repr-Digest` header of an upload incrementally over its data.
+pub(crate) struct ReprDigest(Sha256);
+
+impl ReprDigest {
+    pub(crate) fn new() -> Self {
+        ReprDigest(Sha256::new())
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub(crate) fn finish(self) -> String {
+        format!(
+            "{}=:{}:",
+            REPR_DIGEST_ALGORITHM,
+            base64::encode(&self.0.finalize()[..])
+        )
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{Error, UploadInfo};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// The metadata key the fingerprint of a file is stored under, when its upload is created.
+pub const FINGERPRINT_KEY: &str = "fingerprint";
+
+/// The size of the beginning and the end of a file hashed by `FingerprintMode::Sampled`.
+const SAMPLE_SIZE: u64 = 1024 * 1024;
+
+/// What the fingerprint of a file consists of, in addition to its size, modification time and inode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FingerprintMode {
+    /// Nothing else. This is cheap, but doesn't notice changes which keep the modification time, like copies which preserve it.
+    Metadata,
+    /// A SHA-256 hash of the first and the last MiB of the file.
+    Sampled,
+    /// A SHA-256 hash of the whole file. The whole file is read when the upload is created, and again before it's resumed.
+    Full,
+}
+
+/// Identifies the contents of a local file, to notice when it's modified while it's being uploaded.
+///
+/// The fingerprint is stored in the metadata of the upload, encoded like `size=1024;mtime=1700000000000000000;inode=42;sha256=...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// The size of the file.
+    pub size: u64,
+    /// The modification time of the file, in nanoseconds since the Unix epoch, if the platform supports it.
+    pub modified: Option<u128>,
+    /// The inode of the file, on Unix platforms.
+    pub inode: Option<u64>,
+    /// What the fingerprint consists of.
+    pub mode: FingerprintMode,
+    /// The hex encoded hash of the file, unless the mode is `FingerprintMode::Metadata`.
+    pub hash: Option<String>,
+}
+
+impl Fingerprint {
+    /// Computes the fingerprint of the file at `path`.
+    pub fn compute(path: &Path, mode: FingerprintMode) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let (size, modified, inode) = describe(&metadata);
+        let hash = match mode {
+            FingerprintMode::Metadata => None,
+            FingerprintMode::Sampled => Some(hash(path, size, true)?),
+            FingerprintMode::Full => Some(hash(path, size, false)?),
+        };
+        Ok(Fingerprint {
+            size,
+            modified,
+            inode,
+            mode,
+            hash,
+        })
+    }
+
+    /// Parses a fingerprint encoded by its `Display` implementation. Returns `None` if it's malformed.
+    pub fn parse(data: &str) -> Option<Self> {
+        let mut fingerprint = Fingerprint {
+            size: 0,
+            modified: None,
+            inode: None,
+            mode: FingerprintMode::Metadata,
+            hash: None,
+        };
+        let mut has_size = false;
+        for pair in data.split(';') {
+            let (key, value) = pair.trim().split_once('=')?;
+            match key {
+                "size" => {
+                    fingerprint.size = value.parse().ok()?;
+                    has_size = true;
+                }
+                "mtime" => fingerprint.modified = Some(value.parse().ok()?),
+                "inode" => fingerprint.inode = Some(value.parse().ok()?),
+                "sampled-sha256" => {
+                    fingerprint.mode = FingerprintMode::Sampled;
+                    fingerprint.hash = Some(value.to_owned());
+                }
+                "sha256" => {
+                    fingerprint.mode = FingerprintMode::Full;
+                    fingerprint.hash = Some(value.to_owned());
+                }
+                // Fields added by later versions are ignored
+                _ => {}
+            }
+        }
+        if has_size {
+            Some(fingerprint)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the size, modification time and inode of the file at `path` still match the fingerprint. This doesn't read the file.
+    pub fn matches_metadata(&self, path: &Path) -> io::Result<bool> {
+        let (size, modified, inode) = describe(&fs::metadata(path)?);
+        Ok(size == self.size && modified == self.modified && inode == self.inode)
+    }
+
+    /// Whether the file at `path` still matches the fingerprint, hashing it if the fingerprint includes a hash.
+    pub fn matches(&self, path: &Path) -> io::Result<bool> {
+        Ok(self.matches_metadata(path)? && Fingerprint::compute(path, self.mode)? == *self)
+    }
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "size={}", self.size)?;
+        if let Some(modified) = self.modified {
+            write!(f, ";mtime={}", modified)?;
+        }
+        if let Some(inode) = self.inode {
+            write!(f, ";inode={}", inode)?;
+        }
+        match (&self.hash, self.mode) {
+            (Some(hash), FingerprintMode::Sampled) => write!(f, ";sampled-sha256={}", hash),
+            (Some(hash), FingerprintMode::Full) => write!(f, ";sha256={}", hash),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Adds the fingerprint of the file at `path` to the metadata of its upload.
+pub(crate) fn add_to_metadata(
+    metadata: &mut HashMap<String, String>,
+    path: &Path,
+    mode: FingerprintMode,
+) -> io::Result<()> {
+    let fingerprint = Fingerprint::compute(path, mode)?;
+    metadata.insert(FINGERPRINT_KEY.to_owned(), fingerprint.to_string());
+    Ok(())
+}
+
+/// Verifies the file at `path` against the fingerprint stored in the metadata of its upload, if it has one. The file is only hashed if part of it has been uploaded.
+///
+/// Returns the fingerprint, so the file can be checked again before each chunk.
+pub(crate) fn verify(info: &UploadInfo, path: &Path) -> Result<Option<Fingerprint>, Error> {
+    let fingerprint = match info
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.get(FINGERPRINT_KEY))
+        .and_then(|data| Fingerprint::parse(data))
+    {
+        Some(fingerprint) => fingerprint,
+        None => return Ok(None),
+    };
+
+    let matches = if info.bytes_uploaded > 0 {
+        fingerprint.matches(path)?
+    } else {
+        fingerprint.matches_metadata(path)?
+    };
+    if matches {
+        Ok(Some(fingerprint))
+    } else {
+        Err(Error::FileChanged)
+    }
+}
+
+/// Get the size, modification time and inode of a file.
+fn describe(metadata: &Metadata) -> (u64, Option<u128>, Option<u64>) {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_nanos());
+    #[cfg(unix)]
+    let inode = {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.ino())
+    };
+    #[cfg(not(unix))]
+    let inode = None;
+    (metadata.len(), modified, inode)
+}
+
+/// Hashes a file, or only its first and last `SAMPLE_SIZE` bytes if `sampled` is set.
+fn hash(path: &Path, size: u64, sampled: bool) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    if sampled && size > 2 * SAMPLE_SIZE {
+        io::copy(&mut (&mut file).take(SAMPLE_SIZE), &mut hasher)?;
+        file.seek(SeekFrom::Start(size - SAMPLE_SIZE))?;
+        io::copy(&mut file.take(SAMPLE_SIZE), &mut hasher)?;
+    } else {
+        io::copy(&mut file.take(size), &mut hasher)?;
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
@@ -19,14 +19,60 @@ pub const TUS_MAX_SIZE: &'static str = "tus-max-size";
 /// Use this header if its environment does not support the PATCH or DELETE methods.
 pub const X_HTTP_METHOD_OVERRIDE: &'static str = "x-http-method-override";
 
-/// Use this header if its environment does not support the PATCH or DELETE methods.
+/// The media type of the request body.
 pub const CONTENT_TYPE: &'static str = "content-type";
 
-/// Use this header if its environment does not support the PATCH or DELETE methods.
-//pub const UPLOAD_DEFER_LENGTH: &'static str = "upload-defer-length";
+/// The range of bytes requested by a `GET` request.
+pub const RANGE: &'static str = "range";
 
-/// Use this header if its environment does not support the PATCH or DELETE methods.
+/// The range of bytes contained in a partial response, and the size of the entire resource.
+pub const CONTENT_RANGE: &'static str = "content-range";
+
+/// The size of the body of a message.
+#[cfg(any(feature = "server", feature = "testing"))]
+pub const CONTENT_LENGTH: &'static str = "content-length";
+
+/// Suggests how a downloaded resource is presented, and the name of the file to save it as.
+#[cfg(feature = "server")]
+pub const CONTENT_DISPOSITION: &'static str = "content-disposition";
+
+/// Indicates that the size of an upload isn't known when it's created, and will be declared later with `Upload-Length`.
+pub const UPLOAD_DEFER_LENGTH: &'static str = "upload-defer-length";
+
+/// Comma-separated key-value pairs, where the values are base64 encoded.
 pub const UPLOAD_METADATA: &'static str = "upload-metadata";
 
-/// Use this header if its environment does not support the PATCH or DELETE methods.
+/// The URL of a created resource.
 pub const LOCATION: &'static str = "location";
+
+/// The algorithm and base64 encoded checksum of the request body.
+#[cfg(any(feature = "server", feature = "testing"))]
+pub const UPLOAD_CHECKSUM: &'static str = "upload-checksum";
+
+/// The algorithms a client wants the digest of a finished upload computed with, and their preference, as described by RFC 9530.
+#[cfg(any(feature = "integrity", feature = "server", feature = "testing"))]
+pub const WANT_REPR_DIGEST: &'static str = "want-repr-digest";
+
+/// The digest of the entire data of a finished upload, as described by RFC 9530.
+#[cfg(any(feature = "integrity", feature = "server", feature = "testing"))]
+pub const REPR_DIGEST: &'static str = "repr-digest";
+
+/// A comma-separated list of the checksum algorithms supported by the server.
+#[cfg(any(feature = "server", feature = "testing"))]
+pub const TUS_CHECKSUM_ALGORITHM: &'static str = "tus-checksum-algorithm";
+
+/// The time after which an unfinished upload expires, in the format described by RFC 7231.
+#[cfg(any(feature = "server", feature = "testing"))]
+pub const UPLOAD_EXPIRES: &'static str = "upload-expires";
+
+/// Marks an upload as either a partial upload, or a final upload consisting of partial uploads.
+#[cfg(any(feature = "server", feature = "testing"))]
+pub const UPLOAD_CONCAT: &'static str = "upload-concat";
+
+/// The credentials authenticating a request.
+#[cfg(feature = "record")]
+pub const AUTHORIZATION: &'static str = "authorization";
+
+/// The cookies sent with a request.
+#[cfg(feature = "record")]
+pub const COOKIE: &'static str = "cookie";
@@ -1,18 +1,22 @@
 use crate::Error;
 use std::collections::HashMap;
 use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+use std::time::Duration;
 
 /// An alias for `HashMap<String, String>`, which represents a set of HTTP headers and their values.
 pub type Headers = HashMap<String, String>;
 
 /// Enumerates the HTTP methods used by `tus_client::Client`.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum HttpMethod {
     Head,
     Patch,
     Options,
     Post,
     Delete,
+    Get,
 }
 
 impl fmt::Display for HttpMethod {
@@ -21,13 +25,32 @@ impl fmt::Display for HttpMethod {
     }
 }
 
+impl FromStr for HttpMethod {
+    type Err = ();
+
+    /// Parses the name of an HTTP method, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "HEAD" => Ok(HttpMethod::Head),
+            "PATCH" => Ok(HttpMethod::Patch),
+            "OPTIONS" => Ok(HttpMethod::Options),
+            "POST" => Ok(HttpMethod::Post),
+            "DELETE" => Ok(HttpMethod::Delete),
+            "GET" => Ok(HttpMethod::Get),
+            _ => Err(()),
+        }
+    }
+}
+
 /// Represents an HTTP request to be executed by the handler.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HttpRequest<'a> {
     pub method: HttpMethod,
     pub headers: Headers,
     pub url: String,
     pub body: Option<&'a [u8]>,
+    /// The maximum duration the handler should wait for a response, if set.
+    pub timeout: Option<Duration>,
 }
 
 /// Represents an HTTP response from the server.
@@ -37,9 +60,23 @@ pub struct HttpResponse {
     pub status_code: usize,
 }
 
+/// The body of a response, which is read as it's received.
+pub type ResponseBody<'a> = Box<dyn Read + 'a>;
+
 /// The required trait used by `tus_client::Client` to represent a handler to execute `HttpRequest`s.
 pub trait HttpHandler {
     fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error>;
+
+    /// Executes a request whose response body is needed, like a `GET` request downloading an upload. The body is returned as a reader, so it doesn't have to fit in memory.
+    ///
+    /// `handle_request` may discard response bodies, so it's enough for uploading. The default implementation fails, so `Client::download` requires a handler which implements this.
+    fn handle_download(&self, req: HttpRequest) -> Result<(HttpResponse, ResponseBody<'_>), Error> {
+        Err(Error::HttpHandlerError(format!(
+            "The handler can't read the response body of {} {}",
+            req.method.to_string().to_uppercase(),
+            req.url
+        )))
+    }
 }
 
 /// Returns the default headers required to make requests to an tus enabled endpoint.
@@ -0,0 +1,229 @@
+use crate::{Error, UploadInfo};
+use sha2::{Digest as _, Sha256};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// The metadata key the digest of a file is stored under, when its upload is created.
+pub const DIGEST_KEY: &str = "digest";
+
+/// The hash algorithms a `Digest` can be computed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    /// SHA-256, which is widely supported by servers.
+    Sha256,
+    /// BLAKE3, which is considerably faster than SHA-256.
+    Blake3,
+}
+
+impl DigestAlgorithm {
+    /// The name of the algorithm, as used in the metadata of an upload and in the `Upload-Checksum` header.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha256 => "sha256",
+            DigestAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// The name of the algorithm in the `Repr-Digest` and `Want-Repr-Digest` headers, as described by RFC 9530.
+    pub(crate) fn http_name(self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha256 => "sha-256",
+            DigestAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "sha256" => Some(DigestAlgorithm::Sha256),
+            "blake3" => Some(DigestAlgorithm::Blake3),
+            _ => None,
+        }
+    }
+}
+
+/// The hash of the entire contents of a file. When it matches the digest of an upload computed by the server, checked with `Client::verify_upload`, the upload is identical to the file.
+///
+/// The digest is stored in the metadata of the upload like the `Upload-Checksum` header, as the name of the algorithm and the base64 encoded hash separated by a space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    /// The algorithm the hash was computed with.
+    pub algorithm: DigestAlgorithm,
+    /// The hash.
+    pub value: Vec<u8>,
+}
+
+impl Digest {
+    /// Computes the digest of the file at `path`, reading all of it.
+    pub fn compute(path: &Path, algorithm: DigestAlgorithm) -> io::Result<Self> {
+        let mut hasher = Hasher::new(algorithm);
+        io::copy(&mut File::open(path)?, &mut hasher)?;
+        Ok(hasher.finalize())
+    }
+
+    /// Parses a digest encoded by its `Display` implementation. Returns `None` if it's malformed, or uses an unsupported algorithm.
+    pub fn parse(data: &str) -> Option<Self> {
+        let (algorithm, value) = data.trim().split_once(' ')?;
+        Some(Digest {
+            algorithm: DigestAlgorithm::parse(algorithm)?,
+            value: base64::decode(value.trim()).ok()?,
+        })
+    }
+
+    /// Finds the digest computed with `algorithm` in the value of a `Repr-Digest` header, like `sha-256=:<base64>:`.
+    pub(crate) fn from_repr_digest(header: &str, algorithm: DigestAlgorithm) -> Option<Self> {
+        header.split(',').find_map(|member| {
+            let (name, value) = member.split_once('=')?;
+            if !name.trim().eq_ignore_ascii_case(algorithm.http_name()) {
+                return None;
+            }
+            let value = value.trim().strip_prefix(':')?.strip_suffix(':')?;
+            Some(Digest {
+                algorithm,
+                value: base64::decode(value).ok()?,
+            })
+        })
+    }
+}
+
+impl Display for Digest {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{} {}",
+            self.algorithm.as_str(),
+            base64::encode(&self.value)
+        )
+    }
+}
+
+/// Computes a digest incrementally.
+enum Hasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn new(algorithm: DigestAlgorithm) -> Self {
+        match algorithm {
+            DigestAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            DigestAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    fn finalize(self) -> Digest {
+        match self {
+            Hasher::Sha256(hasher) => Digest {
+                algorithm: DigestAlgorithm::Sha256,
+                value: hasher.finalize().to_vec(),
+            },
+            Hasher::Blake3(hasher) => Digest {
+                algorithm: DigestAlgorithm::Blake3,
+                value: hasher.finalize().as_bytes().to_vec(),
+            },
+        }
+    }
+}
+
+impl Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hashes a file while it's uploaded, from the chunks read for the upload.
+///
+/// Chunks may start after the part hashed so far, when an upload is resumed, in which case the skipped bytes are read from the file. Chunks sent again after an error are only hashed once.
+pub(crate) struct StreamingDigest {
+    hasher: Hasher,
+    file: File,
+    hashed: u64,
+}
+
+impl StreamingDigest {
+    pub(crate) fn new(path: &Path, algorithm: DigestAlgorithm) -> io::Result<Self> {
+        Ok(StreamingDigest {
+            hasher: Hasher::new(algorithm),
+            file: File::open(path)?,
+            hashed: 0,
+        })
+    }
+
+    /// Hashes a chunk of the file, starting at `offset`.
+    pub(crate) fn update(&mut self, offset: usize, chunk: &[u8]) -> io::Result<()> {
+        let offset = offset as u64;
+        self.hash_file_until(offset)?;
+        let end = offset + chunk.len() as u64;
+        if end > self.hashed {
+            self.hasher
+                .update(&chunk[(self.hashed - offset) as usize..]);
+            self.hashed = end;
+        }
+        Ok(())
+    }
+
+    /// Hashes the rest of a file of `len` bytes, and returns its digest.
+    pub(crate) fn finish(mut self, len: u64) -> io::Result<Digest> {
+        self.hash_file_until(len)?;
+        Ok(self.hasher.finalize())
+    }
+
+    /// Reads and hashes the file up to `offset`, if it hasn't been hashed that far.
+    fn hash_file_until(&mut self, offset: u64) -> io::Result<()> {
+        if offset <= self.hashed {
+            return Ok(());
+        }
+        self.file.seek(SeekFrom::Start(self.hashed))?;
+        let copied = io::copy(
+            &mut (&mut self.file).take(offset - self.hashed),
+            &mut self.hasher,
+        )?;
+        self.hashed += copied;
+        if self.hashed < offset {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+}
+
+/// Adds the digest of the file at `path` to the metadata of its upload.
+pub(crate) fn add_to_metadata(
+    metadata: &mut HashMap<String, String>,
+    path: &Path,
+    algorithm: DigestAlgorithm,
+) -> io::Result<()> {
+    let digest = Digest::compute(path, algorithm)?;
+    metadata.insert(DIGEST_KEY.to_owned(), digest.to_string());
+    Ok(())
+}
+
+/// Compares `digest` with the digest stored in the metadata of the upload, if it has one computed with the same algorithm.
+pub(crate) fn verify(info: &UploadInfo, digest: &Digest) -> Result<(), Error> {
+    let stored = info
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.get(DIGEST_KEY))
+        .and_then(|data| Digest::parse(data));
+    match stored {
+        Some(stored) if stored.algorithm == digest.algorithm && stored != *digest => {
+            Err(Error::DigestMismatch)
+        }
+        _ => Ok(()),
+    }
+}
@@ -14,7 +14,10 @@
 //!
 //! ## Usage
 //!
-//! ```rust
+//! ```rust,no_run
+//! # #[cfg(feature = "reqwest")]
+//! # fn main() {
+//! use std::path::Path;
 //! use tus_client::Client;
 //! use reqwest;
 //!
@@ -28,7 +31,7 @@
 //! // If an upload URL is provided for you, you can skip this step.
 //!
 //! let upload_url = client
-//! .create("https://my.tus.server/files/", "/path/to/file")
+//! .create("https://my.tus.server/files/", Path::new("/path/to/file"))
 //! .expect("Failed to create file on server");
 //!
 //! // Next, you can start uploading the file by calling `upload`.
@@ -36,62 +39,165 @@
 //! // To customize the chunk size, use `upload_with_chunk_size` instead of `upload`.
 //!
 //! client
-//! .upload(&upload_url, "/path/to/file")
+//! .upload(&upload_url, Path::new("/path/to/file"))
 //! .expect("Failed to upload file to server");
+//! # }
+//! # #[cfg(not(feature = "reqwest"))]
+//! # fn main() {}
 //! ```
 //!
 //! `upload` (and `upload_with_chunk_size`) will automatically resume the upload from where it left off, if the upload transfer is interrupted.
+//!
+//! ## Sharing a client between threads
+//!
+//! `Client` doesn't require the handler to be `Send` or `Sync`, and can therefore not be shared between threads. If the handler is `Send + Sync` (like `reqwest::Client`), use `Client::new_shared` to create a `SharedClient` instead. Clones of a `SharedClient` share the same handler, and thus any connection pooling it does.
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "reqwest")]
+//! # fn main() {
+//! use std::path::Path;
+//! use std::thread;
+//! use tus_client::Client;
+//!
+//! let client = Client::new_shared(reqwest::Client::new());
+//!
+//! let worker = {
+//!     let client = client.clone();
+//!     thread::spawn(move || client.upload("https://my.tus.server/files/abc", Path::new("/path/to/file")))
+//! };
+//! # }
+//! # #[cfg(not(feature = "reqwest"))]
+//! # fn main() {}
+//! ```
 #![doc(html_root_url = "https://docs.rs/tus_client/0.1.1")]
-use crate::http::{default_headers, Headers, HttpHandler, HttpMethod, HttpRequest};
+use crate::builder::Config;
+use crate::http::{Headers, HttpHandler, HttpMethod, HttpRequest, HttpResponse, ResponseBody};
 use std::collections::HashMap;
+use std::convert::Infallible;
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter};
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io;
 use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
 use std::num::ParseIntError;
 use std::ops::Deref;
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
+mod builder;
+#[cfg(any(feature = "server", feature = "testing"))]
+mod checksum;
+/// Contains the `Fingerprint` of a local file, which is stored in the metadata of its upload to notice when the file is modified. Requires the `fingerprint` feature.
+#[cfg(feature = "fingerprint")]
+pub mod fingerprint;
+#[allow(clippy::redundant_static_lifetimes)]
 mod headers;
 /// Contains the `HttpHandler` trait and related structs. This module is only relevant when implement `HttpHandler` manually.
 pub mod http;
+/// Contains the `Digest` of a local file, which `Client::verify_upload` compares with the digest of its upload computed by the server. Requires the `integrity` feature.
+#[cfg(feature = "integrity")]
+pub mod integrity;
+/// Contains the `UploadManager`, which uploads many files concurrently.
+pub mod manager;
+/// Contains the `MetadataBuilder`, which derives the metadata of an upload from the file being uploaded.
+pub mod metadata;
+/// Contains handlers which record requests and responses to a file, and replay them later. Requires the `record` feature.
+#[cfg(feature = "record")]
+pub mod record;
+/// Contains an embedded tus server, which stores uploads using a `DataStore`. Requires the `server` feature.
+#[cfg(feature = "server")]
+pub mod server;
+/// Reads and writes the JSON state files of a `Watcher` and of the `tus` binary. Not part of the public API.
+#[cfg(feature = "watch")]
+#[doc(hidden)]
+pub mod state;
+/// Contains an in-memory tus server and a handler injecting faults, for testing code using a `Client` without a network. Requires the `testing` feature.
+#[cfg(feature = "testing")]
+pub mod testing;
+/// Contains the `Watcher`, which uploads the files appearing in a directory. Requires the `watch` feature.
+#[cfg(feature = "watch")]
+pub mod watch;
 
 #[cfg(feature = "reqwest")]
 mod reqwest;
 
-const DEFAULT_CHUNK_SIZE: usize = 5 * 1024 * 1024;
+pub use crate::builder::{ClientBuilder, RetryPolicy};
 
 /// Used to interact with a [tus](https://tus.io) endpoint.
-pub struct Client<'a> {
-    use_method_override: bool,
-    http_handler: Box<dyn HttpHandler + 'a>,
+///
+/// The handler type defaults to `dyn HttpHandler`, which places no thread safety requirements on the handler. See `SharedClient` for a variant which can be shared between threads.
+pub struct Client<'a, H: ?Sized = dyn HttpHandler + 'a> {
+    config: Arc<Config>,
+    http_handler: Arc<H>,
+    server_info: Arc<RwLock<HashMap<String, ServerInfo>>>,
+    _lifetime: PhantomData<&'a ()>,
 }
 
+/// A `Client` which is `Send + Sync`, and can be shared between threads.
+///
+/// Cloning a `SharedClient` is cheap, as the clones share the same handler and configuration.
+pub type SharedClient = Client<'static, dyn HttpHandler + Send + Sync>;
+
 impl<'a> Client<'a> {
     /// Instantiates a new instance of `Client`. `http_handler` needs to implement the `HttpHandler` trait.
     /// A default implementation of this trait for the `reqwest` library is available by enabling the `reqwest` feature.
+    ///
+    /// Use `ClientBuilder` to create a `Client` with a custom configuration.
     pub fn new(http_handler: impl HttpHandler + 'a) -> Self {
-        Client {
-            use_method_override: false,
-            http_handler: Box::new(http_handler),
-        }
+        ClientBuilder::new().build(http_handler)
     }
 
     /// Some environments might not support using the HTTP methods `PATCH` and `DELETE`. Use this method to create a `Client` which uses the `X-HTTP-METHOD-OVERRIDE` header to specify these methods instead.
     pub fn with_method_override(http_handler: impl HttpHandler + 'a) -> Self {
+        ClientBuilder::new()
+            .method_override(true)
+            .build(http_handler)
+    }
+}
+
+impl SharedClient {
+    /// Instantiates a new instance of `SharedClient`. `http_handler` needs to implement the `HttpHandler` trait, and be `Send + Sync`.
+    pub fn new_shared(http_handler: impl HttpHandler + Send + Sync + 'static) -> Self {
+        ClientBuilder::new().build_shared(http_handler)
+    }
+
+    /// Same as `new_shared`, but uses a handler which is also used elsewhere.
+    pub fn from_arc(http_handler: Arc<dyn HttpHandler + Send + Sync>) -> Self {
+        ClientBuilder::new().build_from_arc(http_handler)
+    }
+
+    /// Same as `with_method_override`, but creates a `SharedClient`.
+    pub fn shared_with_method_override(
+        http_handler: impl HttpHandler + Send + Sync + 'static,
+    ) -> Self {
+        ClientBuilder::new()
+            .method_override(true)
+            .build_shared(http_handler)
+    }
+}
+
+impl<'a, H: ?Sized> Clone for Client<'a, H> {
+    fn clone(&self) -> Self {
         Client {
-            use_method_override: true,
-            http_handler: Box::new(http_handler),
+            config: Arc::clone(&self.config),
+            http_handler: Arc::clone(&self.http_handler),
+            server_info: Arc::clone(&self.server_info),
+            _lifetime: PhantomData,
         }
     }
+}
 
+impl<'a, H: HttpHandler + ?Sized> Client<'a, H> {
     /// Get info about a file on the server.
     pub fn get_info(&self, url: &str) -> Result<UploadInfo, Error> {
-        let req = self.create_request(HttpMethod::Head, url, None, Some(default_headers()));
+        let req = self.create_request(HttpMethod::Head, url, None, Some(self.default_headers()));
 
-        let response = self.http_handler.deref().handle_request(req)?;
+        let response = self.send(req)?;
 
         let bytes_uploaded = response.headers.get_by_key(headers::UPLOAD_OFFSET);
         let total_size = response
@@ -101,22 +207,7 @@ impl<'a> Client<'a> {
         let metadata = response
             .headers
             .get_by_key(headers::UPLOAD_METADATA)
-            .and_then(|data| base64::decode(data).ok())
-            .map(|decoded| {
-                String::from_utf8(decoded).unwrap().split(';').fold(
-                    HashMap::new(),
-                    |mut acc, key_val| {
-                        let mut parts = key_val.splitn(2, ':');
-                        if let Some(key) = parts.next() {
-                            acc.insert(
-                                String::from(key),
-                                String::from(parts.next().unwrap_or_default()),
-                            );
-                        }
-                        acc
-                    },
-                )
-            });
+            .and_then(|data| metadata::decode(data));
 
         if response.status_code.to_string().starts_with('4') || bytes_uploaded.is_none() {
             return Err(Error::NotFoundError);
@@ -131,9 +222,20 @@ impl<'a> Client<'a> {
         })
     }
 
-    /// Upload a file to the specified upload URL.
+    /// Whether the upload at `url` can be resumed with the file at `path`, as it still exists on the server and has the size of the file.
+    ///
+    /// Returns `false` if the upload doesn't exist, so a new upload can be created instead. Other errors, like an unreachable server, are returned.
+    pub fn can_resume(&self, url: &str, path: &Path) -> Result<bool, Error> {
+        match self.get_info(url) {
+            Ok(info) => Ok(info.total_size == Some(path.metadata()?.len() as usize)),
+            Err(Error::NotFoundError) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Upload a file to the specified upload URL, using the configured chunk size.
     pub fn upload(&self, url: &str, path: &Path) -> Result<(), Error> {
-        self.upload_with_chunk_size(url, path, DEFAULT_CHUNK_SIZE)
+        self.upload_with_chunk_size(url, path, self.config.chunk_size)
     }
 
     /// Upload a file to the specified upload URL with the given chunk size.
@@ -143,6 +245,78 @@ impl<'a> Client<'a> {
         path: &Path,
         chunk_size: usize,
     ) -> Result<(), Error> {
+        self.upload_with_progress(url, path, chunk_size, &mut |_, _| true, &mut |_, _| Ok(()))
+            .map(|_| ())
+    }
+
+    /// Upload a file to the specified upload URL, computing the digest of the whole file while it's uploaded. Returns the digest, which can be passed to `verify_upload`.
+    ///
+    /// When resuming an upload, the part of the file which was already uploaded is read and hashed too. Once the upload is complete, the digest is compared with the one stored in its metadata by `ClientBuilder::digest`, if it has one computed with `algorithm`, failing with `Error::DigestMismatch` if the file was modified since the upload was created. Both digests are computed from the local file, so this doesn't verify what the server received; `verify_upload` does.
+    #[cfg(feature = "integrity")]
+    pub fn upload_with_digest(
+        &self,
+        url: &str,
+        path: &Path,
+        algorithm: integrity::DigestAlgorithm,
+    ) -> Result<integrity::Digest, Error> {
+        let mut digest = integrity::StreamingDigest::new(path, algorithm)?;
+        self.upload_with_progress(
+            url,
+            path,
+            self.config.chunk_size,
+            &mut |_, _| true,
+            &mut |offset, chunk| digest.update(offset, chunk),
+        )?;
+
+        let info = self.get_info(url)?;
+        let digest = digest.finish(info.bytes_uploaded as u64)?;
+        integrity::verify(&info, &digest)?;
+        Ok(digest)
+    }
+
+    /// Verifies that a finished upload is identical to the file with `digest`, by requesting the digest of the upload from the server with the `Want-Repr-Digest` header described by RFC 9530.
+    ///
+    /// Fails with `Error::DigestMismatch` if the digests differ, and with `Error::MissingHeader` if the server doesn't send a `Repr-Digest` header computed with the algorithm of `digest`.
+    #[cfg(feature = "integrity")]
+    pub fn verify_upload(&self, url: &str, digest: &integrity::Digest) -> Result<(), Error> {
+        let mut headers = self.default_headers();
+        headers.insert(
+            headers::WANT_REPR_DIGEST.to_owned(),
+            format!("{}=10", digest.algorithm.http_name()),
+        );
+        let req = self.create_request(HttpMethod::Head, url, None, Some(headers));
+
+        let response = self.send(req)?;
+
+        if response.status_code.to_string().starts_with('4') {
+            return Err(Error::NotFoundError);
+        }
+
+        let server_digest = response
+            .headers
+            .get_by_key(headers::REPR_DIGEST)
+            .and_then(|header| integrity::Digest::from_repr_digest(header, digest.algorithm))
+            .ok_or_else(|| Error::MissingHeader(headers::REPR_DIGEST.to_owned()))?;
+        if server_digest == *digest {
+            Ok(())
+        } else {
+            Err(Error::DigestMismatch)
+        }
+    }
+
+    /// Does the actual uploading for `upload_with_chunk_size`. `on_progress` is called with the number of bytes uploaded and the total size of the file before each chunk. If it returns `false` the upload is stopped, and `Ok(false)` is returned.
+    ///
+    /// `on_read` is called with the offset and the data of each chunk read from the file, before it's sent. Chunks are read again when resending them after an error.
+    pub(crate) fn upload_with_progress(
+        &self,
+        url: &str,
+        path: &Path,
+        chunk_size: usize,
+        on_progress: &mut dyn FnMut(usize, usize) -> bool,
+        on_read: &mut dyn FnMut(usize, &[u8]) -> io::Result<()>,
+    ) -> Result<bool, Error> {
+        self.check_file_size(url, path)?;
+
         let info = self.get_info(url)?;
         let file = File::open(path)?;
         let file_len = file.metadata()?.len();
@@ -152,78 +326,196 @@ impl<'a> Client<'a> {
                 return Err(Error::UnequalSizeError);
             }
         }
+        #[cfg(feature = "fingerprint")]
+        let fingerprint = fingerprint::verify(&info, path)?;
 
         let mut reader = BufReader::new(&file);
         let mut buffer = vec![0; chunk_size];
         let mut progress = info.bytes_uploaded;
+        let mut retry = 0;
 
         reader.seek(SeekFrom::Start(progress as u64))?;
 
-        loop {
-            let bytes_read = reader.read(&mut buffer)?;
+        // The upload may already be complete, when resuming an upload which was interrupted after its last chunk
+        while progress < file_len as usize {
+            if !on_progress(progress, file_len as usize) {
+                return Ok(false);
+            }
+
+            #[cfg(feature = "fingerprint")]
+            if let Some(fingerprint) = &fingerprint {
+                if !fingerprint.matches_metadata(path)? {
+                    return Err(Error::FileChanged);
+                }
+            }
+
+            let bytes_read = read_chunk(&mut reader, &mut buffer)?;
             if bytes_read == 0 {
                 return Err(Error::FileReadError);
             }
+            on_read(progress, &buffer[..bytes_read])?;
 
             let req = self.create_request(
                 HttpMethod::Patch,
                 url,
                 Some(&buffer[..bytes_read]),
-                Some(create_upload_headers(progress)),
+                Some(self.create_upload_headers(progress)),
             );
 
-            let response = self.http_handler.deref().handle_request(req)?;
-
-            if response.status_code == 409 {
-                return Err(Error::WrongUploadOffsetError);
+            match self.send_once(req).and_then(parse_upload_response) {
+                Ok(upload_offset) => {
+                    progress = upload_offset;
+                    retry = 0;
+                }
+                Err(error) => {
+                    progress = self.resume_after(url, error, &mut retry)?.bytes_uploaded;
+                    reader.seek(SeekFrom::Start(progress as u64))?;
+                    continue;
+                }
             }
 
-            if response.status_code == 404 {
-                return Err(Error::NotFoundError);
+            if let Some(hook) = &self.config.on_progress {
+                hook(progress, file_len as usize);
             }
+        }
+
+        on_progress(progress, file_len as usize);
 
-            if response.status_code != 204 {
-                return Err(Error::UnexpectedStatusCode(response.status_code));
+        Ok(true)
+    }
+
+    /// Upload a file while it's being written, to an upload created with `create_with_deferred_length`. Returns the final size of the file.
+    ///
+    /// New data is uploaded as it's appended to the file. Once `options.finished` is set, or the file hasn't grown for `options.idle_timeout`, the rest of the file is uploaded and its size is declared to the server, which finishes the upload. Fails with `Error::UnequalSizeError` if the file shrinks below what has been uploaded.
+    pub fn upload_growing(
+        &self,
+        url: &str,
+        path: &Path,
+        options: &TailOptions,
+    ) -> Result<usize, Error> {
+        let info = self.get_info(url)?;
+        // The size was declared by an earlier, interrupted call
+        if let Some(total_size) = info.total_size {
+            self.upload(url, path)?;
+            return Ok(total_size);
+        }
+
+        let mut file = File::open(path)?;
+        let mut buffer = vec![0; self.config.chunk_size];
+        let mut progress = info.bytes_uploaded;
+        let mut retry = 0;
+        let mut last_len = 0;
+        let mut last_growth = Instant::now();
+
+        loop {
+            // Checked before the size of the file, so everything written before the file was marked finished is uploaded
+            let finished = options.finished.load(Ordering::SeqCst);
+            let file_len = file.metadata()?.len() as usize;
+            if file_len < progress {
+                return Err(Error::UnequalSizeError);
+            }
+            if file_len != last_len {
+                last_len = file_len;
+                last_growth = Instant::now();
             }
 
-            let upload_offset = match response.headers.get_by_key(headers::UPLOAD_OFFSET) {
-                Some(offset) => Ok(offset),
-                None => Err(Error::MissingHeader(headers::UPLOAD_OFFSET.to_owned())),
-            }?;
+            let (bytes_read, length) = if file_len > progress {
+                file.seek(SeekFrom::Start(progress as u64))?;
+                let chunk_len = buffer.len().min(file_len - progress);
+                (read_chunk(&mut file, &mut buffer[..chunk_len])?, None)
+            } else if finished
+                || options
+                    .idle_timeout
+                    .is_some_and(|idle_timeout| last_growth.elapsed() >= idle_timeout)
+            {
+                (0, Some(progress))
+            } else {
+                thread::sleep(options.poll_interval);
+                continue;
+            };
+
+            let mut headers = self.create_upload_headers(progress);
+            if let Some(length) = length {
+                headers.insert(headers::UPLOAD_LENGTH.to_owned(), length.to_string());
+            }
+            let req = self.create_request(
+                HttpMethod::Patch,
+                url,
+                Some(&buffer[..bytes_read]),
+                Some(headers),
+            );
 
-            progress = upload_offset.parse()?;
+            match self.send_once(req).and_then(parse_upload_response) {
+                Ok(upload_offset) => {
+                    progress = upload_offset;
+                    retry = 0;
+                    if length.is_some() {
+                        return Ok(progress);
+                    }
+                }
+                Err(error) => {
+                    let info = self.resume_after(url, error, &mut retry)?;
+                    // The size was declared, even though the response was lost
+                    if let Some(total_size) = info.total_size {
+                        if info.bytes_uploaded == total_size {
+                            return Ok(total_size);
+                        }
+                    }
+                    progress = info.bytes_uploaded;
+                    continue;
+                }
+            }
 
-            if progress >= file_len as usize {
-                break;
+            if let Some(hook) = &self.config.on_progress {
+                hook(progress, file_len);
             }
         }
+    }
 
-        Ok(())
+    /// Waits before resuming an upload after `error`, then gets the current state of the upload from the server. Fails with `error` if it can't be resumed, or the retries of the `RetryPolicy` are used up.
+    ///
+    /// The chunk may have been partially received, so the upload has to be resumed from the offset reported by the server.
+    fn resume_after(
+        &self,
+        url: &str,
+        mut error: Error,
+        retry: &mut usize,
+    ) -> Result<UploadInfo, Error> {
+        loop {
+            if !error.is_resumable() || *retry >= self.config.retry_policy.max_retries {
+                return Err(error);
+            }
+
+            thread::sleep(self.config.retry_policy.delay(*retry));
+            *retry += 1;
+
+            match self.get_info(url) {
+                Ok(info) => return Ok(info),
+                Err(e) => error = e,
+            }
+        }
     }
 
     /// Get information about the tus server
     pub fn get_server_info(&self, url: &str) -> Result<ServerInfo, Error> {
         let req = self.create_request(HttpMethod::Options, url, None, None);
 
-        let response = self.http_handler.deref().handle_request(req)?;
+        let response = self.send(req)?;
 
         if ![200_usize, 204].contains(&response.status_code) {
             return Err(Error::UnexpectedStatusCode(response.status_code));
         }
 
-        let supported_versions: Vec<String> = response
-            .headers
-            .get_by_key(headers::TUS_VERSION)
-            .unwrap()
-            .split(',')
-            .map(String::from)
-            .collect();
+        let supported_versions: Vec<String> =
+            match response.headers.get_by_key(headers::TUS_VERSION) {
+                Some(versions) => versions.split(',').map(|v| v.trim().to_owned()).collect(),
+                None => return Err(Error::MissingHeader(headers::TUS_VERSION.to_owned())),
+            };
         let extensions: Vec<TusExtension> =
             if let Some(ext) = response.headers.get_by_key(headers::TUS_EXTENSION) {
                 ext.split(',')
-                    .map(str::parse)
-                    .filter(Result::is_ok)
-                    .map(Result::unwrap)
+                    .filter(|e| !e.trim().is_empty())
+                    .filter_map(|e| e.parse().ok())
                     .collect()
             } else {
                 Vec::new()
@@ -240,6 +532,41 @@ impl<'a> Client<'a> {
         })
     }
 
+    /// Get information about the tus server, and verify that it supports the version of the protocol used by the `Client`.
+    ///
+    /// The information is cached for the endpoint at `url`, and used to fail early when calling methods on the endpoint or its uploads which require an extension the server doesn't support, or when a file is larger than the server allows. The cache is shared between clones of the `Client`.
+    pub fn connect(&self, url: &str) -> Result<ServerInfo, Error> {
+        let info = self.get_server_info(url)?;
+
+        if !info
+            .supported_versions
+            .iter()
+            .any(|version| version.trim() == self.config.tus_version)
+        {
+            return Err(Error::UnsupportedVersion(self.config.tus_version.clone()));
+        }
+
+        self.server_info
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(url.to_owned(), info.clone());
+
+        Ok(info)
+    }
+
+    /// Get the information cached by `connect` for the endpoint at `url`, or for the endpoint of the upload at `url`, if it has been called.
+    ///
+    /// The endpoint of an upload is the longest endpoint passed to `connect` which its URL starts with.
+    pub fn server_info(&self, url: &str) -> Option<ServerInfo> {
+        self.server_info
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(endpoint, _)| url.starts_with(endpoint.as_str()))
+            .max_by_key(|(endpoint, _)| endpoint.len())
+            .map(|(_, info)| info.clone())
+    }
+
     /// Create a file on the server, receiving the upload URL of the file.
     pub fn create(&self, url: &str, path: &Path) -> Result<String, Error> {
         self.create_with_metadata(url, path, HashMap::new())
@@ -252,23 +579,50 @@ impl<'a> Client<'a> {
         path: &Path,
         metadata: HashMap<String, String>,
     ) -> Result<String, Error> {
-        let mut headers = default_headers();
+        self.check_extension(url, TusExtension::Creation)?;
+        self.check_file_size(url, path)?;
+        let metadata = match &self.config.derive_metadata {
+            Some(builder) => {
+                let mut derived = builder.build(path)?;
+                derived.extend(metadata);
+                derived
+            }
+            None => metadata,
+        };
+        #[cfg(feature = "fingerprint")]
+        let metadata = {
+            let mut metadata = metadata;
+            if let Some(mode) = self.config.fingerprint {
+                fingerprint::add_to_metadata(&mut metadata, path, mode)?;
+            }
+            metadata
+        };
+        #[cfg(feature = "integrity")]
+        let metadata = {
+            let mut metadata = metadata;
+            if let Some(algorithm) = self.config.digest {
+                integrity::add_to_metadata(&mut metadata, path, algorithm)?;
+            }
+            metadata
+        };
+
+        metadata::validate(&metadata)?;
+
+        let mut headers = self.default_headers();
         headers.insert(
             headers::UPLOAD_LENGTH.to_owned(),
             path.metadata()?.len().to_string(),
         );
         if !metadata.is_empty() {
-            let data = metadata
-                .iter()
-                .map(|(key, value)| format!("{} {}", key, base64::encode(value)))
-                .collect::<Vec<_>>()
-                .join(",");
-            headers.insert(headers::UPLOAD_METADATA.to_owned(), data);
+            headers.insert(
+                headers::UPLOAD_METADATA.to_owned(),
+                metadata::encode(&metadata),
+            );
         }
 
         let req = self.create_request(HttpMethod::Post, url, None, Some(headers));
 
-        let response = self.http_handler.deref().handle_request(req)?;
+        let response = self.send(req)?;
 
         if response.status_code == 413 {
             return Err(Error::FileTooLarge);
@@ -287,11 +641,123 @@ impl<'a> Client<'a> {
         Ok(location.unwrap().to_owned())
     }
 
+    /// Create a file on the server including metadata converted from `metadata`, like a struct deriving `Serialize`, receiving the upload URL of the file. Requires the `typed-metadata` feature.
+    ///
+    /// See `metadata::to_map` for how the value is converted.
+    #[cfg(feature = "typed-metadata")]
+    pub fn create_with_typed_metadata<T: serde::Serialize + ?Sized>(
+        &self,
+        url: &str,
+        path: &Path,
+        metadata: &T,
+    ) -> Result<String, Error> {
+        self.create_with_metadata(url, path, metadata::to_map(metadata)?)
+    }
+
+    /// Create a file on the server without declaring its size, receiving the upload URL of the file. This requires the `creation-defer-length` extension.
+    ///
+    /// Use `upload_growing` to upload a file while it's being written, and declare its size once it's complete.
+    pub fn create_with_deferred_length(
+        &self,
+        url: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<String, Error> {
+        self.check_extension(url, TusExtension::Creation)?;
+        self.check_extension(url, TusExtension::CreationDeferLength)?;
+        metadata::validate(&metadata)?;
+
+        let mut headers = self.default_headers();
+        headers.insert(headers::UPLOAD_DEFER_LENGTH.to_owned(), "1".to_owned());
+        if !metadata.is_empty() {
+            headers.insert(
+                headers::UPLOAD_METADATA.to_owned(),
+                metadata::encode(&metadata),
+            );
+        }
+
+        let req = self.create_request(HttpMethod::Post, url, None, Some(headers));
+
+        let response = self.send(req)?;
+
+        if response.status_code != 201 {
+            return Err(Error::UnexpectedStatusCode(response.status_code));
+        }
+
+        match response.headers.get_by_key(headers::LOCATION) {
+            Some(location) => Ok(location.to_owned()),
+            None => Err(Error::MissingHeader(headers::LOCATION.to_owned())),
+        }
+    }
+
+    /// Download a finished upload to `dest`, verifying its size against the size of the upload.
+    ///
+    /// If `dest` already contains the beginning of the upload, from an interrupted download, only the rest is requested using a `Range` header. The download is also resumed when a response contains less data than requested.
+    pub fn download(&self, url: &str, dest: &Path) -> Result<(), Error> {
+        let total_size = match self.get_info(url)?.total_size {
+            Some(total_size) => total_size,
+            None => return Err(Error::MissingHeader(headers::UPLOAD_LENGTH.to_owned())),
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dest)?;
+        let mut offset = file.metadata()?.len() as usize;
+        if offset > total_size {
+            file.set_len(0)?;
+            offset = 0;
+        }
+        file.seek(SeekFrom::Start(offset as u64))?;
+
+        while offset < total_size {
+            let mut headers = self.default_headers();
+            if offset > 0 {
+                headers.insert(headers::RANGE.to_owned(), format!("bytes={}-", offset));
+            }
+            let req = self.create_request(HttpMethod::Get, url, None, Some(headers));
+
+            let (response, body) = self.send_download(req)?;
+
+            match response.status_code {
+                206 if response
+                    .headers
+                    .get_by_key(headers::CONTENT_RANGE)
+                    .is_some_and(|range| range.starts_with(&format!("bytes {}-", offset))) => {}
+                // The server ignored the range, so the whole upload is sent again
+                200 => {
+                    file.set_len(0)?;
+                    file.seek(SeekFrom::Start(0))?;
+                    offset = 0;
+                }
+                206 => return Err(Error::MissingHeader(headers::CONTENT_RANGE.to_owned())),
+                404 | 410 => return Err(Error::NotFoundError),
+                status_code => return Err(Error::UnexpectedStatusCode(status_code)),
+            }
+
+            // The body is written to the file as it's received. One more byte than expected is read, to notice a response longer than the upload
+            let remaining = (total_size - offset) as u64;
+            let received = io::copy(&mut body.take(remaining + 1), &mut file)?;
+            if received == 0 || received > remaining {
+                return Err(Error::UnequalSizeError);
+            }
+            offset += received as usize;
+        }
+
+        if file.metadata()?.len() as usize != total_size {
+            return Err(Error::UnequalSizeError);
+        }
+
+        Ok(())
+    }
+
     /// Delete a file on the server.
     pub fn delete(&self, url: &str) -> Result<(), Error> {
-        let req = self.create_request(HttpMethod::Delete, url, None, Some(default_headers()));
+        self.check_extension(url, TusExtension::Termination)?;
 
-        let response = self.http_handler.deref().handle_request(req)?;
+        let req = self.create_request(HttpMethod::Delete, url, None, Some(self.default_headers()));
+
+        let response = self.send(req)?;
 
         if response.status_code != 204 {
             return Err(Error::UnexpectedStatusCode(response.status_code));
@@ -307,12 +773,19 @@ impl<'a> Client<'a> {
         body: Option<&'b [u8]>,
         headers: Option<Headers>,
     ) -> HttpRequest<'b> {
-        let mut headers = headers.unwrap_or_default();
+        let mut headers = self
+            .config
+            .headers
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .chain(headers.unwrap_or_default())
+            .collect::<Headers>();
 
-        let method = if self.use_method_override {
+        let method = if self.config.use_method_override {
+            // Servers like tusd compare the method case-sensitively, so it's sent in upper case
             headers.insert(
                 headers::X_HTTP_METHOD_OVERRIDE.to_owned(),
-                method.to_string(),
+                method.to_string().to_uppercase(),
             );
             HttpMethod::Post
         } else {
@@ -324,6 +797,127 @@ impl<'a> Client<'a> {
             url: String::from(url),
             body,
             headers,
+            timeout: self.config.timeout,
+        }
+    }
+
+    /// Sends a request, retrying it according to the configured `RetryPolicy`.
+    fn send(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
+        self.send_with_retries(
+            req,
+            |response| response.status_code,
+            |req| self.send_once(req),
+        )
+    }
+
+    /// Sends a request whose response body is needed, like `send`.
+    fn send_download(&self, req: HttpRequest) -> Result<(HttpResponse, ResponseBody<'_>), Error> {
+        self.send_with_retries(
+            req,
+            |(response, _)| response.status_code,
+            |mut req| {
+                if let Some(hook) = &self.config.before_request {
+                    hook(&mut req);
+                }
+                self.http_handler.deref().handle_download(req)
+            },
+        )
+    }
+
+    fn send_with_retries<'r, T>(
+        &self,
+        req: HttpRequest<'r>,
+        status_code: fn(&T) -> usize,
+        mut handle: impl FnMut(HttpRequest<'r>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        // Retrying a `POST` creating an upload may create it twice. With a method override, every request is a `POST`, and the header holds the actual method
+        let method = req
+            .headers
+            .get_by_key(headers::X_HTTP_METHOD_OVERRIDE)
+            .and_then(|method| method.parse().ok())
+            .unwrap_or(req.method);
+        let is_idempotent = method != HttpMethod::Post;
+        let mut retry = 0;
+        loop {
+            let result = handle(req.clone());
+
+            let should_retry = match &result {
+                Ok(response) => is_retryable_status(status_code(response)),
+                Err(error) => error.is_retryable(),
+            };
+            if !is_idempotent || !should_retry || retry >= self.config.retry_policy.max_retries {
+                return result;
+            }
+
+            thread::sleep(self.config.retry_policy.delay(retry));
+            retry += 1;
+        }
+    }
+
+    fn send_once(&self, mut req: HttpRequest) -> Result<HttpResponse, Error> {
+        if let Some(hook) = &self.config.before_request {
+            hook(&mut req);
+        }
+
+        self.http_handler.deref().handle_request(req)
+    }
+
+    fn check_extension(&self, url: &str, extension: TusExtension) -> Result<(), Error> {
+        match self.server_info(url) {
+            Some(info) if !info.extensions.contains(&extension) => {
+                Err(Error::UnsupportedExtension(extension))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_file_size(&self, url: &str, path: &Path) -> Result<(), Error> {
+        match self.server_info(url).and_then(|info| info.max_upload_size) {
+            Some(max_upload_size) if path.metadata()?.len() as usize > max_upload_size => {
+                Err(Error::FileTooLarge)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn default_headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        headers.insert(
+            headers::TUS_RESUMABLE.to_owned(),
+            self.config.tus_version.clone(),
+        );
+        headers
+    }
+
+    fn create_upload_headers(&self, progress: usize) -> Headers {
+        let mut headers = self.default_headers();
+        headers.insert(
+            headers::CONTENT_TYPE.to_owned(),
+            "application/offset+octet-stream".to_owned(),
+        );
+        headers.insert(headers::UPLOAD_OFFSET.to_owned(), progress.to_string());
+        headers
+    }
+}
+
+/// Controls how `Client::upload_growing` follows a file while it's being written.
+#[derive(Debug, Clone)]
+pub struct TailOptions {
+    /// How long to wait before checking the file again, when all of it has been uploaded.
+    pub poll_interval: Duration,
+    /// The file is considered complete once it hasn't grown for this long. If `None`, only `finished` completes it.
+    pub idle_timeout: Option<Duration>,
+    /// Set this to `true`, for instance from another thread, once the file has been written completely.
+    pub finished: Arc<AtomicBool>,
+}
+
+impl Default for TailOptions {
+    /// Checks the file every 500 milliseconds, and considers it complete once it hasn't grown for 30 seconds.
+    fn default() -> Self {
+        TailOptions {
+            poll_interval: Duration::from_millis(500),
+            idle_timeout: Some(Duration::from_secs(30)),
+            finished: Arc::new(AtomicBool::new(false)),
         }
     }
 }
@@ -339,8 +933,21 @@ pub struct UploadInfo {
     pub metadata: Option<HashMap<String, String>>,
 }
 
+impl UploadInfo {
+    /// Converts the metadata of the upload to a type deriving `Deserialize`. An upload without metadata is treated as having empty metadata. Requires the `typed-metadata` feature.
+    ///
+    /// See `metadata::from_map` for how the metadata is converted.
+    #[cfg(feature = "typed-metadata")]
+    pub fn metadata_as<T: serde::de::DeserializeOwned>(&self) -> Result<T, Error> {
+        match &self.metadata {
+            Some(metadata) => metadata::from_map(metadata),
+            None => metadata::from_map(&HashMap::new()),
+        }
+    }
+}
+
 /// Describes the tus enabled server.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ServerInfo {
     /// The different versions of the tus protocol supported by the server, ordered by preference.
     pub supported_versions: Vec<String>,
@@ -351,32 +958,70 @@ pub struct ServerInfo {
 }
 
 /// Enumerates the extensions to the tus protocol.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TusExtension {
     /// The server supports creating files.
     Creation,
-    //// The server supports setting expiration time on files and uploads.
+    /// The server supports including the first chunk of the file when creating it.
+    CreationWithUpload,
+    /// The server supports creating files before their total size is known.
+    CreationDeferLength,
+    /// The server supports setting expiration time on files and uploads.
     Expiration,
     /// The server supports verifying checksums of uploaded chunks.
     Checksum,
+    /// The server supports sending the checksum of a chunk in a trailer, after the chunk itself.
+    ChecksumTrailer,
     /// The server supports deleting files.
     Termination,
     /// The server supports parallel uploads of a single file.
     Concatenation,
+    /// The server supports concatenating partial uploads before they are finished.
+    ConcatenationUnfinished,
+    /// An extension which is not known by this library. Contains the name advertised by the server.
+    Other(String),
+}
+
+impl TusExtension {
+    /// The name of the extension, as used in the `Tus-Extension` header.
+    pub fn as_str(&self) -> &str {
+        match self {
+            TusExtension::Creation => "creation",
+            TusExtension::CreationWithUpload => "creation-with-upload",
+            TusExtension::CreationDeferLength => "creation-defer-length",
+            TusExtension::Expiration => "expiration",
+            TusExtension::Checksum => "checksum",
+            TusExtension::ChecksumTrailer => "checksum-trailer",
+            TusExtension::Termination => "termination",
+            TusExtension::Concatenation => "concatenation",
+            TusExtension::ConcatenationUnfinished => "concatenation-unfinished",
+            TusExtension::Other(name) => name,
+        }
+    }
 }
 
 impl FromStr for TusExtension {
-    type Err = ();
+    type Err = Infallible;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.trim().to_lowercase().as_str() {
-            "creation" => Ok(TusExtension::Creation),
-            "expiration" => Ok(TusExtension::Expiration),
-            "checksum" => Ok(TusExtension::Checksum),
-            "termination" => Ok(TusExtension::Termination),
-            "concatenation" => Ok(TusExtension::Concatenation),
-            _ => Err(()),
-        }
+        Ok(match s.trim().to_lowercase().as_str() {
+            "creation" => TusExtension::Creation,
+            "creation-with-upload" => TusExtension::CreationWithUpload,
+            "creation-defer-length" => TusExtension::CreationDeferLength,
+            "expiration" => TusExtension::Expiration,
+            "checksum" => TusExtension::Checksum,
+            "checksum-trailer" => TusExtension::ChecksumTrailer,
+            "termination" => TusExtension::Termination,
+            "concatenation" => TusExtension::Concatenation,
+            "concatenation-unfinished" => TusExtension::ConcatenationUnfinished,
+            _ => TusExtension::Other(s.trim().to_owned()),
+        })
+    }
+}
+
+impl Display for TusExtension {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.as_str())
     }
 }
 
@@ -403,6 +1048,22 @@ pub enum Error {
     FileTooLarge,
     /// An error occurred in the HTTP handler.
     HttpHandlerError(String),
+    /// The server doesn't support the extension required by the operation.
+    UnsupportedExtension(TusExtension),
+    /// The server doesn't support the version of the tus protocol used by the `Client`.
+    UnsupportedVersion(String),
+    /// The local file was modified after its upload was created, so the upload can't be resumed with it.
+    FileChanged,
+    /// The digest of the uploaded data doesn't match the digest it was expected to have.
+    DigestMismatch,
+    /// A metadata key is empty, or contains characters which can't be sent in the `Upload-Metadata` header.
+    InvalidMetadataKey(String),
+    /// The encoded metadata is longer than `metadata::MAX_ENCODED_LEN` bytes.
+    MetadataTooLarge(usize),
+    /// Metadata couldn't be converted from or to a typed value.
+    InvalidMetadata(String),
+    /// The upload panicked, for example in the HTTP handler.
+    Panicked(String),
 }
 
 impl Display for Error {
@@ -418,6 +1079,14 @@ impl Display for Error {
             Error::WrongUploadOffsetError => "The client tried to upload the file with an incorrect offset".to_string(),
             Error::FileTooLarge => "The specified file is larger that what is supported by the server".to_string(),
             Error::HttpHandlerError(message) => format!("An error occurred in the HTTP handler: {}", message),
+            Error::UnsupportedExtension(extension) => format!("The server doesn't support the extension required by the operation: {}", extension),
+            Error::UnsupportedVersion(version) => format!("The server doesn't support version {} of the tus protocol", version),
+            Error::FileChanged => "The local file was modified after its upload was created".to_string(),
+            Error::DigestMismatch => "The digest of the uploaded data doesn't match the digest it was expected to have".to_string(),
+            Error::InvalidMetadataKey(key) => format!("The metadata key '{}' is invalid. Keys can't be empty, or contain spaces, commas, control characters or non-ASCII characters", key),
+            Error::MetadataTooLarge(len) => format!("The encoded metadata is {} bytes, which is more than the limit of {} bytes", len, metadata::MAX_ENCODED_LEN),
+            Error::InvalidMetadata(message) => format!("The metadata is invalid: {}", message),
+            Error::Panicked(message) => format!("The upload panicked: {}", message),
         };
 
         write!(f, "{}", message)?;
@@ -428,6 +1097,27 @@ impl Display for Error {
 
 impl StdError for Error {}
 
+impl Error {
+    /// Whether the error may be temporary, meaning the request should be retried.
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::HttpHandlerError(_) | Error::IoError(_) => true,
+            Error::UnexpectedStatusCode(status_code) => is_retryable_status(*status_code),
+            _ => false,
+        }
+    }
+
+    /// Whether an upload may be resumed after the error, by requesting the current offset from the server.
+    fn is_resumable(&self) -> bool {
+        match self {
+            Error::WrongUploadOffsetError | Error::MissingHeader(_) | Error::ParsingError(_) => {
+                true
+            }
+            _ => self.is_retryable(),
+        }
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
         Error::IoError(e)
@@ -452,12 +1142,41 @@ impl HeaderMap for HashMap<String, String> {
     }
 }
 
-fn create_upload_headers(progress: usize) -> Headers {
-    let mut headers = default_headers();
-    headers.insert(
-        headers::CONTENT_TYPE.to_owned(),
-        "application/offset+octet-stream".to_owned(),
-    );
-    headers.insert(headers::UPLOAD_OFFSET.to_owned(), progress.to_string());
-    headers
+fn parse_upload_response(response: HttpResponse) -> Result<usize, Error> {
+    if response.status_code == 409 {
+        return Err(Error::WrongUploadOffsetError);
+    }
+
+    if response.status_code == 404 || response.status_code == 410 {
+        return Err(Error::NotFoundError);
+    }
+
+    if response.status_code != 204 {
+        return Err(Error::UnexpectedStatusCode(response.status_code));
+    }
+
+    let upload_offset = match response.headers.get_by_key(headers::UPLOAD_OFFSET) {
+        Some(offset) => Ok(offset),
+        None => Err(Error::MissingHeader(headers::UPLOAD_OFFSET.to_owned())),
+    }?;
+
+    Ok(upload_offset.parse()?)
+}
+
+/// Reads until `buffer` is full or the end of the file is reached, so every chunk except the last one is of the configured size.
+fn read_chunk(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(bytes_read) => filled += bytes_read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn is_retryable_status(status_code: usize) -> bool {
+    status_code == 423 || status_code == 429 || (500..600).contains(&status_code)
 }
@@ -0,0 +1,411 @@
+use crate::{Error, SharedClient};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+/// Describes a file to be uploaded by an `UploadManager`.
+#[derive(Debug, Clone)]
+pub struct UploadJob {
+    /// The creation endpoint of the tus server.
+    pub endpoint: String,
+    /// The file to upload.
+    pub path: PathBuf,
+    /// Metadata to supply when creating the file on the server.
+    pub metadata: HashMap<String, String>,
+    /// Jobs with a higher priority are started before jobs with a lower priority. Jobs with equal priority are started in the order they were submitted.
+    pub priority: i32,
+    /// The upload URL of an earlier, interrupted upload of the file, which is resumed instead of creating a new upload. A new upload is created if it no longer exists on the server, or has a different size than the file.
+    pub upload_url: Option<String>,
+}
+
+impl UploadJob {
+    /// Creates a job uploading the file at `path` to `endpoint`, with no metadata and a priority of `0`.
+    pub fn new(endpoint: &str, path: impl Into<PathBuf>) -> Self {
+        UploadJob {
+            endpoint: endpoint.to_owned(),
+            path: path.into(),
+            metadata: HashMap::new(),
+            priority: 0,
+            upload_url: None,
+        }
+    }
+}
+
+/// Identifies a job submitted to an `UploadManager`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JobId(u64);
+
+/// The status of a single job.
+#[derive(Debug, Clone)]
+pub enum JobStatus {
+    /// The job is waiting for a free worker.
+    Queued,
+    /// The job is being uploaded.
+    Running {
+        /// The upload URL of the file, once it has been created on the server.
+        upload_url: Option<String>,
+        /// How many bytes have been uploaded.
+        bytes_uploaded: usize,
+        /// The total size of the file.
+        total_size: usize,
+    },
+    /// The file was uploaded successfully.
+    Completed {
+        /// The upload URL of the file.
+        upload_url: String,
+    },
+    /// The upload failed.
+    Failed(Arc<Error>),
+    /// The job was cancelled before it completed.
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Whether the job has stopped, either by completing, failing or being cancelled.
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, JobStatus::Queued | JobStatus::Running { .. })
+    }
+}
+
+/// The aggregated progress of all jobs submitted to an `UploadManager`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    /// How many bytes have been uploaded, across all jobs.
+    pub bytes_uploaded: usize,
+    /// The total size of all submitted files.
+    pub total_size: usize,
+    /// The number of jobs waiting for a free worker.
+    pub queued: usize,
+    /// The number of jobs being uploaded.
+    pub running: usize,
+    /// The number of jobs which completed successfully.
+    pub completed: usize,
+    /// The number of jobs which failed.
+    pub failed: usize,
+    /// The number of jobs which were cancelled.
+    pub cancelled: usize,
+}
+
+/// Uploads files concurrently, using a fixed number of worker threads.
+///
+/// Jobs are queued by priority, and started as soon as a worker is available. Dropping the manager cancels all unfinished jobs, and waits for the workers to stop.
+pub struct UploadManager {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+struct Shared {
+    client: SharedClient,
+    state: Mutex<State>,
+    job_available: Condvar,
+    job_finished: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    queue: BinaryHeap<QueuedJob>,
+    jobs: HashMap<JobId, JobEntry>,
+    next_id: u64,
+    shutdown: bool,
+}
+
+struct JobEntry {
+    status: JobStatus,
+    total_size: usize,
+    bytes_uploaded: usize,
+    cancelled: Arc<AtomicBool>,
+}
+
+struct QueuedJob {
+    id: JobId,
+    job: UploadJob,
+}
+
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // `BinaryHeap` is a max-heap, so the lowest id (the earliest submitted) has to compare as the greatest
+        self.job
+            .priority
+            .cmp(&other.job.priority)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for QueuedJob {}
+
+impl UploadManager {
+    /// Creates a new `UploadManager`, which uploads at most `concurrency` files at once using `client`.
+    pub fn new(client: SharedClient, concurrency: usize) -> Self {
+        let shared = Arc::new(Shared {
+            client,
+            state: Mutex::new(State::default()),
+            job_available: Condvar::new(),
+            job_finished: Condvar::new(),
+        });
+
+        let workers = (0..concurrency.max(1))
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || shared.work())
+            })
+            .collect();
+
+        UploadManager { shared, workers }
+    }
+
+    /// Adds a job to the queue, returning the id used to track it.
+    pub fn submit(&self, job: UploadJob) -> Result<JobId, Error> {
+        let total_size = job.path.metadata()?.len() as usize;
+
+        let mut state = self.shared.lock();
+        let id = JobId(state.next_id);
+        state.next_id += 1;
+        state.jobs.insert(
+            id,
+            JobEntry {
+                status: JobStatus::Queued,
+                total_size,
+                bytes_uploaded: 0,
+                cancelled: Arc::new(AtomicBool::new(false)),
+            },
+        );
+        state.queue.push(QueuedJob { id, job });
+        drop(state);
+
+        self.shared.job_available.notify_one();
+
+        Ok(id)
+    }
+
+    /// Get the status of a job.
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        self.shared
+            .lock()
+            .jobs
+            .get(&id)
+            .map(|entry| entry.status.clone())
+    }
+
+    /// Get the aggregated progress of all submitted jobs, which haven't been removed.
+    pub fn progress(&self) -> Progress {
+        let state = self.shared.lock();
+        state
+            .jobs
+            .values()
+            .fold(Progress::default(), |mut acc, entry| {
+                acc.bytes_uploaded += entry.bytes_uploaded;
+                acc.total_size += entry.total_size;
+                match entry.status {
+                    JobStatus::Queued => acc.queued += 1,
+                    JobStatus::Running { .. } => acc.running += 1,
+                    JobStatus::Completed { .. } => acc.completed += 1,
+                    JobStatus::Failed(_) => acc.failed += 1,
+                    JobStatus::Cancelled => acc.cancelled += 1,
+                }
+                acc
+            })
+    }
+
+    /// Cancels a job. A queued job will never be started, while a running job is stopped before uploading its next chunk.
+    ///
+    /// Returns `false` if the job doesn't exist, or has already finished.
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut state = self.shared.lock();
+        let entry = match state.jobs.get_mut(&id) {
+            Some(entry) if !entry.status.is_finished() => entry,
+            _ => return false,
+        };
+
+        entry.cancelled.store(true, AtomicOrdering::SeqCst);
+        if let JobStatus::Queued = entry.status {
+            entry.status = JobStatus::Cancelled;
+            drop(state);
+            self.shared.job_finished.notify_all();
+        }
+
+        true
+    }
+
+    /// Removes a finished job, returning its final status. Finished jobs are kept until they're removed, so a long-running manager should remove them once their status has been handled.
+    ///
+    /// Returns `None` if the job doesn't exist, or hasn't finished.
+    pub fn remove(&self, id: JobId) -> Option<JobStatus> {
+        let mut state = self.shared.lock();
+        if !state.jobs.get(&id)?.status.is_finished() {
+            return None;
+        }
+        state.jobs.remove(&id).map(|entry| entry.status)
+    }
+
+    /// Removes all finished jobs, returning their final statuses ordered by id.
+    pub fn take_finished(&self) -> Vec<(JobId, JobStatus)> {
+        let mut state = self.shared.lock();
+        let mut finished: Vec<_> = state
+            .jobs
+            .iter()
+            .filter(|(_, entry)| entry.status.is_finished())
+            .map(|(id, _)| *id)
+            .collect();
+        finished.sort();
+        finished
+            .into_iter()
+            .filter_map(|id| state.jobs.remove(&id).map(|entry| (id, entry.status)))
+            .collect()
+    }
+
+    /// Blocks until all submitted jobs have finished.
+    pub fn wait(&self) {
+        let mut state = self.shared.lock();
+        while state.jobs.values().any(|entry| !entry.status.is_finished()) {
+            state = self
+                .shared
+                .job_finished
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl Drop for UploadManager {
+    fn drop(&mut self) {
+        {
+            let mut state = self.shared.lock();
+            state.shutdown = true;
+            for entry in state.jobs.values() {
+                entry.cancelled.store(true, AtomicOrdering::SeqCst);
+            }
+        }
+        self.shared.job_available.notify_all();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn work(&self) {
+        while let Some((id, job, cancelled)) = self.next_job() {
+            // A panic, like one in the handler, fails the job instead of leaving it running forever
+            let result = panic::catch_unwind(AssertUnwindSafe(|| self.run(id, &job, &cancelled)))
+                .unwrap_or_else(|payload| Err(Error::Panicked(panic_message(&*payload))));
+            let status = match result {
+                Ok(Some(upload_url)) => JobStatus::Completed { upload_url },
+                Ok(None) => JobStatus::Cancelled,
+                Err(error) => JobStatus::Failed(Arc::new(error)),
+            };
+
+            if let Some(entry) = self.lock().jobs.get_mut(&id) {
+                entry.status = status;
+            }
+            self.job_finished.notify_all();
+        }
+    }
+
+    fn next_job(&self) -> Option<(JobId, UploadJob, Arc<AtomicBool>)> {
+        let mut state = self.lock();
+        loop {
+            if state.shutdown {
+                return None;
+            }
+
+            match state.queue.pop() {
+                Some(QueuedJob { id, job }) => {
+                    // Cancelled jobs are left in the queue, and skipped here, even if they've been removed
+                    let entry = match state.jobs.get_mut(&id) {
+                        Some(entry) => entry,
+                        None => continue,
+                    };
+                    if let JobStatus::Queued = entry.status {
+                        entry.status = JobStatus::Running {
+                            upload_url: None,
+                            bytes_uploaded: 0,
+                            total_size: entry.total_size,
+                        };
+                        return Some((id, job, Arc::clone(&entry.cancelled)));
+                    }
+                }
+                None => {
+                    state = self
+                        .job_available
+                        .wait(state)
+                        .unwrap_or_else(|e| e.into_inner())
+                }
+            }
+        }
+    }
+
+    fn run(
+        &self,
+        id: JobId,
+        job: &UploadJob,
+        cancelled: &AtomicBool,
+    ) -> Result<Option<String>, Error> {
+        let upload_url = match &job.upload_url {
+            Some(upload_url) if self.client.can_resume(upload_url, &job.path)? => {
+                upload_url.clone()
+            }
+            _ => {
+                self.client
+                    .create_with_metadata(&job.endpoint, &job.path, job.metadata.clone())?
+            }
+        };
+        self.update_running(id, |url, _| *url = Some(upload_url.clone()));
+
+        let completed = self.client.upload_with_progress(
+            &upload_url,
+            &job.path,
+            self.client.config.chunk_size,
+            &mut |bytes_uploaded, _| {
+                self.update_running(id, |_, progress| *progress = bytes_uploaded);
+                !cancelled.load(AtomicOrdering::SeqCst)
+            },
+            &mut |_, _| Ok(()),
+        )?;
+
+        Ok(if completed { Some(upload_url) } else { None })
+    }
+
+    fn update_running(&self, id: JobId, update: impl FnOnce(&mut Option<String>, &mut usize)) {
+        if let Some(entry) = self.lock().jobs.get_mut(&id) {
+            if let JobStatus::Running {
+                upload_url,
+                bytes_uploaded,
+                ..
+            } = &mut entry.status
+            {
+                update(upload_url, bytes_uploaded);
+                entry.bytes_uploaded = *bytes_uploaded;
+            }
+        }
+    }
+}
+
+/// Get the message a thread panicked with.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| (*message).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_owned())
+}
@@ -0,0 +1,341 @@
+use crate::Error;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path};
+use std::time::UNIX_EPOCH;
+
+#[cfg(feature = "typed-metadata")]
+mod typed;
+
+#[cfg(feature = "typed-metadata")]
+pub use self::typed::{from_map, to_map};
+
+/// The maximum length of the encoded `Upload-Metadata` header. Many servers and proxies reject requests with headers longer than 8 KiB.
+pub const MAX_ENCODED_LEN: usize = 8 * 1024;
+
+/// The metadata key of the name of a file, as expected by tusd and Uppy.
+pub const FILENAME_KEY: &str = "filename";
+
+/// The metadata key of the media type of a file, as expected by tusd and Uppy.
+pub const FILETYPE_KEY: &str = "filetype";
+
+/// The metadata key of the modification time of a file, in milliseconds since the Unix epoch like the `lastModified` property of a browser `File`.
+pub const LAST_MODIFIED_KEY: &str = "lastModified";
+
+/// The metadata key of the path of a file relative to the directory it was found in, using `/` as separator, like `nested/b.bin`.
+pub const RELATIVE_PATH_KEY: &str = "relativePath";
+
+/// The metadata key Uppy sends the name of a file under, in addition to `filename`.
+pub const NAME_KEY: &str = "name";
+
+/// The metadata key Uppy sends the media type of a file under, in addition to `filetype`.
+pub const TYPE_KEY: &str = "type";
+
+/// The number of bytes read from the beginning of a file to recognize its media type.
+const SNIFF_LEN: usize = 16;
+
+/// Media types by lowercase file extension.
+const MEDIA_TYPES: &[(&str, &str)] = &[
+    ("7z", "application/x-7z-compressed"),
+    ("avi", "video/x-msvideo"),
+    ("bmp", "image/bmp"),
+    ("csv", "text/csv"),
+    ("doc", "application/msword"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("flac", "audio/flac"),
+    ("gif", "image/gif"),
+    ("gz", "application/gzip"),
+    ("heic", "image/heic"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("m4a", "audio/mp4"),
+    ("md", "text/markdown"),
+    ("mkv", "video/x-matroska"),
+    ("mov", "video/quicktime"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("ogg", "audio/ogg"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("svg", "image/svg+xml"),
+    ("tar", "application/x-tar"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("txt", "text/plain"),
+    ("wav", "audio/wav"),
+    ("webm", "video/webm"),
+    ("webp", "image/webp"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    ("xml", "application/xml"),
+    ("zip", "application/zip"),
+];
+
+/// Media types by the bytes the content of a file starts with.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"BM", "image/bmp"),
+    (b"ID3", "audio/mpeg"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+];
+
+/// Derives the metadata of an upload from the file being uploaded, and merges it with metadata supplied by the caller.
+///
+/// By default `filename`, `filetype` and `lastModified` are derived, which is what tusd, Uppy and most server hooks expect. Metadata supplied with `insert` or `extend` takes precedence over derived metadata. The size of the file isn't included, as it's sent in the `Upload-Length` header.
+///
+/// Pass the builder to `ClientBuilder::derive_metadata` to derive the metadata of every upload created by a `Client`, or call `build` to get the metadata of a single file.
+#[derive(Debug, Clone)]
+pub struct MetadataBuilder {
+    filename: bool,
+    filetype: bool,
+    sniff_content: bool,
+    last_modified: bool,
+    uppy_aliases: bool,
+    metadata: HashMap<String, String>,
+}
+
+impl Default for MetadataBuilder {
+    fn default() -> Self {
+        MetadataBuilder {
+            filename: true,
+            filetype: true,
+            sniff_content: true,
+            last_modified: true,
+            uppy_aliases: false,
+            metadata: HashMap::new(),
+        }
+    }
+}
+
+impl MetadataBuilder {
+    /// Creates a `MetadataBuilder` deriving `filename`, `filetype` and `lastModified`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to derive `filename` from the last component of the path. Names which aren't valid UTF-8 are converted lossily.
+    pub fn filename(mut self, enabled: bool) -> Self {
+        self.filename = enabled;
+        self
+    }
+
+    /// Whether to derive `filetype` from the extension of the file, or from its content if the extension isn't recognized.
+    pub fn filetype(mut self, enabled: bool) -> Self {
+        self.filetype = enabled;
+        self
+    }
+
+    /// Whether to read the beginning of the file to recognize its media type, when its extension isn't recognized. Enabled by default.
+    pub fn sniff_content(mut self, enabled: bool) -> Self {
+        self.sniff_content = enabled;
+        self
+    }
+
+    /// Whether to derive `lastModified` from the modification time of the file.
+    pub fn last_modified(mut self, enabled: bool) -> Self {
+        self.last_modified = enabled;
+        self
+    }
+
+    /// Whether to also send the name and media type of the file as `name` and `type`, like Uppy does. Disabled by default.
+    pub fn uppy_aliases(mut self, enabled: bool) -> Self {
+        self.uppy_aliases = enabled;
+        self
+    }
+
+    /// Adds a metadata value, which replaces a derived value with the same key.
+    pub fn insert(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Adds metadata values, which replace derived values with the same keys.
+    pub fn extend(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata.extend(metadata);
+        self
+    }
+
+    /// Derives the metadata of the file at `path`, and merges it with the supplied metadata.
+    pub fn build(&self, path: &Path) -> io::Result<HashMap<String, String>> {
+        let mut metadata = HashMap::new();
+        let filename = path
+            .file_name()
+            .filter(|_| self.filename)
+            .map(|name| name.to_string_lossy().into_owned());
+        if let Some(filename) = filename {
+            if self.uppy_aliases {
+                metadata.insert(NAME_KEY.to_owned(), filename.clone());
+            }
+            metadata.insert(FILENAME_KEY.to_owned(), filename);
+        }
+        if self.filetype {
+            if let Some(filetype) = media_type(path, self.sniff_content)? {
+                if self.uppy_aliases {
+                    metadata.insert(TYPE_KEY.to_owned(), filetype.to_owned());
+                }
+                metadata.insert(FILETYPE_KEY.to_owned(), filetype.to_owned());
+            }
+        }
+        if self.last_modified {
+            let modified = fs::metadata(path)?
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+            if let Some(modified) = modified {
+                metadata.insert(
+                    LAST_MODIFIED_KEY.to_owned(),
+                    modified.as_millis().to_string(),
+                );
+            }
+        }
+        metadata.extend(self.metadata.clone());
+        Ok(metadata)
+    }
+}
+
+/// Get the media type of a file from its extension, or from the beginning of its content if `sniff_content` is set.
+fn media_type(path: &Path, sniff_content: bool) -> io::Result<Option<&'static str>> {
+    let by_extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase)
+        .and_then(|extension| {
+            MEDIA_TYPES
+                .iter()
+                .find(|(known, _)| *known == extension)
+                .map(|(_, media_type)| *media_type)
+        });
+    if by_extension.is_some() || !sniff_content {
+        return Ok(by_extension);
+    }
+
+    let mut start = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut start)?;
+    Ok(SIGNATURES
+        .iter()
+        .find(|(signature, _)| start.starts_with(signature))
+        .map(|(_, media_type)| *media_type)
+        .or_else(|| sniff_container(&start)))
+}
+
+/// Recognizes formats stored in RIFF and ISO base media containers, whose signatures don't start at the beginning of the file.
+fn sniff_container(start: &[u8]) -> Option<&'static str> {
+    match (start.get(..4), start.get(4..8), start.get(8..12)) {
+        (Some(b"RIFF"), _, Some(b"WEBP")) => Some("image/webp"),
+        (Some(b"RIFF"), _, Some(b"WAVE")) => Some("audio/wav"),
+        (Some(b"RIFF"), _, Some(b"AVI ")) => Some("video/x-msvideo"),
+        (_, Some(b"ftyp"), Some(b"qt  ")) => Some("video/quicktime"),
+        (_, Some(b"ftyp"), Some(b"heic")) => Some("image/heic"),
+        (_, Some(b"ftyp"), _) => Some("video/mp4"),
+        _ => None,
+    }
+}
+
+/// Get the path of `path` relative to `base`, using `/` as separator on every platform, as sent under `RELATIVE_PATH_KEY`. A path outside of `base` is returned whole, without its root.
+///
+/// Shared by `Watcher` and the `tus` binary, and not part of the public API.
+#[doc(hidden)]
+pub fn relative_path(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Checks that metadata can be sent in the `Upload-Metadata` header.
+///
+/// Fails with `Error::InvalidMetadataKey` if a key is empty, or contains spaces, commas, control characters or non-ASCII characters, and with `Error::MetadataTooLarge` if the encoded header is longer than `MAX_ENCODED_LEN`.
+pub fn validate(metadata: &HashMap<String, String>) -> Result<(), Error> {
+    if let Some(key) = metadata.keys().find(|key| !is_valid_key(key)) {
+        return Err(Error::InvalidMetadataKey(key.to_owned()));
+    }
+    let len = encode(metadata).len();
+    if len > MAX_ENCODED_LEN {
+        return Err(Error::MetadataTooLarge(len));
+    }
+    Ok(())
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii() && !c.is_ascii_control() && c != ' ' && c != ',')
+}
+
+/// Encodes metadata as described by the `Upload-Metadata` header: comma separated pairs of a key and a base64 encoded value.
+pub(crate) fn encode(metadata: &HashMap<String, String>) -> String {
+    metadata
+        .iter()
+        .map(|(key, value)| format!("{} {}", key, base64::encode(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Decodes the value of an `Upload-Metadata` header. Returns `None` if the header is malformed.
+pub(crate) fn decode(data: &str) -> Option<HashMap<String, String>> {
+    decode_legacy(data).or_else(|| {
+        data.split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let mut parts = pair.splitn(2, ' ');
+                let key = parts.next()?;
+                let value = match parts.next() {
+                    Some(value) => String::from_utf8(base64::decode(value.trim()).ok()?).ok()?,
+                    None => String::new(),
+                };
+                Some((key.to_owned(), value))
+            })
+            .collect()
+    })
+}
+
+/// Earlier versions of this library expected the whole header to be base64 encoded, containing `key:value` pairs separated by `;`.
+fn decode_legacy(data: &str) -> Option<HashMap<String, String>> {
+    if data.contains(' ') || data.contains(',') {
+        return None;
+    }
+
+    let decoded = String::from_utf8(base64::decode(data).ok()?).ok()?;
+    if !decoded.contains(':') {
+        return None;
+    }
+
+    Some(decoded.split(';').fold(HashMap::new(), |mut acc, key_val| {
+        let mut parts = key_val.splitn(2, ':');
+        if let Some(key) = parts.next() {
+            acc.insert(
+                String::from(key),
+                String::from(parts.next().unwrap_or_default()),
+            );
+        }
+        acc
+    }))
+}
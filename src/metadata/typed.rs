@@ -0,0 +1,130 @@
+use crate::Error;
+use serde::de::value::{Error as DeError, MapDeserializer, StrDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::{forward_to_deserialize_any, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Converts a value, like a struct deriving `Serialize`, to metadata.
+///
+/// Strings are used as they are, while numbers and booleans are formatted. Fields which are `None` are left out. Nested structs, maps and sequences can't be represented, and fail with `Error::InvalidMetadata`. The keys and the size of the metadata are validated like by `validate`.
+pub fn to_map<T: Serialize + ?Sized>(value: &T) -> Result<HashMap<String, String>, Error> {
+    let fields = match serde_json::to_value(value) {
+        Ok(Value::Object(fields)) => fields,
+        Ok(_) => {
+            return Err(Error::InvalidMetadata(
+                "metadata must be serialized as a struct or a map".to_owned(),
+            ))
+        }
+        Err(e) => return Err(Error::InvalidMetadata(e.to_string())),
+    };
+
+    let mut metadata = HashMap::new();
+    for (key, value) in fields {
+        let value = match value {
+            Value::Null => continue,
+            Value::String(value) => value,
+            Value::Bool(value) => value.to_string(),
+            Value::Number(value) => value.to_string(),
+            Value::Array(_) | Value::Object(_) => {
+                return Err(Error::InvalidMetadata(format!(
+                    "the value of '{}' is nested, which metadata can't contain",
+                    key
+                )))
+            }
+        };
+        metadata.insert(key, value);
+    }
+    super::validate(&metadata)?;
+    Ok(metadata)
+}
+
+/// Converts metadata to a type deriving `Deserialize`.
+///
+/// Values are parsed as the type of the field they're deserialized into, so numbers, booleans and unit enum variants are supported. Keys which are missing can be deserialized into `Option` fields. Fails with `Error::InvalidMetadata` if a value can't be parsed, or a required key is missing.
+pub fn from_map<T: DeserializeOwned>(metadata: &HashMap<String, String>) -> Result<T, Error> {
+    let deserializer = MapDeserializer::new(
+        metadata
+            .iter()
+            .map(|(key, value)| (key.as_str(), ValueDeserializer(value))),
+    );
+    T::deserialize(deserializer).map_err(|e: DeError| Error::InvalidMetadata(e.to_string()))
+}
+
+/// Deserializes a single metadata value, parsing it as the type requested.
+struct ValueDeserializer<'m>(&'m str);
+
+impl<'m> ValueDeserializer<'m> {
+    fn parse<T: std::str::FromStr>(&self, expected: &'static str) -> Result<T, DeError> {
+        self.0
+            .parse()
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(self.0), &expected))
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident, $expected:expr;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                visitor.$visit(self.parse($expected)?)
+            }
+        )*
+    };
+}
+
+impl<'de, 'm> de::Deserializer<'de> for ValueDeserializer<'m> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_str(self.0)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool, "a boolean";
+        deserialize_i8 => visit_i8, "an integer";
+        deserialize_i16 => visit_i16, "an integer";
+        deserialize_i32 => visit_i32, "an integer";
+        deserialize_i64 => visit_i64, "an integer";
+        deserialize_u8 => visit_u8, "an unsigned integer";
+        deserialize_u16 => visit_u16, "an unsigned integer";
+        deserialize_u32 => visit_u32, "an unsigned integer";
+        deserialize_u64 => visit_u64, "an unsigned integer";
+        deserialize_f32 => visit_f32, "a number";
+        deserialize_f64 => visit_f64, "a number";
+        deserialize_char => visit_char, "a character";
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        let variant: StrDeserializer<DeError> = self.0.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de, 'm> IntoDeserializer<'de, DeError> for ValueDeserializer<'m> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
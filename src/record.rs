@@ -0,0 +1,303 @@
+use crate::headers;
+use crate::http::{Headers, HttpHandler, HttpMethod, HttpRequest, HttpResponse, ResponseBody};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// A request and the response (or error) it resulted in, as stored in a recording.
+///
+/// A recording is a file with one exchange per line, each serialized as a JSON object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    /// The request passed to the handler.
+    pub request: RecordedRequest,
+    /// The response returned by the handler, if it succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<RecordedResponse>,
+    /// The error returned by the handler, if it failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A recorded `HttpRequest`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    /// The name of the HTTP method, in upper case.
+    pub method: String,
+    /// The URL of the request.
+    pub url: String,
+    /// The headers of the request.
+    pub headers: Headers,
+    /// A digest of the request body, if the request had one. The body itself is not recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<RecordedBody>,
+}
+
+/// Describes a request body, without containing it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedBody {
+    /// The length of the body.
+    pub size: usize,
+    /// The hex encoded SHA-256 hash of the body.
+    pub sha256: String,
+}
+
+/// A recorded `HttpResponse`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    /// The status code of the response.
+    pub status_code: usize,
+    /// The headers of the response.
+    pub headers: Headers,
+}
+
+/// The value recorded in place of the value of a redacted header.
+pub const REDACTED: &str = "[redacted]";
+
+impl RecordedRequest {
+    fn new(req: &HttpRequest) -> Self {
+        RecordedRequest {
+            method: req.method.to_string().to_uppercase(),
+            url: req.url.clone(),
+            headers: req.headers.clone(),
+            body: req.body.map(RecordedBody::new),
+        }
+    }
+
+    /// Get the method of the request, if it's one of the methods used by the `Client`.
+    pub fn http_method(&self) -> Option<HttpMethod> {
+        self.method.parse().ok()
+    }
+}
+
+impl RecordedBody {
+    fn new(body: &[u8]) -> Self {
+        RecordedBody {
+            size: body.len(),
+            sha256: Sha256::digest(body)
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        }
+    }
+}
+
+/// Wraps an `HttpHandler`, recording every request passed to it and the response it returns.
+///
+/// Each exchange is written to the recording as soon as the inner handler returns, so the recording is usable even if the process is interrupted. Request bodies are stored as their size and SHA-256 hash, and the values of the headers set with `redact_headers` are replaced by `REDACTED`.
+///
+/// Failing to write the recording doesn't fail the request, the error is stored and can be retrieved with `take_write_error`.
+pub struct Recorder<H> {
+    inner: H,
+    writer: Mutex<Box<dyn Write + Send>>,
+    redacted_headers: Vec<String>,
+    write_error: Mutex<Option<io::Error>>,
+}
+
+impl<H: HttpHandler> Recorder<H> {
+    /// Creates a `Recorder` wrapping `inner`, which writes the recording to the file at `path`. The file is truncated if it exists.
+    pub fn create(inner: H, path: &Path) -> io::Result<Self> {
+        Ok(Recorder::with_writer(
+            inner,
+            BufWriter::new(File::create(path)?),
+        ))
+    }
+
+    /// Creates a `Recorder` wrapping `inner`, which writes the recording to `writer`.
+    pub fn with_writer(inner: H, writer: impl Write + Send + 'static) -> Self {
+        Recorder {
+            inner,
+            writer: Mutex::new(Box::new(writer)),
+            redacted_headers: vec![
+                headers::AUTHORIZATION.to_owned(),
+                headers::COOKIE.to_owned(),
+            ],
+            write_error: Mutex::new(None),
+        }
+    }
+
+    /// The names of the request and response headers whose values are not recorded. Defaults to `Authorization` and `Cookie`.
+    pub fn redact_headers(mut self, names: &[&str]) -> Self {
+        self.redacted_headers = names.iter().map(|name| name.to_lowercase()).collect();
+        self
+    }
+
+    /// Takes the first error which occurred while writing the recording, if any. Exchanges are still recorded after an error, as far as the writer allows.
+    pub fn take_write_error(&self) -> Option<io::Error> {
+        self.write_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Get the inner handler.
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    fn redact(&self, headers: &Headers) -> Headers {
+        headers
+            .iter()
+            .map(|(key, value)| {
+                if self.redacted_headers.contains(&key.to_lowercase()) {
+                    (key.clone(), REDACTED.to_owned())
+                } else {
+                    (key.clone(), value.clone())
+                }
+            })
+            .collect()
+    }
+
+    fn write(&self, exchange: &Exchange) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        serde_json::to_writer(&mut *writer, exchange)?;
+        writer.write_all(b"\n")?;
+        writer.flush()
+    }
+
+    /// Records an exchange, keeping the error if writing it fails.
+    fn record(&self, req: &HttpRequest, result: Result<&HttpResponse, &Error>) {
+        let mut request = RecordedRequest::new(req);
+        request.headers = self.redact(&request.headers);
+
+        let exchange = match result {
+            Ok(response) => Exchange {
+                request,
+                response: Some(RecordedResponse {
+                    status_code: response.status_code,
+                    headers: self.redact(&response.headers),
+                }),
+                error: None,
+            },
+            Err(error) => Exchange {
+                request,
+                response: None,
+                error: Some(error.to_string()),
+            },
+        };
+        if let Err(error) = self.write(&exchange) {
+            self.write_error
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get_or_insert(error);
+        }
+    }
+}
+
+impl<H: HttpHandler> HttpHandler for Recorder<H> {
+    fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
+        let result = self.inner.handle_request(req.clone());
+        self.record(&req, result.as_ref());
+        result
+    }
+
+    /// Records the exchange like `handle_request`. The response body isn't recorded.
+    fn handle_download(&self, req: HttpRequest) -> Result<(HttpResponse, ResponseBody<'_>), Error> {
+        let result = self.inner.handle_download(req.clone());
+        self.record(&req, result.as_ref().map(|(response, _)| response));
+        result
+    }
+}
+
+/// An `HttpHandler` which replays a recording made by a `Recorder`, without making any network requests.
+///
+/// The recorded exchanges are replayed in order. Each request must have the same method and URL as the recorded request, otherwise an error is returned. Request bodies are only compared when enabled with `verify_bodies`.
+///
+/// Downloads can't be replayed, as response bodies aren't recorded: `handle_download` always returns an error.
+pub struct Replayer {
+    exchanges: Vec<Exchange>,
+    verify_bodies: bool,
+    position: Mutex<usize>,
+}
+
+impl Replayer {
+    /// Creates a `Replayer` from the recording in the file at `path`.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let exchanges = BufReader::new(File::open(path)?)
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect::<io::Result<_>>()?;
+
+        Ok(Replayer::new(exchanges))
+    }
+
+    /// Creates a `Replayer` from the given exchanges.
+    pub fn new(exchanges: Vec<Exchange>) -> Self {
+        Replayer {
+            exchanges,
+            verify_bodies: false,
+            position: Mutex::new(0),
+        }
+    }
+
+    /// Whether requests must have the same body as the recorded requests. Disabled by default, as a new version of the client may split an upload into different chunks.
+    pub fn verify_bodies(mut self, verify_bodies: bool) -> Self {
+        self.verify_bodies = verify_bodies;
+        self
+    }
+
+    /// Get the recorded exchanges.
+    pub fn exchanges(&self) -> &[Exchange] {
+        &self.exchanges
+    }
+
+    /// Get the number of exchanges which have not been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.exchanges.len() - *self.lock()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, usize> {
+        self.position.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl HttpHandler for Replayer {
+    fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
+        let mut position = self.lock();
+        let request = RecordedRequest::new(&req);
+
+        let exchange = self.exchanges.get(*position).ok_or_else(|| {
+            Error::HttpHandlerError(format!(
+                "The recording has no more exchanges, but received {} {}",
+                request.method, request.url
+            ))
+        })?;
+
+        if exchange.request.method != request.method
+            || exchange.request.url != request.url
+            || (self.verify_bodies && exchange.request.body != request.body)
+        {
+            return Err(Error::HttpHandlerError(format!(
+                "Exchange {} of the recording is {} {}, but received {} {}",
+                *position,
+                exchange.request.method,
+                exchange.request.url,
+                request.method,
+                request.url
+            )));
+        }
+
+        *position += 1;
+
+        match (&exchange.response, &exchange.error) {
+            (Some(response), _) => Ok(HttpResponse {
+                status_code: response.status_code,
+                headers: response.headers.clone(),
+            }),
+            (None, error) => Err(Error::HttpHandlerError(error.clone().unwrap_or_default())),
+        }
+    }
+
+    fn handle_download(&self, req: HttpRequest) -> Result<(HttpResponse, ResponseBody<'_>), Error> {
+        Err(Error::HttpHandlerError(format!(
+            "Downloads can't be replayed, as response bodies aren't recorded, but received {} {}",
+            req.method.to_string().to_uppercase(),
+            req.url
+        )))
+    }
+}
@@ -1,46 +1,83 @@
-use crate::http::{HttpHandler, HttpMethod, HttpRequest, HttpResponse};
+use crate::http::{HttpHandler, HttpMethod, HttpRequest, HttpResponse, ResponseBody};
 use crate::Error;
 use reqwest::header::{HeaderMap, HeaderName};
 use reqwest::Method;
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
 
 impl HttpHandler for reqwest::Client {
     fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
-        let mut headers = HeaderMap::new();
-        for (key, value) in req.headers {
-            headers.insert(HeaderName::from_str(&key).unwrap(), value.parse().unwrap());
-        }
+        send(self, req).map(|(response, _)| response)
+    }
 
-        let mut builder = match req.method {
-            HttpMethod::Head => self.head(&req.url),
-            HttpMethod::Patch => self.patch(&req.url),
-            HttpMethod::Options => self.request(Method::OPTIONS, &req.url),
-            HttpMethod::Post => self.post(&req.url),
-            HttpMethod::Delete => self.delete(&req.url),
-        }
-        .headers(headers);
+    fn handle_download(&self, req: HttpRequest) -> Result<(HttpResponse, ResponseBody<'_>), Error> {
+        send(self, req).map(|(response, body)| (response, Box::new(body) as ResponseBody))
+    }
+}
 
-        if let Some(body) = req.body {
-            builder = builder.body(Vec::from(body));
-        }
+/// Sends a request, returning the response with its body still unread.
+fn send(
+    client: &reqwest::Client,
+    req: HttpRequest,
+) -> Result<(HttpResponse, reqwest::Response), Error> {
+    let mut headers = HeaderMap::new();
+    for (key, value) in req.headers {
+        headers.insert(HeaderName::from_str(&key).unwrap(), value.parse().unwrap());
+    }
 
-        let response = match builder.send() {
-            Ok(resp) => resp,
-            Err(err) => return Err(Error::HttpHandlerError(err.to_string())),
-        };
-
-        let mut headers = HashMap::new();
-        for (key, value) in response.headers() {
-            headers.insert(
-                key.to_string(),
-                value.to_str().map(String::from).unwrap_or_default(),
-            );
+    let mut builder = match req.method {
+        HttpMethod::Head => client.head(&req.url),
+        HttpMethod::Patch => client.patch(&req.url),
+        HttpMethod::Options => client.request(Method::OPTIONS, &req.url),
+        HttpMethod::Post => client.post(&req.url),
+        HttpMethod::Delete => client.delete(&req.url),
+        HttpMethod::Get => client.get(&req.url),
+    }
+    .headers(headers);
+
+    if let Some(body) = req.body {
+        builder = builder.body(Vec::from(body));
+    }
+
+    let response = match req.timeout {
+        // The blocking client of reqwest 0.9 only supports a timeout configured on the client itself, so the request is sent on a separate thread, which is left running when the timeout elapses until the client gives up
+        Some(timeout) => {
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let _ = sender.send(builder.send());
+            });
+            let (method, url) = (req.method, &req.url);
+            receiver.recv_timeout(timeout).map_err(|_| {
+                Error::HttpHandlerError(format!(
+                    "No response to {} {} within {:?}",
+                    method.to_string().to_uppercase(),
+                    url,
+                    timeout
+                ))
+            })?
         }
+        None => builder.send(),
+    };
+    let response = match response {
+        Ok(resp) => resp,
+        Err(err) => return Err(Error::HttpHandlerError(err.to_string())),
+    };
 
-        Ok(HttpResponse {
+    let mut headers = HashMap::new();
+    for (key, value) in response.headers() {
+        headers.insert(
+            key.to_string(),
+            value.to_str().map(String::from).unwrap_or_default(),
+        );
+    }
+
+    Ok((
+        HttpResponse {
             status_code: response.status().as_u16() as usize,
             headers,
-        })
-    }
+        },
+        response,
+    ))
 }
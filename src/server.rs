@@ -0,0 +1,864 @@
+use crate::checksum::{self, Checksum};
+use crate::headers;
+use crate::http::{self, Headers, HttpHandler, HttpMethod, HttpRequest, HttpResponse};
+use crate::{metadata, Error, HeaderMap, TusExtension};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+mod cors;
+mod download;
+mod expiration;
+mod file_store;
+mod hooks;
+mod lock;
+#[cfg(feature = "tower")]
+mod service;
+mod store;
+mod tusd;
+
+pub use self::cors::Cors;
+pub use self::expiration::{Collector, ExpirationReport};
+pub use self::file_store::{FileStore, SyncPolicy};
+use self::hooks::{Dispatcher, ErrorHandler};
+pub use self::hooks::{
+    FileHook, Hook, HookEvent, HookRequest, HookResponse, HookType, HttpHook, Rejection,
+};
+pub use self::lock::LockConflict;
+use self::lock::{Locker, UploadLock};
+#[cfg(feature = "tower")]
+pub use self::service::{ShutdownHandle, TusService};
+pub use self::store::{DataStore, FileInfo, MemoryStore};
+
+/// The version of the tus protocol implemented by the server.
+const TUS_VERSION: &str = "1.0.0";
+
+const DEFAULT_BASE_PATH: &str = "/files/";
+
+/// Used to configure and create a `Server`.
+///
+/// ```rust,ignore
+/// use tus_client::server::{MemoryStore, ServerBuilder};
+///
+/// let server = ServerBuilder::new()
+///     .base_path("/uploads/")
+///     .max_size(1024 * 1024 * 1024)
+///     .build(MemoryStore::new());
+/// ```
+pub struct ServerBuilder {
+    config: ServerConfig,
+}
+
+struct ServerConfig {
+    base_path: String,
+    max_size: Option<usize>,
+    lock_conflict: LockConflict,
+    lock_directory: Option<PathBuf>,
+    hooks: Vec<Arc<dyn Hook>>,
+    on_hook_error: Option<Arc<ErrorHandler>>,
+    expiration: Option<Duration>,
+    cors: Option<Cors>,
+    behind_proxy: bool,
+}
+
+impl ServerBuilder {
+    /// Creates a `ServerBuilder` with the default configuration.
+    pub fn new() -> Self {
+        ServerBuilder {
+            config: ServerConfig {
+                base_path: DEFAULT_BASE_PATH.to_owned(),
+                max_size: None,
+                lock_conflict: LockConflict::default(),
+                lock_directory: None,
+                hooks: Vec::new(),
+                on_hook_error: None,
+                expiration: None,
+                cors: None,
+                behind_proxy: false,
+            },
+        }
+    }
+
+    /// The path of the creation endpoint. Uploads are served from URLs below it. Defaults to `/files/`.
+    pub fn base_path(mut self, base_path: &str) -> Self {
+        self.config.base_path = format!("/{}/", base_path.trim_matches('/')).replace("//", "/");
+        self
+    }
+
+    /// The maximum size of an upload, which is advertised in the `Tus-Max-Size` header. Larger uploads are rejected.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.config.max_size = Some(max_size);
+        self
+    }
+
+    /// Unfinished uploads expire when they haven't received any data for the given duration. The time an upload expires is reported in the `Upload-Expires` header. Expired uploads can be deleted with `Server::collect_expired` or a `Collector`.
+    ///
+    /// By default, uploads never expire.
+    pub fn expiration(mut self, expiration: Duration) -> Self {
+        self.config.expiration = Some(expiration);
+        self
+    }
+
+    /// Sends CORS headers, so browsers can make requests to the server. By default, no CORS headers are sent.
+    pub fn cors(mut self, cors: Cors) -> Self {
+        self.config.cors = Some(cors);
+        self
+    }
+
+    /// Trusts the `Forwarded` and `X-Forwarded-*` headers of requests, which a reverse proxy sets to the origin the client sent the request to. The URLs of created uploads then use that origin. By default, the headers are ignored, as any client can send them.
+    ///
+    /// Only enable this when all requests pass through a proxy which sets or removes these headers.
+    pub fn behind_proxy(mut self, behind_proxy: bool) -> Self {
+        self.config.behind_proxy = behind_proxy;
+        self
+    }
+
+    /// What happens when a request needs the lock of an upload which is held by another request. Defaults to `LockConflict::Reject`.
+    ///
+    /// Each `PATCH` and `DELETE` request holds an exclusive lock of its upload, so concurrent requests can't corrupt it.
+    pub fn on_lock_conflict(mut self, lock_conflict: LockConflict) -> Self {
+        self.config.lock_conflict = lock_conflict;
+        self
+    }
+
+    /// Also lock uploads by creating `<id>.lock` files in `directory`. Use this when several processes serve the same uploads, with a store which doesn't cache uploads in memory, like `FileStore`.
+    pub fn lock_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.config.lock_directory = Some(directory.into());
+        self
+    }
+
+    /// Adds a hook which is called at each stage of the lifecycle of an upload. Hooks are called in the order they were added.
+    pub fn hook(mut self, hook: impl Hook + 'static) -> Self {
+        self.config.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Sets a callback which is called with the type of a hook and its error when a hook fails or panics. The errors of hooks other than `HookType::PreCreate` are otherwise lost, as they don't affect the response.
+    pub fn on_hook_error(
+        mut self,
+        on_error: impl Fn(HookType, &io::Error) + Send + Sync + 'static,
+    ) -> Self {
+        self.config.on_hook_error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// Creates a `Server` storing uploads in `store`, using the current configuration.
+    pub fn build<S: DataStore>(self, store: S) -> Server<S> {
+        Server {
+            locker: Locker::new(
+                self.config.lock_conflict,
+                self.config.lock_directory.clone(),
+            ),
+            dispatcher: if self.config.hooks.is_empty() {
+                None
+            } else {
+                Some(Dispatcher::new(
+                    self.config.hooks.clone(),
+                    self.config.on_hook_error.clone(),
+                ))
+            },
+            config: self.config,
+            store,
+            ids: IdGenerator::new(),
+        }
+    }
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        ServerBuilder::new()
+    }
+}
+
+/// A tus server, implementing the core protocol and the Creation, Expiration, Checksum, Termination and Concatenation extensions.
+///
+/// The server is independent of any HTTP framework: it handles an `HttpRequest` and produces a `ServerResponse`. It also implements `HttpHandler`, so a `Client` can use it directly.
+///
+/// ```rust,ignore
+/// use tus_client::server::{MemoryStore, Server};
+///
+/// let server = Server::new(MemoryStore::new());
+/// let response = server.handle(request);
+/// ```
+pub struct Server<S> {
+    config: ServerConfig,
+    store: S,
+    ids: IdGenerator,
+    locker: Locker,
+    dispatcher: Option<Dispatcher>,
+}
+
+/// A response of a `Server`.
+#[derive(Debug)]
+pub struct ServerResponse {
+    /// The headers of the response.
+    pub headers: Headers,
+    /// The status code of the response.
+    pub status_code: usize,
+    /// The body of the response.
+    pub body: ResponseBody,
+}
+
+/// The body of a `ServerResponse`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseBody {
+    /// The response has no body.
+    Empty,
+    /// A body held in memory, like the message of a rejected upload.
+    Bytes(Vec<u8>),
+    /// The data of an upload, served by a `GET` request. Use `Server::read_body` to read it from the store in pieces as it's sent.
+    Upload {
+        /// The id of the upload.
+        id: String,
+        /// The offset of the first byte to send.
+        offset: usize,
+        /// The number of bytes to send.
+        len: usize,
+    },
+}
+
+impl<S: DataStore> Server<S> {
+    /// Creates a `Server` storing uploads in `store`, using the default configuration.
+    pub fn new(store: S) -> Self {
+        ServerBuilder::new().build(store)
+    }
+
+    /// Get the store used by the server.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Handles a request, using its body as the data of a `PATCH` request.
+    ///
+    /// The method may be overridden by the `X-HTTP-Method-Override` header, for environments which only support `GET` and `POST`.
+    pub fn handle(&self, req: HttpRequest) -> ServerResponse {
+        let mut body = req.body.unwrap_or_default();
+        self.handle_with_body(req, &mut body)
+    }
+
+    /// Handles a request, streaming the data of a `PATCH` request from `body` instead of using the body of `req`. The data is written to the store as it's read.
+    pub fn handle_with_body(&self, mut req: HttpRequest, body: &mut dyn Read) -> ServerResponse {
+        if let Some(method) = req.headers.get_by_key(headers::X_HTTP_METHOD_OVERRIDE) {
+            match method.parse() {
+                Ok(method) => req.method = method,
+                Err(()) => return self.finish(&req, Response::new(400)),
+            }
+        }
+
+        let response = match req.method {
+            HttpMethod::Options => self.handle_options(),
+            // Downloads may be made by clients unaware of tus, like browsers
+            HttpMethod::Get => self.handle_get(&req),
+            _ if req
+                .headers
+                .get_by_key(headers::TUS_RESUMABLE)
+                .map(String::as_str)
+                != Some(TUS_VERSION) =>
+            {
+                Response::new(412).header(headers::TUS_VERSION, TUS_VERSION)
+            }
+            HttpMethod::Post => self.handle_post(&req),
+            HttpMethod::Head => self.handle_head(&req),
+            HttpMethod::Patch => self.handle_patch(&req, body),
+            HttpMethod::Delete => self.handle_delete(&req),
+        };
+        self.finish(&req, response)
+    }
+
+    /// Adds the headers sent with every response.
+    fn finish(&self, req: &HttpRequest, mut response: Response) -> ServerResponse {
+        response
+            .headers
+            .insert(headers::TUS_RESUMABLE.to_owned(), TUS_VERSION.to_owned());
+        if let (Some(cors), Some(origin)) = (&self.config.cors, req.headers.get_by_key("origin")) {
+            let is_preflight = req.method == HttpMethod::Options
+                && req
+                    .headers
+                    .get_by_key("access-control-request-method")
+                    .is_some();
+            cors.apply(origin, is_preflight, &mut response.headers);
+        }
+
+        ServerResponse {
+            headers: response.headers,
+            status_code: response.status_code,
+            body: response.body,
+        }
+    }
+
+    fn handle_options(&self) -> Response {
+        let extensions = [
+            TusExtension::Creation,
+            TusExtension::CreationDeferLength,
+            TusExtension::Expiration,
+            TusExtension::Checksum,
+            TusExtension::Termination,
+            TusExtension::Concatenation,
+        ]
+        .iter()
+        .filter(|extension| {
+            **extension != TusExtension::Expiration || self.config.expiration.is_some()
+        })
+        .map(TusExtension::as_str)
+        .collect::<Vec<_>>()
+        .join(",");
+
+        let response = Response::new(204)
+            .header(headers::TUS_VERSION, TUS_VERSION)
+            .header(headers::TUS_EXTENSION, &extensions)
+            .header(
+                headers::TUS_CHECKSUM_ALGORITHM,
+                checksum::SUPPORTED_ALGORITHMS,
+            );
+        match self.config.max_size {
+            Some(max_size) => response.header(headers::TUS_MAX_SIZE, &max_size.to_string()),
+            None => response,
+        }
+    }
+
+    fn handle_post(&self, req: &HttpRequest) -> Response {
+        if self.upload_id(&req.url).is_some() {
+            return Response::new(405);
+        }
+
+        let concat = req
+            .headers
+            .get_by_key(headers::UPLOAD_CONCAT)
+            .map(|c| c.trim());
+        let (size, size_is_deferred, partial_uploads) = match concat {
+            Some(concat) if concat.starts_with("final;") => {
+                match self.find_partial_uploads(&concat["final;".len()..]) {
+                    Ok(partial_uploads) => (
+                        partial_uploads.iter().map(|partial| partial.size).sum(),
+                        false,
+                        Some(partial_uploads),
+                    ),
+                    Err(response) => return response,
+                }
+            }
+            Some(concat) if concat != "partial" => return Response::new(400),
+            _ => match (
+                req.headers.get_by_key(headers::UPLOAD_LENGTH),
+                req.headers
+                    .get_by_key(headers::UPLOAD_DEFER_LENGTH)
+                    .map(|d| d.trim()),
+            ) {
+                (Some(length), None) => match length.parse() {
+                    Ok(size) => (size, false, None),
+                    Err(_) => return Response::new(400),
+                },
+                (None, Some("1")) => (0, true, None),
+                _ => return Response::new(400),
+            },
+        };
+
+        if self.config.max_size.is_some_and(|max_size| size > max_size) {
+            return Response::new(413);
+        }
+
+        let metadata = match req.headers.get_by_key(headers::UPLOAD_METADATA) {
+            Some(data) => match metadata::decode(data) {
+                Some(metadata) => metadata,
+                None => return Response::new(400),
+            },
+            None => HashMap::new(),
+        };
+
+        let mut info = FileInfo {
+            id: self.ids.next(),
+            size,
+            size_is_deferred,
+            offset: 0,
+            metadata,
+            expires: self.expires().filter(|_| partial_uploads.is_none()),
+            is_partial: concat == Some("partial"),
+            partial_uploads: partial_uploads
+                .as_ref()
+                .map(|partial_uploads| partial_uploads.iter().map(|p| p.id.clone()).collect()),
+        };
+        for hook in &self.config.hooks {
+            let event = hook_event(HookType::PreCreate, &info, req);
+            let response = match hooks::call(&**hook, &event, self.config.on_hook_error.as_deref())
+            {
+                Ok(response) => response,
+                Err(_) => return Response::new(500),
+            };
+            if let Some(rejection) = response.rejection {
+                return Response::new(rejection.status_code).body(&rejection.message);
+            }
+            if let Some(id) = response.id {
+                if !is_valid_id(&id) || !matches!(self.store.get(&id), Ok(None)) {
+                    return Response::new(500);
+                }
+                info.id = id;
+            }
+            if let Some(metadata) = response.metadata {
+                info.metadata = metadata;
+            }
+        }
+        let location = self.location(req, &info.id);
+
+        if self.store.create(info.clone()).is_err() {
+            return Response::new(500);
+        }
+        if let Some(partial_ids) = &info.partial_uploads {
+            if self.store.concatenate(&info.id, partial_ids).is_err() {
+                let _ = self.store.delete(&info.id);
+                return Response::new(500);
+            }
+            info.offset = info.size;
+            self.notify(HookType::PostFinish, &info, req);
+        }
+
+        Response::new(201)
+            .header(headers::LOCATION, &location)
+            .expires(&info)
+    }
+
+    fn handle_head(&self, req: &HttpRequest) -> Response {
+        let info = match self.find_upload(&req.url) {
+            Ok(info) => info,
+            Err(response) => return response,
+        };
+
+        let mut response = Response::new(200)
+            .header(headers::UPLOAD_OFFSET, &info.offset.to_string())
+            .header("cache-control", "no-store")
+            .expires(&info);
+        response = if info.size_is_deferred {
+            response.header(headers::UPLOAD_DEFER_LENGTH, "1")
+        } else {
+            response.header(headers::UPLOAD_LENGTH, &info.size.to_string())
+        };
+        if !info.metadata.is_empty() {
+            response = response.header(headers::UPLOAD_METADATA, &metadata::encode(&info.metadata));
+        }
+        if info.is_partial {
+            response = response.header(headers::UPLOAD_CONCAT, "partial");
+        }
+        if let Some(partial_ids) = &info.partial_uploads {
+            let urls = partial_ids
+                .iter()
+                .map(|id| self.location(req, id))
+                .collect::<Vec<_>>();
+            response =
+                response.header(headers::UPLOAD_CONCAT, &format!("final;{}", urls.join(" ")));
+        }
+        let wants_digest = req
+            .headers
+            .get_by_key(headers::WANT_REPR_DIGEST)
+            .is_some_and(|header| checksum::wants_repr_digest(header));
+        if wants_digest && info.is_finished() {
+            response = match self.repr_digest(&info) {
+                Ok(digest) => response.header(headers::REPR_DIGEST, &digest),
+                Err(_) => return Response::new(500),
+            };
+        }
+        response
+    }
+
+    fn handle_patch(&self, req: &HttpRequest, body: &mut dyn Read) -> Response {
+        let lock = match self.lock_upload(&req.url) {
+            Ok(lock) => lock,
+            Err(response) => return response,
+        };
+        let mut info = match self.find_upload(&req.url) {
+            Ok(info) => info,
+            Err(response) => return response,
+        };
+
+        if info.is_final() {
+            return Response::new(403);
+        }
+
+        if req
+            .headers
+            .get_by_key(headers::CONTENT_TYPE)
+            .map(String::as_str)
+            != Some("application/offset+octet-stream")
+        {
+            return Response::new(415);
+        }
+
+        let offset = req
+            .headers
+            .get_by_key(headers::UPLOAD_OFFSET)
+            .and_then(|o| o.parse::<usize>().ok());
+        if offset != Some(info.offset) {
+            return Response::new(409);
+        }
+
+        // A deferred size is declared by any `PATCH` request, which may have no data
+        if let Some(length) = req
+            .headers
+            .get_by_key(headers::UPLOAD_LENGTH)
+            .filter(|_| info.size_is_deferred)
+        {
+            let size = match length.parse::<usize>() {
+                Ok(size) if size >= info.offset => size,
+                _ => return Response::new(400),
+            };
+            if self.config.max_size.is_some_and(|max_size| size > max_size) {
+                return Response::new(413);
+            }
+            info.size = size;
+            info.size_is_deferred = false;
+            if self.store.update(&info).is_err() {
+                return Response::new(500);
+            }
+        }
+
+        let remaining = if info.size_is_deferred {
+            self.config.max_size.unwrap_or(usize::MAX) - info.offset
+        } else {
+            info.size - info.offset
+        };
+        if req.body.is_some_and(|body| body.len() > remaining) {
+            return Response::new(413);
+        }
+
+        let mut checksum = match req.headers.get_by_key(headers::UPLOAD_CHECKSUM) {
+            Some(header) => match Checksum::parse(header) {
+                Ok(checksum) => Some(checksum),
+                Err(_) => return Response::new(400),
+            },
+            None => None,
+        };
+
+        // A streamed body may be longer than announced, so only the remaining bytes are read
+        let mut body = ChecksumReader {
+            body: lock.guard(body).take(remaining as u64),
+            checksum: checksum.as_mut(),
+        };
+        let result = self.store.append(&info.id, &mut body);
+
+        if let Some(checksum) = checksum {
+            // Data which doesn't match the checksum, or couldn't be verified as the request failed, is discarded
+            if result.is_err() || checksum.verify().is_err() {
+                if self.store.truncate(&info.id, info.offset).is_err() {
+                    return Response::new(500);
+                }
+                if result.is_ok() {
+                    return Response::new(460);
+                }
+            }
+        }
+
+        match result {
+            Ok(offset) => {
+                // Receiving data postpones the expiration, until the upload is finished
+                let mut info = FileInfo { offset, ..info };
+                let expires = if info.is_finished() {
+                    None
+                } else {
+                    self.expires()
+                };
+                if expires != info.expires {
+                    info.expires = expires;
+                    if self.store.update(&info).is_err() {
+                        return Response::new(500);
+                    }
+                }
+                self.notify(HookType::PostReceive, &info, req);
+                if info.is_finished() {
+                    self.notify(HookType::PostFinish, &info, req);
+                }
+                Response::new(204)
+                    .header(headers::UPLOAD_OFFSET, &offset.to_string())
+                    .expires(&info)
+            }
+            Err(_) if lock.is_release_requested() => Response::new(400),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Response::new(404),
+            Err(_) => Response::new(500),
+        }
+    }
+
+    fn handle_delete(&self, req: &HttpRequest) -> Response {
+        let lock = match self.lock_upload(&req.url) {
+            Ok(lock) => lock,
+            Err(response) => return response,
+        };
+
+        // Expired uploads can still be deleted
+        let info = match self.store.get(lock.id()) {
+            Ok(Some(info)) => info,
+            Ok(None) => return Response::new(404),
+            Err(_) => return Response::new(500),
+        };
+
+        match self.store.delete(lock.id()) {
+            Ok(true) => {
+                self.notify(HookType::PostTerminate, &info, req);
+                Response::new(204)
+            }
+            Ok(false) => Response::new(404),
+            Err(_) => Response::new(500),
+        }
+    }
+
+    /// The time a new or modified upload expires at.
+    fn expires(&self) -> Option<SystemTime> {
+        self.config
+            .expiration
+            .map(|expiration| SystemTime::now() + expiration)
+    }
+
+    /// Queues an event for the hooks, which are called on the thread of the dispatcher, ignoring their responses.
+    fn notify(&self, hook_type: HookType, info: &FileInfo, req: &HttpRequest) {
+        if let Some(dispatcher) = &self.dispatcher {
+            dispatcher.dispatch(hook_event(hook_type, info, req));
+        }
+    }
+
+    /// Takes the lock of the upload a request URL refers to, or the response to send if it can't be taken.
+    fn lock_upload(&self, url: &str) -> Result<UploadLock<'_>, Response> {
+        let id = self.upload_id(url).ok_or_else(|| Response::new(404))?;
+        match self.locker.lock(id) {
+            Ok(Some(lock)) => Ok(lock),
+            Ok(None) => Err(Response::new(423)),
+            Err(_) => Err(Response::new(500)),
+        }
+    }
+
+    /// Finds the finished partial uploads listed in an `Upload-Concat` header of a final upload.
+    fn find_partial_uploads(&self, urls: &str) -> Result<Vec<FileInfo>, Response> {
+        let partial_uploads = urls
+            .split_whitespace()
+            .map(|url| match self.find_upload(url) {
+                Ok(info) if info.is_partial && info.is_finished() => Ok(info),
+                Ok(_) => Err(Response::new(400)),
+                Err(response) => Err(response),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if partial_uploads.is_empty() {
+            Err(Response::new(400))
+        } else {
+            Ok(partial_uploads)
+        }
+    }
+
+    /// Finds the upload a request URL refers to, or the response to send if there is none.
+    fn find_upload(&self, url: &str) -> Result<FileInfo, Response> {
+        let id = self.upload_id(url).ok_or_else(|| Response::new(404))?;
+        match self.store.get(id) {
+            Ok(Some(info)) if info.is_expired() => Err(Response::new(410)),
+            Ok(Some(info)) => Ok(info),
+            Ok(None) => Err(Response::new(404)),
+            Err(_) => Err(Response::new(500)),
+        }
+    }
+
+    /// Get the id of the upload a request URL refers to. The URL may either be absolute, or only a path.
+    fn upload_id<'u>(&self, url: &'u str) -> Option<&'u str> {
+        let id = path(url).strip_prefix(self.config.base_path.as_str())?;
+        if id.is_empty() || id.contains('/') {
+            None
+        } else {
+            Some(id)
+        }
+    }
+
+    /// Get the URL of an upload, using the origin the client sent the request to if it's known, and a relative URL otherwise.
+    ///
+    /// When the server is behind a proxy, the origin it reports in the `Forwarded` or `X-Forwarded-*` headers takes precedence over the URL and `Host` header of the request.
+    fn location(&self, req: &HttpRequest, id: &str) -> String {
+        let (scheme, host) = match req.url.find("://") {
+            Some(scheme_end) => {
+                let rest = &req.url[scheme_end + 3..];
+                let host = rest.find('/').map_or(rest, |i| &rest[..i]);
+                (
+                    Some(req.url[..scheme_end].to_owned()),
+                    Some(host.to_owned()),
+                )
+            }
+            None => (None, req.headers.get_by_key("host").cloned()),
+        };
+        let scheme = self
+            .config
+            .forwarded(&req.headers, "proto")
+            .or(scheme)
+            .unwrap_or_else(|| "http".to_owned());
+        match self.config.forwarded(&req.headers, "host").or(host) {
+            Some(host) => format!("{}://{}{}{}", scheme, host, self.config.base_path, id),
+            None => format!("{}{}", self.config.base_path, id),
+        }
+    }
+}
+
+impl<S: DataStore> HttpHandler for Server<S> {
+    fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
+        let response = self.handle(req);
+        Ok(HttpResponse {
+            headers: response.headers,
+            status_code: response.status_code,
+        })
+    }
+
+    fn handle_download(
+        &self,
+        req: HttpRequest,
+    ) -> Result<(HttpResponse, http::ResponseBody<'_>), Error> {
+        let response = self.handle(req);
+        let body = self.read_body(response.body);
+        Ok((
+            HttpResponse {
+                headers: response.headers,
+                status_code: response.status_code,
+            },
+            body,
+        ))
+    }
+}
+
+fn hook_event(hook_type: HookType, info: &FileInfo, req: &HttpRequest) -> HookEvent {
+    HookEvent {
+        hook_type,
+        upload: info.clone(),
+        request: HookRequest {
+            method: req.method,
+            url: req.url.clone(),
+            headers: req.headers.clone(),
+        },
+    }
+}
+
+/// Whether an id, which may be chosen by a hook, is safe to use in URLs and file names. Ids can't contain `.`, so they never clash with the `.info`, `.lock` and `.tmp` files stored next to uploads.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl ServerConfig {
+    /// Get a parameter of a request forwarded by a reverse proxy, like `proto` or `host`, if the server is behind a proxy.
+    fn forwarded(&self, headers: &Headers, parameter: &str) -> Option<String> {
+        if self.behind_proxy {
+            forwarded(headers, parameter)
+        } else {
+            None
+        }
+    }
+}
+
+/// Get a parameter of a request forwarded by a reverse proxy, like `proto` or `host`, from the first element of the `Forwarded` header or from the matching `X-Forwarded-*` header.
+fn forwarded(headers: &Headers, parameter: &str) -> Option<String> {
+    let from_forwarded = headers.get_by_key("forwarded").and_then(|value| {
+        value.split(',').next()?.split(';').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            if key.trim().eq_ignore_ascii_case(parameter) {
+                Some(value.trim().trim_matches('"').to_owned())
+            } else {
+                None
+            }
+        })
+    });
+    from_forwarded.or_else(|| {
+        headers
+            .get_by_key(&format!("x-forwarded-{}", parameter))
+            .and_then(|value| value.split(',').next())
+            .map(|value| value.trim().to_owned())
+    })
+}
+
+/// Get the path of a URL, without the scheme, authority, query or fragment.
+fn path(url: &str) -> &str {
+    let path = match url.find("://") {
+        Some(scheme_end) => {
+            let rest = &url[scheme_end + 3..];
+            rest.find('/').map_or("", |i| &rest[i..])
+        }
+        None => url,
+    };
+    path.split(['?', '#']).next().unwrap_or_default()
+}
+
+/// Computes a checksum over the data read from a request body.
+struct ChecksumReader<'c, R> {
+    body: R,
+    checksum: Option<&'c mut Checksum>,
+}
+
+impl<'c, R: Read> Read for ChecksumReader<'c, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.body.read(buf)?;
+        if let Some(checksum) = &mut self.checksum {
+            checksum.update(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
+/// Generates unique, unpredictable upload ids.
+struct IdGenerator {
+    state: RandomState,
+    counter: AtomicUsize,
+}
+
+impl IdGenerator {
+    fn new() -> Self {
+        IdGenerator {
+            state: RandomState::new(),
+            counter: AtomicUsize::new(0),
+        }
+    }
+
+    fn next(&self) -> String {
+        let counter = self.counter.fetch_add(1, Ordering::SeqCst);
+        let now = SystemTime::now();
+
+        (0..2_u8)
+            .map(|round| format!("{:016x}", self.state.hash_one((round, counter, now))))
+            .collect()
+    }
+}
+
+struct Response {
+    status_code: usize,
+    headers: Headers,
+    body: ResponseBody,
+}
+
+impl Response {
+    fn new(status_code: usize) -> Self {
+        Response {
+            status_code,
+            headers: Headers::new(),
+            body: ResponseBody::Empty,
+        }
+    }
+
+    fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    fn expires(self, info: &FileInfo) -> Self {
+        match info.expires {
+            Some(expires) => {
+                self.header(headers::UPLOAD_EXPIRES, &httpdate::fmt_http_date(expires))
+            }
+            None => self,
+        }
+    }
+
+    fn upload(mut self, id: &str, offset: usize, len: usize) -> Self {
+        self.body = ResponseBody::Upload {
+            id: id.to_owned(),
+            offset,
+            len,
+        };
+        self
+    }
+
+    fn body(mut self, message: &str) -> Self {
+        self.body = ResponseBody::Bytes(message.as_bytes().to_vec());
+        self.header(headers::CONTENT_TYPE, "text/plain; charset=utf-8")
+    }
+}
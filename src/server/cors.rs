@@ -0,0 +1,123 @@
+use crate::http::Headers;
+use std::time::Duration;
+
+/// The request headers browsers are allowed to send.
+const ALLOWED_HEADERS: &str = "Authorization, Origin, X-Requested-With, X-Request-ID, X-HTTP-Method-Override, Content-Type, Upload-Length, Upload-Offset, Tus-Resumable, Upload-Metadata, Upload-Defer-Length, Upload-Concat, Upload-Checksum";
+
+/// The response headers browsers are allowed to read.
+const EXPOSED_HEADERS: &str = "Upload-Offset, Location, Upload-Length, Tus-Version, Tus-Resumable, Tus-Max-Size, Tus-Extension, Upload-Metadata, Upload-Defer-Length, Upload-Concat, Upload-Expires, Tus-Checksum-Algorithm, Content-Range, Content-Disposition";
+
+const ALLOWED_METHODS: &str = "POST, GET, HEAD, PATCH, OPTIONS, DELETE";
+
+/// Configures the CORS headers sent by a `Server`, which browser clients like tus-js-client need to make requests to it.
+///
+/// ```rust,ignore
+/// use std::time::Duration;
+/// use tus_client::server::Cors;
+///
+/// let cors = Cors::with_origins(&["https://app.example.com"])
+///     .allow_credentials(true)
+///     .max_age(Duration::from_secs(24 * 60 * 60));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cors {
+    origins: Option<Vec<String>>,
+    allow_credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl Cors {
+    /// Allows requests from any origin.
+    pub fn new() -> Self {
+        Cors {
+            origins: None,
+            allow_credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Only allows requests from the given origins, like `https://app.example.com`.
+    pub fn with_origins(origins: &[&str]) -> Self {
+        Cors {
+            origins: Some(
+                origins
+                    .iter()
+                    .map(|origin| origin.trim_end_matches('/').to_owned())
+                    .collect(),
+            ),
+            ..Cors::new()
+        }
+    }
+
+    /// Whether browsers may send credentials, like cookies, with requests. Disabled by default.
+    pub fn allow_credentials(mut self, allow_credentials: bool) -> Self {
+        self.allow_credentials = allow_credentials;
+        self
+    }
+
+    /// How long browsers may cache the response to a preflight request.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        match &self.origins {
+            Some(origins) => origins.iter().any(|allowed| allowed == origin),
+            None => true,
+        }
+    }
+
+    /// Adds the CORS headers for a request from `origin` to a response. Nothing is added if the origin isn't allowed.
+    pub(super) fn apply(&self, origin: &str, is_preflight: bool, headers: &mut Headers) {
+        if !self.allows(origin) {
+            return;
+        }
+
+        // A wildcard is not accepted by browsers for requests with credentials
+        let allowed_origin = if self.origins.is_none() && !self.allow_credentials {
+            "*"
+        } else {
+            origin
+        };
+        headers.insert(
+            "access-control-allow-origin".to_owned(),
+            allowed_origin.to_owned(),
+        );
+        headers.insert("vary".to_owned(), "Origin".to_owned());
+        if self.allow_credentials {
+            headers.insert(
+                "access-control-allow-credentials".to_owned(),
+                "true".to_owned(),
+            );
+        }
+
+        if is_preflight {
+            headers.insert(
+                "access-control-allow-methods".to_owned(),
+                ALLOWED_METHODS.to_owned(),
+            );
+            headers.insert(
+                "access-control-allow-headers".to_owned(),
+                ALLOWED_HEADERS.to_owned(),
+            );
+            if let Some(max_age) = self.max_age {
+                headers.insert(
+                    "access-control-max-age".to_owned(),
+                    max_age.as_secs().to_string(),
+                );
+            }
+        } else {
+            headers.insert(
+                "access-control-expose-headers".to_owned(),
+                EXPOSED_HEADERS.to_owned(),
+            );
+        }
+    }
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Cors::new()
+    }
+}
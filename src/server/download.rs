@@ -0,0 +1,186 @@
+use super::{DataStore, FileInfo, Response, ResponseBody, Server};
+use crate::checksum::ReprDigest;
+use crate::headers;
+use crate::http::{self, HttpRequest};
+use crate::metadata::{FILENAME_KEY, FILETYPE_KEY};
+use crate::HeaderMap;
+use std::io::{self, Cursor, Read};
+
+/// The amount of data read from the store at a time, when serving an upload or computing its digest.
+const READ_SIZE: usize = 1024 * 1024;
+
+impl<S: DataStore> Server<S> {
+    /// Get a reader of the body of a response returned by `handle`. The data of an upload is read from the store in pieces, as the body is read.
+    ///
+    /// Reading fails with `UnexpectedEof` if the upload is deleted while it's served.
+    pub fn read_body(&self, body: ResponseBody) -> http::ResponseBody<'_> {
+        match body {
+            ResponseBody::Empty => Box::new(io::empty()),
+            ResponseBody::Bytes(data) => Box::new(Cursor::new(data)),
+            ResponseBody::Upload { id, offset, len } => Box::new(UploadReader {
+                store: &self.store,
+                id,
+                offset,
+                end: offset + len,
+                chunk: Cursor::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Computes the value of the `Repr-Digest` header of a finished upload, reading its data in pieces.
+    pub(super) fn repr_digest(&self, info: &FileInfo) -> io::Result<String> {
+        let mut digest = ReprDigest::new();
+        let mut offset = 0;
+        while offset < info.size {
+            let len = READ_SIZE.min(info.size - offset);
+            let data = self.store.read(&info.id, offset, len)?;
+            if data.len() != len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            digest.update(&data);
+            offset += len;
+        }
+        Ok(digest.finish())
+    }
+
+    /// Serves the data of a finished upload. A single byte range may be requested with the `Range` header, so interrupted downloads can be resumed.
+    pub(super) fn handle_get(&self, req: &HttpRequest) -> Response {
+        let info = match self.find_upload(&req.url) {
+            Ok(info) => info,
+            Err(response) => return response,
+        };
+
+        if !info.is_finished() {
+            return Response::new(409);
+        }
+
+        let range = match req.headers.get_by_key(headers::RANGE) {
+            Some(header) => match parse_range(header, info.size) {
+                Ok(range) => range,
+                Err(()) => {
+                    return Response::new(416)
+                        .header(headers::CONTENT_RANGE, &format!("bytes */{}", info.size))
+                }
+            },
+            None => None,
+        };
+        let (start, len) = match range {
+            Some((start, end)) => (start, end - start + 1),
+            None => (0, info.size),
+        };
+
+        let mut response = Response::new(if range.is_some() { 206 } else { 200 })
+            .header(headers::CONTENT_TYPE, content_type(&info))
+            .header(headers::CONTENT_DISPOSITION, &content_disposition(&info))
+            .header(headers::CONTENT_LENGTH, &len.to_string())
+            .header("accept-ranges", "bytes");
+        if range.is_some() {
+            response = response.header(
+                headers::CONTENT_RANGE,
+                &format!("bytes {}-{}/{}", start, start + len - 1, info.size),
+            );
+        }
+        response.upload(&info.id, start, len)
+    }
+}
+
+/// Reads a range of the data of an upload from the store, `READ_SIZE` bytes at a time.
+struct UploadReader<'a, S> {
+    store: &'a S,
+    id: String,
+    offset: usize,
+    end: usize,
+    chunk: Cursor<Vec<u8>>,
+}
+
+impl<'a, S: DataStore> Read for UploadReader<'a, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.chunk.position() as usize == self.chunk.get_ref().len() && self.offset < self.end {
+            let len = READ_SIZE.min(self.end - self.offset);
+            let data = self.store.read(&self.id, self.offset, len)?;
+            if data.len() != len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.offset += len;
+            self.chunk = Cursor::new(data);
+        }
+        self.chunk.read(buf)
+    }
+}
+
+/// Parses a `Range` header, returning the first and last byte of the range.
+///
+/// Only a single range of bytes is supported, other ranges are ignored by returning `None`, so the whole upload is served. An error is returned when the range doesn't overlap the upload.
+fn parse_range(header: &str, size: usize) -> Result<Option<(usize, usize)>, ()> {
+    let spec = match header.trim().strip_prefix("bytes=") {
+        Some(spec) if !spec.contains(',') => spec,
+        _ => return Ok(None),
+    };
+    let (start, end) = match spec.split_once('-') {
+        Some((start, end)) => (start.trim(), end.trim()),
+        None => return Ok(None),
+    };
+
+    let last = size.saturating_sub(1);
+    let (start, end) = match (start, end) {
+        // A suffix range, containing the last bytes
+        ("", suffix) => match suffix.parse::<usize>() {
+            Ok(0) => return Err(()),
+            Ok(suffix) => (size.saturating_sub(suffix), last),
+            Err(_) => return Ok(None),
+        },
+        (start, "") => match start.parse() {
+            Ok(start) => (start, last),
+            Err(_) => return Ok(None),
+        },
+        (start, end) => match (start.parse::<usize>(), end.parse::<usize>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(last)),
+            _ => return Ok(None),
+        },
+    };
+
+    if start >= size {
+        return Err(());
+    }
+    Ok(Some((start, end)))
+}
+
+fn content_type(info: &FileInfo) -> &str {
+    info.metadata
+        .get(FILETYPE_KEY)
+        .map(String::as_str)
+        .filter(|filetype| filetype.contains('/') && !filetype.chars().any(char::is_control))
+        .unwrap_or("application/octet-stream")
+}
+
+/// Get the `Content-Disposition` header of an upload, which names the file after its `filename` metadata.
+fn content_disposition(info: &FileInfo) -> String {
+    let filename = match info.metadata.get(FILENAME_KEY) {
+        Some(filename) => filename,
+        None => return "attachment".to_owned(),
+    };
+
+    // The quoted name is a fallback for clients which don't support the encoded name described by RFC 6266
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    )
+}
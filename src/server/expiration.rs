@@ -0,0 +1,100 @@
+use super::{DataStore, FileInfo, Server};
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Describes the expired uploads found by `Server::collect_expired`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExpirationReport {
+    /// The expired uploads which were deleted, or would have been deleted in a dry run.
+    pub expired: Vec<FileInfo>,
+    /// The ids of expired uploads which were skipped, because they were locked by a request.
+    pub skipped: Vec<String>,
+    /// The number of bytes of data of the expired uploads.
+    pub bytes: usize,
+    /// Whether this was a dry run, in which nothing was deleted.
+    pub dry_run: bool,
+}
+
+impl<S: DataStore> Server<S> {
+    /// Deletes the unfinished uploads which have expired. When `dry_run` is `true`, the expired uploads are only reported.
+    ///
+    /// Uploads locked by a request are skipped rather than waited for, whatever the `LockConflict` of the server, and are collected by a later call once they expire again.
+    pub fn collect_expired(&self, dry_run: bool) -> io::Result<ExpirationReport> {
+        let mut report = ExpirationReport {
+            dry_run,
+            ..ExpirationReport::default()
+        };
+
+        for info in self.store.list()? {
+            if !info.is_expired() {
+                continue;
+            }
+            let info = if dry_run {
+                info
+            } else {
+                // An upload receiving data is skipped, as the request postpones its expiration
+                let _lock = match self.locker.try_lock(&info.id)? {
+                    Some(lock) => lock,
+                    None => {
+                        report.skipped.push(info.id);
+                        continue;
+                    }
+                };
+                // The upload may have received data, or been deleted, since it was listed
+                let info = match self.store.get(&info.id)? {
+                    Some(info) if info.is_expired() => info,
+                    _ => continue,
+                };
+                if !self.store.delete(&info.id)? {
+                    continue;
+                }
+                info
+            };
+            report.bytes += info.offset;
+            report.expired.push(info);
+        }
+
+        Ok(report)
+    }
+}
+
+/// Periodically deletes the expired uploads of a `Server`, on a separate thread. The thread is stopped when the `Collector` is dropped.
+pub struct Collector {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Collector {
+    /// Starts calling `Server::collect_expired` every `interval`. Each report, or error, is passed to `on_report`.
+    pub fn spawn<S: DataStore + 'static>(
+        server: Arc<Server<S>>,
+        interval: Duration,
+        dry_run: bool,
+        on_report: impl Fn(io::Result<ExpirationReport>) + Send + 'static,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                on_report(server.collect_expired(dry_run));
+            }
+        });
+
+        Collector {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread up
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
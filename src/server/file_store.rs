@@ -0,0 +1,256 @@
+use super::store::not_found;
+use super::tusd::TusdFileInfo;
+use super::{DataStore, FileInfo};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const INFO_EXTENSION: &str = "info";
+const TEMP_EXTENSION: &str = "tmp";
+
+/// Decides when a `FileStore` calls `fsync` to make sure writes have reached the disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncPolicy {
+    /// Never sync, leaving it to the operating system. Data acknowledged to a client may be lost if the machine crashes.
+    Never,
+    /// Sync `.info` files and the directory containing them, but not the data of uploads. An upload may lose data acknowledged to a client if the machine crashes, but is never corrupted: its offset is always the size of its data file.
+    #[default]
+    Metadata,
+    /// Sync everything, including the data received by every `PATCH` request before it's acknowledged.
+    Always,
+}
+
+/// A `DataStore` which stores uploads in a directory, using the same layout as the filestore of [tusd](https://github.com/tus/tusd).
+///
+/// The data of an upload is stored in a file named after its id, and the upload itself in a JSON file with the `.info` extension. The offset of an upload is the size of its data file. A directory written by tusd can be served by a `FileStore`, and vice versa.
+///
+/// Nothing is cached in memory: every operation reads the `.info` file of the upload, so several processes can serve the same directory, when they lock uploads using the same `ServerBuilder::lock_directory`.
+pub struct FileStore {
+    directory: PathBuf,
+    sync_policy: SyncPolicy,
+}
+
+impl FileStore {
+    /// Opens the store in `directory`, using `SyncPolicy::Metadata`. See `with_sync_policy`.
+    pub fn open(directory: impl Into<PathBuf>) -> io::Result<Self> {
+        FileStore::with_sync_policy(directory, SyncPolicy::default())
+    }
+
+    /// Opens the store in `directory`, creating the directory if it doesn't exist.
+    ///
+    /// The uploads already in the directory are served, so a server can continue where it left off when restarted. Temporary files left by an interrupted write are removed, and data files are truncated to the size of their upload.
+    ///
+    /// When several processes serve the directory, only open the store while no other process is writing to it, as the temporary files of their writes would be removed.
+    pub fn with_sync_policy(
+        directory: impl Into<PathBuf>,
+        sync_policy: SyncPolicy,
+    ) -> io::Result<Self> {
+        let store = FileStore {
+            directory: directory.into(),
+            sync_policy,
+        };
+        fs::create_dir_all(&store.directory)?;
+
+        for entry in fs::read_dir(&store.directory)? {
+            let path = entry?.path();
+            match path.extension().and_then(|e| e.to_str()) {
+                Some(TEMP_EXTENSION) => fs::remove_file(&path)?,
+                Some(INFO_EXTENSION) => store.recover(&path)?,
+                _ => {}
+            }
+        }
+
+        Ok(store)
+    }
+
+    /// Get the directory the uploads are stored in.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Get the path of the file containing the data of an upload.
+    pub fn data_path(&self, id: &str) -> PathBuf {
+        self.directory.join(id)
+    }
+
+    fn info_path(&self, id: &str) -> PathBuf {
+        self.directory.join(format!("{}.{}", id, INFO_EXTENSION))
+    }
+
+    /// Loads an upload from its `.info` file. Its offset is the size of its data file.
+    fn load(&self, path: &Path) -> io::Result<FileInfo> {
+        let file: TusdFileInfo = serde_json::from_reader(File::open(path)?)?;
+        let mut info = FileInfo::from(file);
+        info.offset = fs::metadata(self.data_path(&info.id))?.len() as usize;
+        Ok(info)
+    }
+
+    /// Loads the upload with the given id, or returns `None` if it doesn't exist.
+    fn find(&self, id: &str) -> io::Result<Option<FileInfo>> {
+        match self.load(&self.info_path(id)) {
+            Ok(info) => Ok(Some(info)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Creates the missing data file of an upload, and truncates its data file to the size of the upload.
+    fn recover(&self, path: &Path) -> io::Result<()> {
+        let file: TusdFileInfo = serde_json::from_reader(File::open(path)?)?;
+        let info = FileInfo::from(file);
+
+        let data = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.data_path(&info.id))?;
+        if !info.size_is_deferred && data.metadata()?.len() > info.size as u64 {
+            data.set_len(info.size as u64)?;
+        }
+        Ok(())
+    }
+
+    fn ensure_exists(&self, id: &str) -> io::Result<()> {
+        if self.info_path(id).is_file() {
+            Ok(())
+        } else {
+            Err(not_found())
+        }
+    }
+
+    /// Writes the `.info` file of an upload, replacing it atomically so it's never seen half written.
+    fn write_info(&self, info: &FileInfo) -> io::Result<()> {
+        let mut storage = HashMap::new();
+        storage.insert("Type".to_owned(), "filestore".to_owned());
+        storage.insert(
+            "Path".to_owned(),
+            self.data_path(&info.id).to_string_lossy().into_owned(),
+        );
+        let contents = serde_json::to_vec(&TusdFileInfo::new(info, Some(storage)))?;
+
+        let temp_path = self
+            .directory
+            .join(format!("{}.{}.{}", info.id, INFO_EXTENSION, TEMP_EXTENSION));
+        let mut temp_file = File::create(&temp_path)?;
+        temp_file.write_all(&contents)?;
+        if self.sync_policy != SyncPolicy::Never {
+            temp_file.sync_all()?;
+        }
+        fs::rename(&temp_path, self.info_path(&info.id))?;
+        self.sync_directory()
+    }
+
+    fn sync_directory(&self) -> io::Result<()> {
+        // Directories can only be opened as files on unix-like systems
+        if cfg!(unix) && self.sync_policy != SyncPolicy::Never {
+            File::open(&self.directory)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl DataStore for FileStore {
+    fn create(&self, info: FileInfo) -> io::Result<()> {
+        File::create(self.data_path(&info.id))?;
+        self.write_info(&info)
+    }
+
+    fn get(&self, id: &str) -> io::Result<Option<FileInfo>> {
+        self.find(id)
+    }
+
+    fn list(&self) -> io::Result<Vec<FileInfo>> {
+        let mut uploads = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(INFO_EXTENSION) {
+                continue;
+            }
+            // An upload may be deleted by another process while listing
+            match self.load(&path) {
+                Ok(info) => uploads.push(info),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(uploads)
+    }
+
+    fn update(&self, info: &FileInfo) -> io::Result<()> {
+        let offset = match self.find(&info.id)? {
+            Some(stored) => stored.offset,
+            None => return Err(not_found()),
+        };
+        self.write_info(&FileInfo {
+            offset,
+            ..info.clone()
+        })
+    }
+
+    fn read(&self, id: &str, offset: usize, len: usize) -> io::Result<Vec<u8>> {
+        self.ensure_exists(id)?;
+
+        let mut file = File::open(self.data_path(id))?;
+        file.seek(SeekFrom::Start(offset as u64))?;
+        let mut data = Vec::new();
+        file.take(len as u64).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn truncate(&self, id: &str, offset: usize) -> io::Result<()> {
+        self.ensure_exists(id)?;
+
+        let file = OpenOptions::new().write(true).open(self.data_path(id))?;
+        file.set_len(offset as u64)?;
+        if self.sync_policy == SyncPolicy::Always {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn concatenate(&self, id: &str, partial_ids: &[String]) -> io::Result<()> {
+        self.ensure_exists(id)?;
+
+        // `io::copy` between files uses the fastest copy the platform supports, like `copy_file_range` on Linux
+        let mut file = OpenOptions::new().append(true).open(self.data_path(id))?;
+        for partial_id in partial_ids {
+            io::copy(&mut File::open(self.data_path(partial_id))?, &mut file)?;
+        }
+        if self.sync_policy == SyncPolicy::Always {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn append(&self, id: &str, data: &mut dyn Read) -> io::Result<usize> {
+        self.ensure_exists(id)?;
+
+        let mut file = OpenOptions::new().append(true).open(self.data_path(id))?;
+        let result = io::copy(data, &mut file);
+        if self.sync_policy == SyncPolicy::Always {
+            file.sync_data()?;
+        }
+
+        // The offset is the size of the data file, which includes everything written before an error
+        let offset = file.metadata()?.len() as usize;
+        result.map(|_| offset)
+    }
+
+    fn delete(&self, id: &str) -> io::Result<bool> {
+        // The `.info` file is removed first, so the upload no longer exists once its data file is removed
+        match fs::remove_file(self.info_path(id)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        }
+        if let Err(e) = fs::remove_file(self.data_path(id)) {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(e);
+            }
+        }
+        self.sync_directory()?;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,463 @@
+use super::tusd::TusdFileInfo;
+use super::FileInfo;
+use crate::http::{Headers, HttpHandler, HttpMethod, HttpRequest};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The number of events which can be queued for the hooks. Once the queue is full, requests wait for the hooks to catch up.
+const DISPATCH_QUEUE_SIZE: usize = 1024;
+
+/// The time a `FileHook` or `HttpHook` waits for the hook to respond by default.
+const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a `FileHook` checks whether its executable has exited.
+const FILE_HOOK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Called with the hook type and the error when a hook fails, see `ServerBuilder::on_hook_error`.
+pub(super) type ErrorHandler = dyn Fn(HookType, &io::Error) + Send + Sync;
+
+/// The maximum size of the response of an `HttpHook`.
+const MAX_HTTP_HOOK_RESPONSE_SIZE: u64 = 1024 * 1024;
+
+/// Enumerates the stages of the lifecycle of an upload at which hooks are called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookType {
+    /// Called before an upload is created. The hook may reject the upload, or change its id and metadata.
+    PreCreate,
+    /// Called after data has been appended to an upload by a `PATCH` request.
+    PostReceive,
+    /// Called after all the data of an upload has been received.
+    PostFinish,
+    /// Called after an upload has been deleted.
+    PostTerminate,
+}
+
+impl HookType {
+    /// Get the name of the hook type, as used by tusd.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookType::PreCreate => "pre-create",
+            HookType::PostReceive => "post-receive",
+            HookType::PostFinish => "post-finish",
+            HookType::PostTerminate => "post-terminate",
+        }
+    }
+}
+
+/// Describes the request which caused a hook to be called.
+#[derive(Debug, Clone)]
+pub struct HookRequest {
+    /// The method of the request.
+    pub method: HttpMethod,
+    /// The URL of the request.
+    pub url: String,
+    /// The headers of the request.
+    pub headers: Headers,
+}
+
+/// The event passed to a hook.
+#[derive(Debug, Clone)]
+pub struct HookEvent {
+    /// The stage of the lifecycle the hook is called at.
+    pub hook_type: HookType,
+    /// The upload, as it will be created by `HookType::PreCreate` hooks, or as it's stored by the other hooks.
+    pub upload: FileInfo,
+    /// The request which caused the hook to be called.
+    pub request: HookRequest,
+}
+
+/// The response of a hook. Only `HookType::PreCreate` hooks can affect the upload, the responses of other hooks are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HookResponse {
+    /// Rejects the upload, responding to the request with the given status and message.
+    pub rejection: Option<Rejection>,
+    /// Replaces the id of the upload. It may only contain ASCII letters, digits, `-` and `_`, otherwise the upload isn't created.
+    pub id: Option<String>,
+    /// Replaces the metadata of the upload.
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+/// The response sent when a hook rejects an upload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    /// The status code of the response.
+    pub status_code: usize,
+    /// The body of the response.
+    pub message: String,
+}
+
+impl HookResponse {
+    /// Creates a response rejecting the upload.
+    pub fn reject(status_code: usize, message: &str) -> Self {
+        HookResponse {
+            rejection: Some(Rejection {
+                status_code,
+                message: message.to_owned(),
+            }),
+            ..HookResponse::default()
+        }
+    }
+}
+
+/// A hook called by a `Server` at each stage of the lifecycle of an upload.
+///
+/// `HookType::PreCreate` hooks are called before responding to the request, and the upload is not created if one fails. Other hooks are called after the request has been handled, on a single thread per server, so they see the events in the order they occurred. Requests only wait for these hooks when they fall behind by more than a thousand events. Errors and panics of hooks are reported to `ServerBuilder::on_hook_error`.
+///
+/// This is implemented for closures taking a `&HookEvent`.
+pub trait Hook: Send + Sync {
+    /// Handles an event.
+    fn call(&self, event: &HookEvent) -> io::Result<HookResponse>;
+}
+
+impl<F> Hook for F
+where
+    F: Fn(&HookEvent) -> io::Result<HookResponse> + Send + Sync,
+{
+    fn call(&self, event: &HookEvent) -> io::Result<HookResponse> {
+        self(event)
+    }
+}
+
+/// A hook which runs executables, like the file hooks of tusd.
+///
+/// For each event, the executable in the hook directory named after the hook type (like `pre-create`) is run, if it exists. The event is written to its standard input as JSON, and a response may be written to its standard output, using the same format as tusd. The hook fails if the executable exits with a non-zero status, or doesn't exit within the timeout, in which case it's killed.
+pub struct FileHook {
+    directory: PathBuf,
+    timeout: Duration,
+}
+
+impl FileHook {
+    /// Creates a `FileHook` running the executables in `directory`.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        FileHook {
+            directory: directory.into(),
+            timeout: DEFAULT_HOOK_TIMEOUT,
+        }
+    }
+
+    /// The time to wait for an executable to exit. Defaults to 30 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl Hook for FileHook {
+    fn call(&self, event: &HookEvent) -> io::Result<HookResponse> {
+        let path = self.directory.join(event.hook_type.as_str());
+        if !path.is_file() {
+            return Ok(HookResponse::default());
+        }
+
+        let mut child = Command::new(&path)
+            .arg(event.hook_type.as_str())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // The pipes are written and read on separate threads, so an executable which doesn't read its input or fills its output can still be killed
+        let request = serde_json::to_vec(&TusdHookRequest::new(event))?;
+        let stdin = child.stdin.take().map(|mut stdin| {
+            thread::spawn(move || match stdin.write_all(&request) {
+                // The executable may exit without reading the event
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e),
+                _ => Ok(()),
+            })
+        });
+        let stdout = child.stdout.take().map(read_pipe);
+        let stderr = child.stderr.take().map(read_pipe);
+
+        let status = match wait_timeout(&mut child, self.timeout)? {
+            Some(status) => status,
+            None => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("Hook {} didn't exit in time and was killed", path.display()),
+                ));
+            }
+        };
+        if let Some(stdin) = stdin {
+            join(stdin)?;
+        }
+        let stdout = stdout.map(join).transpose()?.unwrap_or_default();
+        let stderr = stderr.map(join).transpose()?.unwrap_or_default();
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "Hook {} failed with {}: {}",
+                path.display(),
+                status,
+                String::from_utf8_lossy(&stderr).trim()
+            )));
+        }
+
+        parse_response(&stdout)
+    }
+}
+
+/// Reads a pipe of a child process to the end on a separate thread.
+fn read_pipe(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut data = Vec::new();
+        pipe.read_to_end(&mut data)?;
+        Ok(data)
+    })
+}
+
+fn join<T>(handle: thread::JoinHandle<io::Result<T>>) -> io::Result<T> {
+    handle
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("Reading a hook's pipe panicked")))
+}
+
+/// Waits for a child process to exit, returning `None` if it's still running after `timeout`.
+fn wait_timeout(child: &mut Child, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(FILE_HOOK_POLL_INTERVAL);
+    }
+}
+
+/// A hook which sends events to a URL in `POST` requests, like the HTTP hooks of tusd.
+///
+/// The event is sent as JSON, and the response may contain a response body, using the same format as tusd. The hook fails if the response has a status code other than `2xx`, or if no response arrives within the timeout.
+pub struct HttpHook {
+    url: String,
+    handler: Arc<dyn HttpHandler + Send + Sync>,
+    timeout: Duration,
+}
+
+impl HttpHook {
+    /// Creates a `HttpHook` sending events to `url` using `handler`, which must be able to read response bodies (see `HttpHandler::handle_download`).
+    pub fn new(url: &str, handler: Arc<dyn HttpHandler + Send + Sync>) -> Self {
+        HttpHook {
+            url: url.to_owned(),
+            handler,
+            timeout: DEFAULT_HOOK_TIMEOUT,
+        }
+    }
+
+    /// The time to wait for a response. Defaults to 30 seconds.
+    ///
+    /// The request is sent on a separate thread, which is left running when the timeout elapses, until the handler gives up. Configure a timeout on the handler as well, like with `reqwest::ClientBuilder::timeout`, so the thread doesn't run forever.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl Hook for HttpHook {
+    fn call(&self, event: &HookEvent) -> io::Result<HookResponse> {
+        let body = serde_json::to_vec(&TusdHookRequest::new(event))?;
+        let handler = Arc::clone(&self.handler);
+        let url = self.url.clone();
+        let timeout = self.timeout;
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(send_event(&*handler, url, &body, timeout));
+        });
+
+        let (status_code, body) = match receiver.recv_timeout(self.timeout) {
+            Ok(result) => result?,
+            Err(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("Hook {} didn't respond in time", self.url),
+                ))
+            }
+        };
+        if !(200..300).contains(&status_code) {
+            return Err(io::Error::other(format!(
+                "Hook {} responded with status {}",
+                self.url, status_code
+            )));
+        }
+
+        parse_response(&body)
+    }
+}
+
+/// Sends an event to an HTTP hook, returning the status code and the body of the response.
+fn send_event(
+    handler: &dyn HttpHandler,
+    url: String,
+    body: &[u8],
+    timeout: Duration,
+) -> io::Result<(usize, Vec<u8>)> {
+    let mut headers = Headers::new();
+    headers.insert("content-type".to_owned(), "application/json".to_owned());
+
+    let (response, response_body) = handler
+        .handle_download(HttpRequest {
+            method: HttpMethod::Post,
+            headers,
+            url,
+            body: Some(body),
+            timeout: Some(timeout),
+        })
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let mut data = Vec::new();
+    response_body
+        .take(MAX_HTTP_HOOK_RESPONSE_SIZE)
+        .read_to_end(&mut data)?;
+    Ok((response.status_code, data))
+}
+
+/// Calls a hook, reporting its error to `on_error`. A panicking hook fails like a hook returning an error.
+pub(super) fn call(
+    hook: &dyn Hook,
+    event: &HookEvent,
+    on_error: Option<&ErrorHandler>,
+) -> io::Result<HookResponse> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| hook.call(event))).unwrap_or_else(|e| {
+        let message = e
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| e.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown error");
+        Err(io::Error::other(format!("Hook panicked: {}", message)))
+    });
+    if let (Err(e), Some(on_error)) = (&result, on_error) {
+        on_error(event.hook_type, e);
+    }
+    result
+}
+
+/// Calls the hooks of a `Server` with the events occurring after requests have been handled, on a single thread and in order.
+pub(super) struct Dispatcher {
+    sender: SyncSender<HookEvent>,
+}
+
+impl Dispatcher {
+    pub(super) fn new(hooks: Vec<Arc<dyn Hook>>, on_error: Option<Arc<ErrorHandler>>) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<HookEvent>(DISPATCH_QUEUE_SIZE);
+        // The thread exits once the server, and with it the sender, is dropped
+        thread::spawn(move || {
+            for event in receiver {
+                for hook in &hooks {
+                    // A failing hook mustn't stop the hooks from being called for later events
+                    let _ = call(&**hook, &event, on_error.as_deref());
+                }
+            }
+        });
+        Dispatcher { sender }
+    }
+
+    /// Queues an event, waiting for room in the queue if the hooks fall behind.
+    pub(super) fn dispatch(&self, event: HookEvent) {
+        let _ = self.sender.send(event);
+    }
+}
+
+fn parse_response(body: &[u8]) -> io::Result<HookResponse> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(HookResponse::default());
+    }
+
+    let response: TusdHookResponse = serde_json::from_slice(body)?;
+    let change = response.change_file_info.unwrap_or_default();
+    Ok(HookResponse {
+        rejection: if response.reject_upload {
+            let http_response = response.http_response.unwrap_or_default();
+            Some(Rejection {
+                status_code: http_response.status_code.unwrap_or(400),
+                message: http_response.body.unwrap_or_default(),
+            })
+        } else {
+            None
+        },
+        id: change.id,
+        metadata: change.metadata,
+    })
+}
+
+#[derive(Serialize)]
+struct TusdHookRequest {
+    #[serde(rename = "Type")]
+    hook_type: &'static str,
+    #[serde(rename = "Event")]
+    event: TusdEvent,
+}
+
+#[derive(Serialize)]
+struct TusdEvent {
+    #[serde(rename = "Upload")]
+    upload: TusdFileInfo,
+    #[serde(rename = "HTTPRequest")]
+    http_request: TusdHttpRequest,
+}
+
+#[derive(Serialize)]
+struct TusdHttpRequest {
+    #[serde(rename = "Method")]
+    method: String,
+    #[serde(rename = "URI")]
+    uri: String,
+    #[serde(rename = "RemoteAddr")]
+    remote_addr: String,
+    #[serde(rename = "Header")]
+    header: HashMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+struct TusdHookResponse {
+    #[serde(rename = "HTTPResponse", default)]
+    http_response: Option<TusdHttpResponse>,
+    #[serde(rename = "RejectUpload", default)]
+    reject_upload: bool,
+    #[serde(rename = "ChangeFileInfo", default)]
+    change_file_info: Option<TusdChangeFileInfo>,
+}
+
+#[derive(Deserialize, Default)]
+struct TusdHttpResponse {
+    #[serde(rename = "StatusCode", default)]
+    status_code: Option<usize>,
+    #[serde(rename = "Body", default)]
+    body: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct TusdChangeFileInfo {
+    #[serde(rename = "ID", default)]
+    id: Option<String>,
+    #[serde(rename = "MetaData", default)]
+    metadata: Option<HashMap<String, String>>,
+}
+
+impl TusdHookRequest {
+    fn new(event: &HookEvent) -> Self {
+        TusdHookRequest {
+            hook_type: event.hook_type.as_str(),
+            event: TusdEvent {
+                upload: TusdFileInfo::new(&event.upload, None),
+                http_request: TusdHttpRequest {
+                    method: event.request.method.to_string().to_uppercase(),
+                    uri: event.request.url.clone(),
+                    remote_addr: String::new(),
+                    header: event
+                        .request
+                        .headers
+                        .iter()
+                        .map(|(key, value)| (key.clone(), vec![value.clone()]))
+                        .collect(),
+                },
+            },
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Decides what happens when a request needs the lock of an upload which is held by another request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LockConflict {
+    /// The new request is rejected with `423 Locked`.
+    #[default]
+    Reject,
+    /// The request holding the lock is asked to release it, which stops it before it writes more data. The new request waits up to the given duration for the lock, and is rejected with `423 Locked` if it isn't released in time.
+    ///
+    /// Lock files of other processes are never released.
+    RequestRelease(Duration),
+}
+
+/// Hands out exclusive locks of uploads, within the process and optionally through lock files.
+pub(super) struct Locker {
+    conflict: LockConflict,
+    directory: Option<PathBuf>,
+    locks: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    unlocked: Condvar,
+}
+
+/// An exclusive lock of an upload, which is released when dropped.
+pub(super) struct UploadLock<'a> {
+    locker: &'a Locker,
+    id: String,
+    release_requested: Arc<AtomicBool>,
+    file: Option<PathBuf>,
+}
+
+impl Locker {
+    pub(super) fn new(conflict: LockConflict, directory: Option<PathBuf>) -> Self {
+        Locker {
+            conflict,
+            directory,
+            locks: Mutex::new(HashMap::new()),
+            unlocked: Condvar::new(),
+        }
+    }
+
+    fn lock_map(&self) -> MutexGuard<'_, HashMap<String, Arc<AtomicBool>>> {
+        self.locks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Takes the lock of an upload. Returns `None` if it's held by another request.
+    pub(super) fn lock(&self, id: &str) -> io::Result<Option<UploadLock<'_>>> {
+        self.acquire(id, self.conflict)
+    }
+
+    /// Takes the lock of an upload if it's free, without asking the request holding it to release it. Returns `None` if it's held by another request.
+    pub(super) fn try_lock(&self, id: &str) -> io::Result<Option<UploadLock<'_>>> {
+        self.acquire(id, LockConflict::Reject)
+    }
+
+    fn acquire(&self, id: &str, conflict: LockConflict) -> io::Result<Option<UploadLock<'_>>> {
+        let mut locks = self.lock_map();
+        if let Some(holder) = locks.get(id) {
+            let timeout = match conflict {
+                LockConflict::Reject => return Ok(None),
+                LockConflict::RequestRelease(timeout) => timeout,
+            };
+            holder.store(true, Ordering::SeqCst);
+
+            let deadline = Instant::now() + timeout;
+            while locks.contains_key(id) {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Ok(None);
+                }
+                locks = self
+                    .unlocked
+                    .wait_timeout(locks, deadline - now)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+        }
+
+        let release_requested = Arc::new(AtomicBool::new(false));
+        locks.insert(id.to_owned(), Arc::clone(&release_requested));
+        drop(locks);
+
+        let mut lock = UploadLock {
+            locker: self,
+            id: id.to_owned(),
+            release_requested,
+            file: None,
+        };
+        if let Some(directory) = &self.directory {
+            let path = directory.join(format!("{}.lock", id));
+            if !create_lock_file(&path)? {
+                return Ok(None);
+            }
+            lock.file = Some(path);
+        }
+
+        Ok(Some(lock))
+    }
+}
+
+impl<'a> UploadLock<'a> {
+    /// Get the id of the locked upload.
+    pub(super) fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether another request has asked for the lock to be released.
+    pub(super) fn is_release_requested(&self) -> bool {
+        self.release_requested.load(Ordering::SeqCst)
+    }
+
+    /// Wraps a request body, so reading it fails once another request has asked for the lock to be released.
+    pub(super) fn guard<'r>(&'r self, body: &'r mut dyn Read) -> impl Read + 'r {
+        ReleasableReader { lock: self, body }
+    }
+}
+
+impl<'a> Drop for UploadLock<'a> {
+    fn drop(&mut self) {
+        if let Some(path) = &self.file {
+            let _ = fs::remove_file(path);
+        }
+        self.locker.lock_map().remove(&self.id);
+        self.locker.unlocked.notify_all();
+    }
+}
+
+struct ReleasableReader<'r, 'a> {
+    lock: &'r UploadLock<'a>,
+    body: &'r mut dyn Read,
+}
+
+impl<'r, 'a> Read for ReleasableReader<'r, 'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.lock.is_release_requested() {
+            return Err(io::Error::other("the upload lock was released"));
+        }
+        self.body.read(buf)
+    }
+}
+
+/// Creates a lock file containing the id of the current process. Returns `false` if the file is held by a running process.
+fn create_lock_file(path: &Path) -> io::Result<bool> {
+    for _ in 0..2 {
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(mut file) => {
+                file.write_all(std::process::id().to_string().as_bytes())?;
+                return Ok(true);
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                if !is_stale(path) {
+                    return Ok(false);
+                }
+                // The process which created the lock file has died, so the lock is taken over
+                let _ = fs::remove_file(path);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(false)
+}
+
+/// Whether a lock file was left behind by a process which is no longer running. This can only be detected on Linux.
+fn is_stale(path: &Path) -> bool {
+    let pid = match fs::read_to_string(path).map(|pid| pid.trim().parse::<u32>()) {
+        Ok(Ok(pid)) => pid,
+        _ => return false,
+    };
+    cfg!(target_os = "linux") && !Path::new("/proc").join(pid.to_string()).exists()
+}
@@ -0,0 +1,404 @@
+use super::{DataStore, ResponseBody, Server, ServerResponse};
+use crate::http::{Headers, HttpMethod, HttpRequest};
+use bytes::{Buf, Bytes};
+use http::{Request, Response, StatusCode};
+use http_body::{Body, Frame, SizeHint};
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full};
+use std::convert::Infallible;
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io::{self, Read};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::{mpsc, Notify};
+use tower_service::Service;
+
+/// The number of body chunks buffered between the connection and the store.
+const BODY_BUFFER: usize = 4;
+
+/// The size of the chunks of data sent by a download.
+const DOWNLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The body of the responses of a `TusService`.
+type ServiceBody = BoxBody<Bytes, io::Error>;
+
+/// Exposes a `Server` as a `tower::Service`, so it can be mounted in frameworks like axum and hyper. Requires the `tower` feature.
+///
+/// The service can be mounted under the base path of the server, whether the framework strips the path prefix (like `Router::nest_service` in axum) or not. The data of `PATCH` requests is streamed to the store on a blocking thread, and so is the data of `GET` requests from the store, so the service must be used within a tokio runtime.
+///
+/// ```rust,ignore
+/// use std::sync::Arc;
+/// use tus_client::server::{MemoryStore, ServerBuilder, TusService};
+///
+/// let service = TusService::new(Arc::new(ServerBuilder::new().base_path("/files/").build(MemoryStore::new())));
+/// let app = axum::Router::new().nest_service("/files", service);
+/// ```
+pub struct TusService<S> {
+    server: Arc<Server<S>>,
+    shutdown: Arc<ShutdownState>,
+}
+
+/// Used to shut a `TusService` down gracefully.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    state: Arc<ShutdownState>,
+}
+
+#[derive(Default)]
+struct ShutdownState {
+    shutting_down: AtomicBool,
+    requests: AtomicUsize,
+    interrupted: Notify,
+    finished: Notify,
+}
+
+impl<S: DataStore + 'static> TusService<S> {
+    /// Creates a `TusService` handling requests with `server`.
+    pub fn new(server: Arc<Server<S>>) -> Self {
+        TusService {
+            server,
+            shutdown: Arc::new(ShutdownState::default()),
+        }
+    }
+
+    /// Get a handle which shuts down this service, and all clones of it.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            state: Arc::clone(&self.shutdown),
+        }
+    }
+}
+
+impl<S> Clone for TusService<S> {
+    fn clone(&self) -> Self {
+        TusService {
+            server: Arc::clone(&self.server),
+            shutdown: Arc::clone(&self.shutdown),
+        }
+    }
+}
+
+impl ShutdownHandle {
+    /// Starts shutting down. New requests are rejected with `503 Service Unavailable`, and uploads in progress are interrupted. The data they received is kept, so clients can resume them from another instance.
+    ///
+    /// Completes when all requests have been handled. Await this in the signal passed to the graceful shutdown of the HTTP server, like `with_graceful_shutdown` in axum, so its connections can close once the uploads are interrupted:
+    ///
+    /// ```rust,ignore
+    /// let handle = service.shutdown_handle();
+    /// axum::serve(listener, app)
+    ///     .with_graceful_shutdown(async move {
+    ///         tokio::signal::ctrl_c().await.ok();
+    ///         handle.shutdown().await
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn shutdown(&self) {
+        self.state.shutting_down.store(true, Ordering::SeqCst);
+        self.state.interrupted.notify_waiters();
+        while self.state.requests.load(Ordering::SeqCst) > 0 {
+            self.state.finished.notified().await;
+        }
+    }
+
+    /// Whether the service is shutting down.
+    pub fn is_shutting_down(&self) -> bool {
+        self.state.shutting_down.load(Ordering::SeqCst)
+    }
+}
+
+impl<S, B> Service<Request<B>> for TusService<S>
+where
+    S: DataStore + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Response = Response<ServiceBody>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let server = Arc::clone(&self.server);
+        let shutdown = Arc::clone(&self.shutdown);
+        Box::pin(async move { Ok(handle(server, shutdown, req).await) })
+    }
+}
+
+async fn handle<S, B>(
+    server: Arc<Server<S>>,
+    shutdown: Arc<ShutdownState>,
+    req: Request<B>,
+) -> Response<ServiceBody>
+where
+    S: DataStore + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    if shutdown.shutting_down.load(Ordering::SeqCst) {
+        return status_response(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    let _request = RequestGuard::new(&shutdown);
+
+    let method = match req.method().as_str().parse::<HttpMethod>() {
+        Ok(method) => method,
+        Err(()) => return status_response(StatusCode::METHOD_NOT_ALLOWED),
+    };
+    let (parts, body) = req.into_parts();
+
+    let mut headers = Headers::new();
+    for (key, value) in &parts.headers {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        headers
+            .entry(key.as_str().to_owned())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            })
+            .or_insert_with(|| value.into_owned());
+    }
+
+    let request = HttpRequest {
+        method,
+        url: request_url(&server, &parts.uri, &headers),
+        headers,
+        body: None,
+        timeout: None,
+    };
+
+    // The body is read on an async task, while the server writes it to the store on a blocking thread
+    let (sender, receiver) = mpsc::channel(BODY_BUFFER);
+    let mut reader = BodyReader {
+        receiver,
+        chunk: Bytes::new(),
+        shutdown: Arc::clone(&shutdown),
+    };
+    let handler = {
+        let server = Arc::clone(&server);
+        tokio::task::spawn_blocking(move || server.handle_with_body(request, &mut reader))
+    };
+
+    // Registered before checking the flag, so a shutdown starting in between isn't missed
+    let interrupted = shutdown.interrupted.notified();
+    tokio::pin!(interrupted);
+    interrupted.as_mut().enable();
+
+    let mut body = Box::pin(body);
+    loop {
+        // Frames are converted right away, as the error type of the body may not be `Send`
+        let frame = if shutdown.shutting_down.load(Ordering::SeqCst) {
+            None
+        } else {
+            tokio::select! {
+                frame = body.frame() => frame.map(into_chunk),
+                _ = &mut interrupted => None,
+            }
+        };
+        let chunk = match frame {
+            Some(Ok(Some(data))) => Ok(data),
+            // Trailers are ignored
+            Some(Ok(None)) => continue,
+            Some(Err(e)) => Err(e),
+            None if shutdown.shutting_down.load(Ordering::SeqCst) => Err(shutting_down()),
+            None => break,
+        };
+        let failed = chunk.is_err();
+        // Sending fails once the handler stops reading the body
+        if sender.send(chunk).await.is_err() || failed {
+            break;
+        }
+    }
+    drop(sender);
+
+    match handler.await {
+        Ok(response) => into_response(server, response),
+        Err(_) => status_response(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Get the absolute URL of a request, so the server can create `Location` URLs. A path which doesn't start with the base path of the server is assumed to be relative to it, as the framework stripped the prefix it's mounted under.
+fn request_url<S>(server: &Server<S>, uri: &http::Uri, headers: &Headers) -> String {
+    let base_path = server.config.base_path.as_str();
+    let path = uri.path();
+    let mut url = if path.starts_with(base_path) || path == base_path.trim_end_matches('/') {
+        path.to_owned()
+    } else {
+        format!("{}{}", base_path, path.trim_start_matches('/'))
+    };
+    if let Some(query) = uri.query() {
+        url = format!("{}?{}", url, query);
+    }
+
+    let scheme = server
+        .config
+        .forwarded(headers, "proto")
+        .or_else(|| uri.scheme_str().map(str::to_owned))
+        .unwrap_or_else(|| "http".to_owned());
+    let host = server
+        .config
+        .forwarded(headers, "host")
+        .or_else(|| headers.get("host").cloned())
+        .or_else(|| uri.authority().map(|authority| authority.to_string()));
+    match host {
+        Some(host) => format!("{}://{}{}", scheme, host, url),
+        None => url,
+    }
+}
+
+/// Get the data of a frame of a request body, or `None` for trailers.
+fn into_chunk<D, E>(frame: Result<Frame<D>, E>) -> io::Result<Option<Bytes>>
+where
+    D: Buf,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    match frame.map_err(|e| io::Error::other(e.into()))?.into_data() {
+        Ok(mut data) => Ok(Some(data.copy_to_bytes(data.remaining()))),
+        Err(_) => Ok(None),
+    }
+}
+
+fn into_response<S: DataStore + 'static>(
+    server: Arc<Server<S>>,
+    response: ServerResponse,
+) -> Response<ServiceBody> {
+    let mut builder = Response::builder().status(response.status_code as u16);
+    for (key, value) in &response.headers {
+        builder = builder.header(key.as_str(), value.as_str());
+    }
+    let body = match response.body {
+        ResponseBody::Empty => full(Bytes::new()),
+        ResponseBody::Bytes(data) => full(Bytes::from(data)),
+        ResponseBody::Upload { len, .. } => download_body(server, response.body, len as u64),
+    };
+    builder
+        .body(body)
+        .unwrap_or_else(|_| status_response(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn status_response(status: StatusCode) -> Response<ServiceBody> {
+    let mut response = Response::new(full(Bytes::new()));
+    *response.status_mut() = status;
+    response
+}
+
+fn full(data: Bytes) -> ServiceBody {
+    Full::new(data).map_err(|never| match never {}).boxed()
+}
+
+/// Streams the data of an upload, which is read from the store on a blocking thread.
+fn download_body<S: DataStore + 'static>(
+    server: Arc<Server<S>>,
+    body: ResponseBody,
+    len: u64,
+) -> ServiceBody {
+    let (sender, receiver) = mpsc::channel(BODY_BUFFER);
+    tokio::task::spawn_blocking(move || {
+        let mut reader = server.read_body(body);
+        loop {
+            let mut chunk = vec![0; DOWNLOAD_CHUNK_SIZE];
+            let result = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(len) => {
+                    chunk.truncate(len);
+                    Ok(Bytes::from(chunk))
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => Err(e),
+            };
+            let failed = result.is_err();
+            // Sending fails once the connection is closed
+            if sender.blocking_send(result).is_err() || failed {
+                break;
+            }
+        }
+    });
+    DownloadBody {
+        receiver,
+        remaining: len,
+    }
+    .boxed()
+}
+
+/// Counts a request as in progress, until it's dropped.
+struct RequestGuard<'a>(&'a ShutdownState);
+
+impl<'a> RequestGuard<'a> {
+    fn new(state: &'a ShutdownState) -> Self {
+        state.requests.fetch_add(1, Ordering::SeqCst);
+        RequestGuard(state)
+    }
+}
+
+impl<'a> Drop for RequestGuard<'a> {
+    fn drop(&mut self) {
+        self.0.requests.fetch_sub(1, Ordering::SeqCst);
+        self.0.finished.notify_one();
+    }
+}
+
+/// Reads the body received by the async task on a blocking thread.
+struct BodyReader {
+    receiver: mpsc::Receiver<io::Result<Bytes>>,
+    chunk: Bytes,
+    shutdown: Arc<ShutdownState>,
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.shutdown.shutting_down.load(Ordering::SeqCst) {
+            return Err(shutting_down());
+        }
+
+        while self.chunk.is_empty() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => self.chunk = chunk?,
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk[..len]);
+        self.chunk.advance(len);
+        Ok(len)
+    }
+}
+
+/// The body of a download, receiving the data read from the store on a blocking thread.
+struct DownloadBody {
+    receiver: mpsc::Receiver<io::Result<Bytes>>,
+    remaining: u64,
+}
+
+impl Body for DownloadBody {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, io::Error>>> {
+        let chunk = match self.receiver.poll_recv(cx) {
+            Poll::Ready(chunk) => chunk,
+            Poll::Pending => return Poll::Pending,
+        };
+        if let Some(Ok(data)) = &chunk {
+            self.remaining = self.remaining.saturating_sub(data.len() as u64);
+        }
+        Poll::Ready(chunk.map(|chunk| chunk.map(Frame::data)))
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.remaining)
+    }
+}
+
+fn shutting_down() -> io::Error {
+    io::Error::other("the server is shutting down")
+}
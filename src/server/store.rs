@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// Describes an upload stored by a `DataStore`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FileInfo {
+    /// The id of the upload, which is the last segment of its URL.
+    pub id: String,
+    /// The total size of the upload. This is `0` while the size is deferred.
+    pub size: usize,
+    /// Whether the upload was created without its size, which is declared by a later `PATCH` request.
+    pub size_is_deferred: bool,
+    /// How many bytes have been received.
+    pub offset: usize,
+    /// The metadata supplied when the upload was created.
+    pub metadata: HashMap<String, String>,
+    /// The time after which the upload expires, if it's unfinished.
+    pub expires: Option<SystemTime>,
+    /// Whether this is a partial upload, which is meant to be concatenated into a final upload.
+    pub is_partial: bool,
+    /// The ids of the partial uploads, if this is a final upload consisting of them.
+    pub partial_uploads: Option<Vec<String>>,
+}
+
+impl FileInfo {
+    /// Whether all the data of the upload has been received.
+    pub fn is_finished(&self) -> bool {
+        !self.size_is_deferred && self.offset == self.size
+    }
+
+    /// Whether this is a final upload, consisting of partial uploads.
+    pub fn is_final(&self) -> bool {
+        self.partial_uploads.is_some()
+    }
+
+    /// Whether the upload is unfinished, and has expired.
+    pub fn is_expired(&self) -> bool {
+        !self.is_finished()
+            && self
+                .expires
+                .is_some_and(|expires| expires <= SystemTime::now())
+    }
+}
+
+/// The storage used by a `Server` to store uploads and their data.
+///
+/// Implementations must be safe to use from multiple threads at once.
+pub trait DataStore: Send + Sync {
+    /// Stores a new upload, without any data.
+    fn create(&self, info: FileInfo) -> io::Result<()>;
+
+    /// Get an upload, or `None` if it doesn't exist.
+    fn get(&self, id: &str) -> io::Result<Option<FileInfo>>;
+
+    /// Get all uploads.
+    fn list(&self) -> io::Result<Vec<FileInfo>>;
+
+    /// Replaces the stored information of an upload, except for its offset, which is determined by its data.
+    fn update(&self, info: &FileInfo) -> io::Result<()>;
+
+    /// Reads `data` to its end, appending it to the data of an upload. Returns the new offset of the upload.
+    ///
+    /// The data which has been read should be kept if an error occurs, so the upload can be resumed.
+    fn append(&self, id: &str, data: &mut dyn Read) -> io::Result<usize>;
+
+    /// Reads up to `len` bytes of the data of an upload, starting at `offset`. This is used to serve finished uploads to `GET` requests.
+    fn read(&self, id: &str, offset: usize, len: usize) -> io::Result<Vec<u8>>;
+
+    /// Removes the data of an upload after `offset`, which must not be larger than its current offset. This is used to discard data which failed verification.
+    fn truncate(&self, id: &str, offset: usize) -> io::Result<()>;
+
+    /// Sets the data of an upload, which must have no data yet, to the data of the given uploads in order. The offset of the upload becomes the sum of their sizes.
+    fn concatenate(&self, id: &str, partial_ids: &[String]) -> io::Result<()>;
+
+    /// Deletes an upload and its data. Returns `false` if the upload doesn't exist.
+    fn delete(&self, id: &str) -> io::Result<bool>;
+}
+
+/// A `DataStore` which keeps all uploads in memory. The uploads are lost when it's dropped.
+#[derive(Default)]
+pub struct MemoryStore {
+    uploads: Mutex<HashMap<String, (FileInfo, Vec<u8>)>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty `MemoryStore`.
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    /// Get the data received for an upload.
+    pub fn data(&self, id: &str) -> Option<Vec<u8>> {
+        self.lock().get(id).map(|(_, data)| data.clone())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, (FileInfo, Vec<u8>)>> {
+        self.uploads.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl DataStore for MemoryStore {
+    fn create(&self, info: FileInfo) -> io::Result<()> {
+        self.lock().insert(info.id.clone(), (info, Vec::new()));
+        Ok(())
+    }
+
+    fn get(&self, id: &str) -> io::Result<Option<FileInfo>> {
+        Ok(self.lock().get(id).map(|(info, _)| info.clone()))
+    }
+
+    fn list(&self) -> io::Result<Vec<FileInfo>> {
+        Ok(self.lock().values().map(|(info, _)| info.clone()).collect())
+    }
+
+    fn update(&self, info: &FileInfo) -> io::Result<()> {
+        let mut uploads = self.lock();
+        let (stored, _) = uploads.get_mut(&info.id).ok_or_else(not_found)?;
+        *stored = FileInfo {
+            offset: stored.offset,
+            ..info.clone()
+        };
+        Ok(())
+    }
+
+    fn append(&self, id: &str, data: &mut dyn Read) -> io::Result<usize> {
+        // The data is read before taking the lock, so a slow request doesn't block other uploads
+        let mut buffer = Vec::new();
+        let result = data.read_to_end(&mut buffer);
+
+        let mut uploads = self.lock();
+        let (info, data) = uploads.get_mut(id).ok_or_else(not_found)?;
+        data.extend_from_slice(&buffer);
+        info.offset = data.len();
+
+        result.map(|_| info.offset)
+    }
+
+    fn read(&self, id: &str, offset: usize, len: usize) -> io::Result<Vec<u8>> {
+        let uploads = self.lock();
+        let (_, data) = uploads.get(id).ok_or_else(not_found)?;
+        let start = offset.min(data.len());
+        let end = offset.saturating_add(len).min(data.len());
+        Ok(data[start..end].to_vec())
+    }
+
+    fn truncate(&self, id: &str, offset: usize) -> io::Result<()> {
+        let mut uploads = self.lock();
+        let (info, data) = uploads.get_mut(id).ok_or_else(not_found)?;
+        data.truncate(offset);
+        info.offset = data.len();
+        Ok(())
+    }
+
+    fn concatenate(&self, id: &str, partial_ids: &[String]) -> io::Result<()> {
+        let mut uploads = self.lock();
+        let mut concatenated = Vec::new();
+        for partial_id in partial_ids {
+            let (_, data) = uploads.get(partial_id).ok_or_else(not_found)?;
+            concatenated.extend_from_slice(data);
+        }
+
+        let (info, data) = uploads.get_mut(id).ok_or_else(not_found)?;
+        *data = concatenated;
+        info.offset = data.len();
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> io::Result<bool> {
+        Ok(self.lock().remove(id).is_some())
+    }
+}
+
+pub(super) fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "upload not found")
+}
@@ -0,0 +1,71 @@
+use super::FileInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// An upload as represented by tusd, in `.info` files and hook requests.
+#[derive(Serialize, Deserialize)]
+pub(super) struct TusdFileInfo {
+    #[serde(rename = "ID")]
+    pub(super) id: String,
+    #[serde(rename = "Size")]
+    pub(super) size: usize,
+    #[serde(rename = "SizeIsDeferred", default)]
+    pub(super) size_is_deferred: bool,
+    #[serde(rename = "Offset", default)]
+    pub(super) offset: usize,
+    #[serde(rename = "MetaData", default)]
+    pub(super) metadata: Option<HashMap<String, String>>,
+    #[serde(rename = "IsPartial", default)]
+    pub(super) is_partial: bool,
+    #[serde(rename = "IsFinal", default)]
+    pub(super) is_final: bool,
+    #[serde(rename = "PartialUploads", default)]
+    pub(super) partial_uploads: Option<Vec<String>>,
+    #[serde(rename = "Storage", default)]
+    pub(super) storage: Option<HashMap<String, String>>,
+    /// The expiration time, in seconds since the Unix epoch. This is not written by tusd, which ignores it.
+    #[serde(rename = "Expires", default, skip_serializing_if = "Option::is_none")]
+    pub(super) expires: Option<u64>,
+}
+
+impl TusdFileInfo {
+    pub(super) fn new(info: &FileInfo, storage: Option<HashMap<String, String>>) -> Self {
+        TusdFileInfo {
+            id: info.id.clone(),
+            size: info.size,
+            size_is_deferred: info.size_is_deferred,
+            offset: info.offset,
+            metadata: Some(info.metadata.clone()),
+            is_partial: info.is_partial,
+            is_final: info.is_final(),
+            partial_uploads: info.partial_uploads.clone(),
+            storage,
+            expires: info
+                .expires
+                .and_then(|expires| expires.duration_since(UNIX_EPOCH).ok())
+                .map(|expires| expires.as_secs()),
+        }
+    }
+}
+
+impl From<TusdFileInfo> for FileInfo {
+    fn from(info: TusdFileInfo) -> Self {
+        FileInfo {
+            id: info.id,
+            size: info.size,
+            size_is_deferred: info.size_is_deferred,
+            offset: info.offset,
+            metadata: info.metadata.unwrap_or_default(),
+            expires: info
+                .expires
+                .map(|expires| UNIX_EPOCH + Duration::from_secs(expires)),
+            is_partial: info.is_partial,
+            partial_uploads: if info.is_final {
+                Some(info.partial_uploads.unwrap_or_default())
+            } else {
+                None
+            },
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Reads the JSON state file at `path`. A missing file is read as the default state.
+pub fn load<T: DeserializeOwned + Default>(path: &Path) -> io::Result<T> {
+    match fs::read(path) {
+        Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Writes `state` to the JSON state file at `path`, creating its directory if it doesn't exist.
+///
+/// The state is written to the file at `temp_path(path)` first, which then replaces the state file, so the state file is never seen half written.
+pub fn save<T: Serialize>(path: &Path, state: &T) -> io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = temp_path(path);
+    fs::write(&temp_path, serde_json::to_vec_pretty(state)?)?;
+    fs::rename(&temp_path, path)
+}
+
+/// Get the path of the temporary file written by `save` before it replaces the state file at `path`, which is the path with `.tmp` appended, so state files differing only by extension don't share it.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
@@ -0,0 +1,5 @@
+mod fault;
+mod mock;
+
+pub use self::fault::{Fault, FaultInjector, FaultSchedule, InjectedFault};
+pub use self::mock::{MockRequest, MockServer, MockUpload};
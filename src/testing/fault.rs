@@ -0,0 +1,263 @@
+use crate::headers;
+use crate::http::{HttpHandler, HttpMethod, HttpRequest, HttpResponse, ResponseBody};
+use crate::Error;
+use std::io::{self, Read};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// Enumerates the faults a `FaultInjector` can inject into a request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// The request is never passed to the inner handler, and an error is returned.
+    Drop,
+    /// The request is passed to the inner handler after the given delay.
+    Latency(Duration),
+    /// Only the given fraction (between `0.0` and `1.0`) of the request body is passed to the inner handler, after which an error is returned. This simulates a connection which is lost while sending a chunk.
+    ///
+    /// When downloading, only the given fraction of the response body, as announced by its `Content-Length` header, is returned.
+    Truncate(f64),
+    /// The request is never passed to the inner handler, and a response with the given status code is returned.
+    Status(usize),
+    /// The request is passed to the inner handler, but the value of the given response header is replaced by garbage.
+    CorruptHeader(String),
+}
+
+/// Decides which requests a `FaultInjector` injects faults into.
+#[derive(Debug, Clone)]
+pub enum FaultSchedule {
+    /// Injects the given faults into the requests with the given indices, counting from `0`.
+    Scripted(Vec<(usize, Fault)>),
+    /// Injects a fault into each request with the given probability. The fault is picked at random from `faults`. The same seed always produces the same faults for the same sequence of requests.
+    Seeded {
+        /// The seed of the random number generator.
+        seed: u64,
+        /// The probability (between `0.0` and `1.0`) of a request having a fault injected.
+        probability: f64,
+        /// The faults to pick from.
+        faults: Vec<Fault>,
+    },
+}
+
+/// A fault injected by a `FaultInjector`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InjectedFault {
+    /// The index of the request, counting from `0`.
+    pub request: usize,
+    /// The method of the request.
+    pub method: HttpMethod,
+    /// The fault which was injected.
+    pub fault: Fault,
+}
+
+/// Wraps an `HttpHandler`, injecting faults into the requests passed to it.
+///
+/// Use this to verify that code using a `Client` copes with unreliable networks and servers.
+///
+/// ```rust,ignore
+/// use tus_client::testing::{Fault, FaultInjector, FaultSchedule, MockServer};
+///
+/// let handler = FaultInjector::new(
+///     MockServer::new(),
+///     FaultSchedule::Scripted(vec![(2, Fault::Truncate(0.5)), (4, Fault::Status(503))]),
+/// );
+/// ```
+pub struct FaultInjector<H> {
+    inner: H,
+    methods: Option<Vec<HttpMethod>>,
+    state: Mutex<FaultState>,
+}
+
+struct FaultState {
+    schedule: FaultSchedule,
+    rng: XorShift,
+    requests: usize,
+    injected: Vec<InjectedFault>,
+}
+
+impl<H: HttpHandler> FaultInjector<H> {
+    /// Creates a `FaultInjector` wrapping `inner`, which injects faults according to `schedule`.
+    pub fn new(inner: H, schedule: FaultSchedule) -> Self {
+        let seed = match &schedule {
+            FaultSchedule::Seeded { seed, .. } => *seed,
+            FaultSchedule::Scripted(_) => 0,
+        };
+
+        FaultInjector {
+            inner,
+            methods: None,
+            state: Mutex::new(FaultState {
+                schedule,
+                rng: XorShift::new(seed),
+                requests: 0,
+                injected: Vec::new(),
+            }),
+        }
+    }
+
+    /// Only inject faults into requests with one of the given methods. Other requests are still counted by `FaultSchedule::Scripted`.
+    pub fn with_methods(mut self, methods: &[HttpMethod]) -> Self {
+        self.methods = Some(methods.to_vec());
+        self
+    }
+
+    /// Get the faults which have been injected so far.
+    pub fn injected(&self) -> Vec<InjectedFault> {
+        self.lock().injected.clone()
+    }
+
+    /// Get the inner handler.
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FaultState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn next_fault(&self, method: HttpMethod) -> Option<Fault> {
+        let mut state = self.lock();
+        let request = state.requests;
+        state.requests += 1;
+
+        if let Some(methods) = &self.methods {
+            if !methods.contains(&method) {
+                return None;
+            }
+        }
+
+        let FaultState { schedule, rng, .. } = &mut *state;
+        let fault = match schedule {
+            FaultSchedule::Scripted(faults) => faults
+                .iter()
+                .find(|(index, _)| *index == request)
+                .map(|(_, fault)| fault.clone()),
+            FaultSchedule::Seeded {
+                probability,
+                faults,
+                ..
+            } => {
+                if !faults.is_empty() && rng.next_f64() < *probability {
+                    Some(faults[rng.next_u64() as usize % faults.len()].clone())
+                } else {
+                    None
+                }
+            }
+        }?;
+
+        state.injected.push(InjectedFault {
+            request,
+            method,
+            fault: fault.clone(),
+        });
+        Some(fault)
+    }
+}
+
+impl<H: HttpHandler> HttpHandler for FaultInjector<H> {
+    fn handle_request(&self, mut req: HttpRequest) -> Result<HttpResponse, Error> {
+        let fault = match self.next_fault(req.method) {
+            Some(fault) => fault,
+            None => return self.inner.handle_request(req),
+        };
+
+        match fault {
+            Fault::Drop => Err(Error::HttpHandlerError(String::from(
+                "The request was dropped by the fault injector",
+            ))),
+            Fault::Latency(delay) => {
+                thread::sleep(delay);
+                self.inner.handle_request(req)
+            }
+            Fault::Truncate(fraction) => {
+                if let Some(body) = req.body {
+                    let len = (body.len() as f64 * fraction.clamp(0.0, 1.0)) as usize;
+                    req.body = Some(&body[..len]);
+                }
+                self.inner.handle_request(req)?;
+                Err(Error::HttpHandlerError(String::from(
+                    "The request was truncated by the fault injector",
+                )))
+            }
+            Fault::Status(status_code) => Ok(HttpResponse {
+                status_code,
+                headers: Default::default(),
+            }),
+            Fault::CorruptHeader(name) => {
+                let mut response = self.inner.handle_request(req)?;
+                corrupt_header(&mut response, &name);
+                Ok(response)
+            }
+        }
+    }
+
+    fn handle_download(&self, req: HttpRequest) -> Result<(HttpResponse, ResponseBody<'_>), Error> {
+        let fault = match self.next_fault(req.method) {
+            Some(fault) => fault,
+            None => return self.inner.handle_download(req),
+        };
+
+        match fault {
+            Fault::Drop => Err(Error::HttpHandlerError(String::from(
+                "The request was dropped by the fault injector",
+            ))),
+            Fault::Latency(delay) => {
+                thread::sleep(delay);
+                self.inner.handle_download(req)
+            }
+            Fault::Truncate(fraction) => {
+                let (response, body) = self.inner.handle_download(req)?;
+                let len = response
+                    .headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(headers::CONTENT_LENGTH))
+                    .and_then(|(_, value)| value.parse::<u64>().ok())
+                    .unwrap_or(0);
+                let len = (len as f64 * fraction.clamp(0.0, 1.0)) as u64;
+                Ok((response, Box::new(body.take(len))))
+            }
+            Fault::Status(status_code) => Ok((
+                HttpResponse {
+                    status_code,
+                    headers: Default::default(),
+                },
+                Box::new(io::empty()),
+            )),
+            Fault::CorruptHeader(name) => {
+                let (mut response, body) = self.inner.handle_download(req)?;
+                corrupt_header(&mut response, &name);
+                Ok((response, body))
+            }
+        }
+    }
+}
+
+/// Replaces the value of a response header by garbage.
+fn corrupt_header(response: &mut HttpResponse, name: &str) {
+    for (key, value) in response.headers.iter_mut() {
+        if key.eq_ignore_ascii_case(name) {
+            *value = String::from("corrupted");
+        }
+    }
+}
+
+/// A small, deterministic pseudo random number generator (xorshift64*).
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // The state must never be zero
+        XorShift((seed ^ 0x9E37_79B9_7F4A_7C15).max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
@@ -0,0 +1,483 @@
+use crate::checksum::{self, Checksum, ChecksumError, ReprDigest};
+use crate::headers;
+use crate::http::{Headers, HttpHandler, HttpMethod, HttpRequest, HttpResponse};
+use crate::{metadata, Error, HeaderMap, TusExtension};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+const TUS_VERSION: &str = "1.0.0";
+
+/// An in-memory tus server, implementing `HttpHandler`.
+///
+/// The server supports the core protocol and the creation, expiration, checksum, termination and concatenation extensions. Clones share the same state, so a clone may be passed to a `Client` while another is kept to inspect the uploads and requests received. Configuring a clone with the `with_*` and `without_extension` methods only changes the configuration of that clone.
+///
+/// The URL of an upload is the URL used to create it, followed by `/` and a sequential id.
+#[derive(Clone)]
+pub struct MockServer {
+    config: Arc<MockConfig>,
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Clone)]
+struct MockConfig {
+    max_size: Option<usize>,
+    expiration: Option<Duration>,
+    disabled_extensions: Vec<TusExtension>,
+}
+
+#[derive(Default)]
+struct MockState {
+    next_id: usize,
+    uploads: HashMap<String, MockUpload>,
+    requests: Vec<MockRequest>,
+}
+
+/// An upload stored by a `MockServer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockUpload {
+    /// The URL of the upload.
+    pub url: String,
+    /// The data received so far. The length of the data is the offset of the upload.
+    pub data: Vec<u8>,
+    /// The total size of the upload. This is `0` while the length is deferred.
+    pub length: usize,
+    /// Whether the upload was created without its length, which is declared by a later `PATCH` request.
+    pub length_is_deferred: bool,
+    /// The metadata supplied when the upload was created.
+    pub metadata: HashMap<String, String>,
+    /// When the upload expires, if it's unfinished.
+    pub expires: Option<SystemTime>,
+    /// Whether the upload was created as a partial upload, to be concatenated later.
+    pub is_partial: bool,
+    /// The URLs of the partial uploads this upload was concatenated from, if it's a final upload.
+    pub partial_uploads: Option<Vec<String>>,
+}
+
+impl MockUpload {
+    /// Whether all the data of the upload has been received.
+    pub fn is_finished(&self) -> bool {
+        !self.length_is_deferred && self.data.len() == self.length
+    }
+}
+
+/// A request received by a `MockServer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRequest {
+    /// The method of the request, after applying the `X-HTTP-Method-Override` header.
+    pub method: HttpMethod,
+    /// The URL of the request.
+    pub url: String,
+    /// The headers of the request.
+    pub headers: Headers,
+    /// The length of the request body.
+    pub body_len: usize,
+}
+
+impl MockServer {
+    /// Creates a `MockServer` with no maximum upload size, where uploads never expire.
+    pub fn new() -> Self {
+        MockServer {
+            config: Arc::new(MockConfig {
+                max_size: None,
+                expiration: None,
+                disabled_extensions: Vec::new(),
+            }),
+            state: Arc::new(Mutex::new(MockState::default())),
+        }
+    }
+
+    /// Sets the maximum size of an upload, which is advertised in the `Tus-Max-Size` header.
+    pub fn with_max_size(self, max_size: usize) -> Self {
+        self.configure(|config| config.max_size = Some(max_size))
+    }
+
+    /// Makes unfinished uploads expire `expiration` after they are created.
+    pub fn with_expiration(self, expiration: Duration) -> Self {
+        self.configure(|config| config.expiration = Some(expiration))
+    }
+
+    /// Disables an extension, so it's no longer advertised or handled by the server.
+    pub fn without_extension(self, extension: TusExtension) -> Self {
+        self.configure(|config| config.disabled_extensions.push(extension))
+    }
+
+    /// Get all uploads stored by the server.
+    pub fn uploads(&self) -> Vec<MockUpload> {
+        self.lock().uploads.values().cloned().collect()
+    }
+
+    /// Get the upload with the given URL.
+    pub fn upload(&self, url: &str) -> Option<MockUpload> {
+        self.lock().uploads.get(url).cloned()
+    }
+
+    /// Get all requests received by the server, in the order they were received.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.lock().requests.clone()
+    }
+
+    fn configure(mut self, configure: impl FnOnce(&mut MockConfig)) -> Self {
+        // Clones share the uploads, but a clone configured later gets its own copy of the configuration
+        configure(Arc::make_mut(&mut self.config));
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn supports(&self, extension: &TusExtension) -> bool {
+        match extension {
+            TusExtension::Expiration if self.config.expiration.is_none() => false,
+            _ => !self.config.disabled_extensions.contains(extension),
+        }
+    }
+
+    fn handle(&self, state: &mut MockState, method: HttpMethod, req: &HttpRequest) -> Response {
+        if method == HttpMethod::Options {
+            return self.handle_options();
+        }
+
+        if req
+            .headers
+            .get_by_key(headers::TUS_RESUMABLE)
+            .map(String::as_str)
+            != Some(TUS_VERSION)
+        {
+            return Response::new(412).header(headers::TUS_VERSION, TUS_VERSION);
+        }
+
+        match method {
+            HttpMethod::Post if self.supports(&TusExtension::Creation) => {
+                self.handle_post(state, req)
+            }
+            HttpMethod::Head => self.handle_head(state, req),
+            HttpMethod::Patch => self.handle_patch(state, req),
+            HttpMethod::Delete if self.supports(&TusExtension::Termination) => {
+                match state.uploads.remove(&req.url) {
+                    Some(_) => Response::new(204),
+                    None => Response::new(404),
+                }
+            }
+            _ => Response::new(405),
+        }
+    }
+
+    fn handle_options(&self) -> Response {
+        let extensions = [
+            TusExtension::Creation,
+            TusExtension::CreationDeferLength,
+            TusExtension::Expiration,
+            TusExtension::Checksum,
+            TusExtension::Termination,
+            TusExtension::Concatenation,
+        ]
+        .iter()
+        .filter(|extension| self.supports(extension))
+        .map(TusExtension::as_str)
+        .collect::<Vec<_>>()
+        .join(",");
+
+        let mut response = Response::new(204)
+            .header(headers::TUS_VERSION, TUS_VERSION)
+            .header(headers::TUS_EXTENSION, &extensions);
+        if let Some(max_size) = self.config.max_size {
+            response = response.header(headers::TUS_MAX_SIZE, &max_size.to_string());
+        }
+        if self.supports(&TusExtension::Checksum) {
+            response = response.header(
+                headers::TUS_CHECKSUM_ALGORITHM,
+                checksum::SUPPORTED_ALGORITHMS,
+            );
+        }
+        response
+    }
+
+    fn handle_post(&self, state: &mut MockState, req: &HttpRequest) -> Response {
+        let concat = req
+            .headers
+            .get_by_key(headers::UPLOAD_CONCAT)
+            .filter(|_| self.supports(&TusExtension::Concatenation));
+
+        let (data, length, length_is_deferred, partial_uploads) = match concat.map(|c| c.trim()) {
+            Some(concat) if concat.starts_with("final;") => {
+                let urls: Vec<String> = concat["final;".len()..]
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect();
+                let mut data = Vec::new();
+                for url in &urls {
+                    match state.uploads.get(url) {
+                        Some(partial) if partial.is_partial && partial.is_finished() => {
+                            data.extend_from_slice(&partial.data)
+                        }
+                        _ => return Response::new(400),
+                    }
+                }
+                let length = data.len();
+                (data, length, false, Some(urls))
+            }
+            Some(concat) if concat != "partial" => return Response::new(400),
+            _ => {
+                let defer_length = req
+                    .headers
+                    .get_by_key(headers::UPLOAD_DEFER_LENGTH)
+                    .filter(|_| self.supports(&TusExtension::CreationDeferLength))
+                    .map(|d| d.trim());
+                match (req.headers.get_by_key(headers::UPLOAD_LENGTH), defer_length) {
+                    (Some(length), None) => match length.parse() {
+                        Ok(length) => (Vec::new(), length, false, None),
+                        Err(_) => return Response::new(400),
+                    },
+                    (None, Some("1")) => (Vec::new(), 0, true, None),
+                    _ => return Response::new(400),
+                }
+            }
+        };
+
+        if self
+            .config
+            .max_size
+            .is_some_and(|max_size| length > max_size)
+        {
+            return Response::new(413);
+        }
+
+        let metadata = match req.headers.get_by_key(headers::UPLOAD_METADATA) {
+            Some(data) => match metadata::decode(data) {
+                Some(metadata) => metadata,
+                None => return Response::new(400),
+            },
+            None => HashMap::new(),
+        };
+
+        state.next_id += 1;
+        let url = format!("{}/{}", req.url.trim_end_matches('/'), state.next_id);
+        let upload = MockUpload {
+            url: url.clone(),
+            is_partial: concat.map(|c| c.trim()) == Some("partial"),
+            expires: self
+                .config
+                .expiration
+                .filter(|_| length_is_deferred || data.len() < length)
+                .map(|expiration| SystemTime::now() + expiration),
+            data,
+            length,
+            length_is_deferred,
+            metadata,
+            partial_uploads,
+        };
+
+        let response = Response::new(201)
+            .header(headers::LOCATION, &url)
+            .expires(&upload);
+        state.uploads.insert(url, upload);
+        response
+    }
+
+    fn handle_head(&self, state: &mut MockState, req: &HttpRequest) -> Response {
+        let upload = match find_upload(state, &req.url) {
+            Ok(upload) => upload,
+            Err(response) => return response,
+        };
+
+        let mut response = Response::new(200)
+            .header(headers::UPLOAD_OFFSET, &upload.data.len().to_string())
+            .header("cache-control", "no-store")
+            .expires(upload);
+        response = if upload.length_is_deferred {
+            response.header(headers::UPLOAD_DEFER_LENGTH, "1")
+        } else {
+            response.header(headers::UPLOAD_LENGTH, &upload.length.to_string())
+        };
+        if !upload.metadata.is_empty() {
+            response = response.header(
+                headers::UPLOAD_METADATA,
+                &metadata::encode(&upload.metadata),
+            );
+        }
+        if upload.is_partial {
+            response = response.header(headers::UPLOAD_CONCAT, "partial");
+        }
+        if let Some(partial_uploads) = &upload.partial_uploads {
+            response = response.header(
+                headers::UPLOAD_CONCAT,
+                &format!("final;{}", partial_uploads.join(" ")),
+            );
+        }
+        let wants_digest = req
+            .headers
+            .get_by_key(headers::WANT_REPR_DIGEST)
+            .is_some_and(|header| checksum::wants_repr_digest(header));
+        if wants_digest && upload.is_finished() {
+            let mut digest = ReprDigest::new();
+            digest.update(&upload.data);
+            response = response.header(headers::REPR_DIGEST, &digest.finish());
+        }
+        response
+    }
+
+    fn handle_patch(&self, state: &mut MockState, req: &HttpRequest) -> Response {
+        let upload = match find_upload(state, &req.url) {
+            Ok(upload) => upload,
+            Err(response) => return response,
+        };
+
+        if upload.partial_uploads.is_some() {
+            return Response::new(403);
+        }
+
+        if req
+            .headers
+            .get_by_key(headers::CONTENT_TYPE)
+            .map(String::as_str)
+            != Some("application/offset+octet-stream")
+        {
+            return Response::new(415);
+        }
+
+        let offset = req
+            .headers
+            .get_by_key(headers::UPLOAD_OFFSET)
+            .and_then(|o| o.parse::<usize>().ok());
+        if offset != Some(upload.data.len()) {
+            return Response::new(409);
+        }
+
+        if let Some(length) = req
+            .headers
+            .get_by_key(headers::UPLOAD_LENGTH)
+            .filter(|_| upload.length_is_deferred)
+        {
+            match length.parse::<usize>() {
+                Ok(length) if length >= upload.data.len() => {
+                    if self
+                        .config
+                        .max_size
+                        .is_some_and(|max_size| length > max_size)
+                    {
+                        return Response::new(413);
+                    }
+                    upload.length = length;
+                    upload.length_is_deferred = false;
+                }
+                _ => return Response::new(400),
+            }
+        }
+
+        let body = req.body.unwrap_or_default();
+        let length = if upload.length_is_deferred {
+            self.config.max_size.unwrap_or(usize::MAX)
+        } else {
+            upload.length
+        };
+        if upload.data.len() + body.len() > length {
+            return Response::new(413);
+        }
+
+        if let Some(header) = req
+            .headers
+            .get_by_key(headers::UPLOAD_CHECKSUM)
+            .filter(|_| self.supports(&TusExtension::Checksum))
+        {
+            let result = Checksum::parse(header).and_then(|mut checksum| {
+                checksum.update(body);
+                checksum.verify()
+            });
+            match result {
+                Ok(()) => {}
+                Err(ChecksumError::BadRequest) => return Response::new(400),
+                Err(ChecksumError::Mismatch) => return Response::new(460),
+            }
+        }
+
+        upload.data.extend_from_slice(body);
+        if upload.is_finished() {
+            upload.expires = None;
+        }
+
+        Response::new(204)
+            .header(headers::UPLOAD_OFFSET, &upload.data.len().to_string())
+            .expires(upload)
+    }
+}
+
+impl Default for MockServer {
+    fn default() -> Self {
+        MockServer::new()
+    }
+}
+
+impl HttpHandler for MockServer {
+    fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
+        let method = match req.headers.get_by_key(headers::X_HTTP_METHOD_OVERRIDE) {
+            Some(method) if req.method == HttpMethod::Post => method
+                .parse()
+                .map_err(|_| Error::HttpHandlerError(format!("Unknown method: {}", method)))?,
+            _ => req.method,
+        };
+
+        let mut state = self.lock();
+        state.requests.push(MockRequest {
+            method,
+            url: req.url.clone(),
+            headers: req.headers.clone(),
+            body_len: req.body.map_or(0, <[u8]>::len),
+        });
+
+        let Response {
+            status_code,
+            mut headers,
+        } = self.handle(&mut state, method, &req);
+        headers.insert(headers::TUS_RESUMABLE.to_owned(), TUS_VERSION.to_owned());
+
+        Ok(HttpResponse {
+            headers,
+            status_code,
+        })
+    }
+}
+
+/// Finds an upload which hasn't expired, or the response to send if there is none.
+fn find_upload<'a>(state: &'a mut MockState, url: &str) -> Result<&'a mut MockUpload, Response> {
+    match state.uploads.get_mut(url) {
+        Some(upload)
+            if upload
+                .expires
+                .is_some_and(|expires| expires <= SystemTime::now()) =>
+        {
+            Err(Response::new(410))
+        }
+        Some(upload) => Ok(upload),
+        None => Err(Response::new(404)),
+    }
+}
+
+struct Response {
+    status_code: usize,
+    headers: Headers,
+}
+
+impl Response {
+    fn new(status_code: usize) -> Self {
+        Response {
+            status_code,
+            headers: Headers::new(),
+        }
+    }
+
+    fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    fn expires(self, upload: &MockUpload) -> Self {
+        match upload.expires {
+            Some(expires) => {
+                self.header(headers::UPLOAD_EXPIRES, &httpdate::fmt_http_date(expires))
+            }
+            None => self,
+        }
+    }
+}
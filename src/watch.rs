@@ -0,0 +1,450 @@
+use crate::metadata::{self, RELATIVE_PATH_KEY};
+use crate::{state, Error, SharedClient};
+use notify::{RecursiveMode, Watcher as _};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The longest time between two checks of the files waiting to become stable.
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What is done with a local file once it has been uploaded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AfterUpload {
+    /// The file is left where it is.
+    Keep,
+    /// The file is deleted.
+    Delete,
+    /// The file is moved to the given directory, keeping its path relative to the watched directory.
+    MoveTo(PathBuf),
+}
+
+/// Describes which directory a `Watcher` watches, and how the files appearing in it are uploaded.
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    /// The creation endpoint of the tus server.
+    pub endpoint: String,
+    /// The directory to watch, including its subdirectories.
+    pub directory: PathBuf,
+    /// A file is uploaded once its size and modification time haven't changed for this long.
+    pub stable_for: Duration,
+    /// What is done with a file once it has been uploaded.
+    pub after_upload: AfterUpload,
+    /// The file where the uploads are recorded, so they aren't repeated after a restart.
+    pub state_file: PathBuf,
+    /// Metadata to supply when creating each upload, in addition to `filename` and `relativePath`.
+    pub metadata: HashMap<String, String>,
+}
+
+impl WatchConfig {
+    /// Creates a config uploading the files appearing in `directory` to `endpoint`, once they have been stable for 5 seconds. The files are kept after the upload, and the state is stored in `.tus-watch.json` in the directory.
+    pub fn new(endpoint: &str, directory: impl Into<PathBuf>) -> Self {
+        let directory = directory.into();
+        WatchConfig {
+            endpoint: endpoint.to_owned(),
+            state_file: directory.join(".tus-watch.json"),
+            directory,
+            stable_for: Duration::from_secs(5),
+            after_upload: AfterUpload::Keep,
+            metadata: HashMap::new(),
+        }
+    }
+}
+
+/// Something which happened to a file in the watched directory.
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// The file became stable, and is being uploaded.
+    Uploading {
+        /// The path of the file.
+        path: PathBuf,
+    },
+    /// The file was uploaded, and the `AfterUpload` action was done.
+    Uploaded {
+        /// The path the file had when it was uploaded.
+        path: PathBuf,
+        /// The upload URL of the file.
+        upload_url: String,
+    },
+    /// The upload of the file failed, or the file couldn't be read. It is tried again once the file has been stable for another `WatchConfig::stable_for`, or when it changes.
+    Failed {
+        /// The path of the file.
+        path: PathBuf,
+        /// Why the upload failed.
+        error: Error,
+    },
+}
+
+/// Stops a running `Watcher`. Clones of a handle stop the same watcher.
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    /// Tells the watcher to stop. A file being uploaded is finished first.
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the watcher was told to stop.
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Uploads the files which appear in a directory, once they stop changing.
+///
+/// New and changed files are found through filesystem notifications, and by scanning the directory when the watcher starts. Each upload is recorded in a state file, so a file which was uploaded isn't uploaded again after a restart, and an interrupted upload is resumed.
+pub struct Watcher {
+    client: SharedClient,
+    config: WatchConfig,
+    stop: StopHandle,
+}
+
+/// A file waiting to become stable.
+struct Candidate {
+    size: u64,
+    modified: SystemTime,
+    since: Instant,
+}
+
+/// The uploads of a `Watcher`, keyed by the relative path of the file.
+#[derive(Serialize, Deserialize, Default)]
+struct State {
+    files: BTreeMap<String, FileState>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct FileState {
+    size: u64,
+    modified: SystemTime,
+    upload_url: String,
+    completed: bool,
+}
+
+impl Watcher {
+    /// Creates a watcher uploading files with `client`, as described by `config`.
+    ///
+    /// Fails if the directory doesn't exist, or the directories of the state file and `AfterUpload::MoveTo` can't be created.
+    pub fn new(client: SharedClient, mut config: WatchConfig) -> Result<Self, Error> {
+        // Notifications name files by their canonical path, which the paths of the config are compared with
+        config.directory = fs::canonicalize(&config.directory)?;
+        if let AfterUpload::MoveTo(directory) = &config.after_upload {
+            fs::create_dir_all(directory)?;
+            config.after_upload = AfterUpload::MoveTo(fs::canonicalize(directory)?);
+        }
+        let file_name = config.state_file.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "The state file has no name")
+        })?;
+        let parent = match config.state_file.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+            _ => PathBuf::from("."),
+        };
+        fs::create_dir_all(&parent)?;
+        config.state_file = fs::canonicalize(parent)?.join(file_name);
+
+        Ok(Watcher {
+            client,
+            config,
+            stop: StopHandle::default(),
+        })
+    }
+
+    /// Get a handle which stops the watcher, from another thread or from the callback of `run`.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Watches the directory until the watcher is stopped, calling `on_event` for each upload.
+    ///
+    /// Fails if the directory can't be watched, or the state file can't be read or written. Failed uploads, and files or directories which can't be read, are reported to `on_event` instead.
+    pub fn run(&self, mut on_event: impl FnMut(WatchEvent)) -> Result<(), Error> {
+        let mut state: State = state::load(&self.config.state_file)?;
+        // Files removed while the watcher wasn't running are forgotten
+        let directory = &self.config.directory;
+        state.files.retain(|key, _| directory.join(key).is_file());
+        state::save(&self.config.state_file, &state)?;
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(notify_error)?;
+        watcher
+            .watch(&self.config.directory, RecursiveMode::Recursive)
+            .map_err(notify_error)?;
+
+        let mut candidates = HashMap::new();
+        self.scan(&self.config.directory, &mut candidates, &mut on_event);
+        let poll_interval = self.config.stable_for.min(MAX_POLL_INTERVAL);
+
+        while !self.stop.is_stopped() {
+            match receiver.recv_timeout(poll_interval) {
+                Ok(event) => self.notified(event, &mut candidates, &mut on_event),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            // Handle all pending notifications before checking the candidates
+            while let Ok(event) = receiver.try_recv() {
+                self.notified(event, &mut candidates, &mut on_event);
+            }
+
+            for path in self.stable_files(&mut candidates, &mut on_event) {
+                if self.stop.is_stopped() {
+                    break;
+                }
+                let key = metadata::relative_path(&self.config.directory, &path);
+                let result = match state.uploaded_url(&key, &path) {
+                    Some(_) if self.config.after_upload == AfterUpload::Keep => continue,
+                    // The watcher stopped after uploading the file, but before moving or deleting it
+                    Some(upload_url) => self
+                        .after_upload(&path, &key, &mut state)
+                        .map(|_| upload_url)
+                        .map_err(Error::from),
+                    None => {
+                        on_event(WatchEvent::Uploading { path: path.clone() });
+                        self.upload(&path, &key, &mut state)
+                    }
+                };
+                match result {
+                    Ok(upload_url) => on_event(WatchEvent::Uploaded { path, upload_url }),
+                    Err(error) => {
+                        // The file is tried again once it has been stable for another period
+                        self.scan(&path, &mut candidates, &mut on_event);
+                        on_event(WatchEvent::Failed { path, error });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn notified(
+        &self,
+        event: notify::Result<notify::Event>,
+        candidates: &mut HashMap<PathBuf, Candidate>,
+        on_event: &mut dyn FnMut(WatchEvent),
+    ) {
+        match event {
+            // Reading files and directories, which the watcher does itself, doesn't change them
+            Ok(event) if event.kind.is_access() => {}
+            Ok(event) => {
+                for path in event.paths {
+                    self.scan(&path, candidates, on_event);
+                }
+            }
+            // Notifications may have been lost, so the whole directory is scanned again
+            Err(_) => self.scan(&self.config.directory, candidates, on_event),
+        }
+    }
+
+    /// Adds the file at `path` to the candidates, or all files below it if it's a directory. A file or directory which can't be read is reported as failed, and skipped.
+    fn scan(
+        &self,
+        path: &Path,
+        candidates: &mut HashMap<PathBuf, Candidate>,
+        on_event: &mut dyn FnMut(WatchEvent),
+    ) {
+        if let Err(error) = self.try_scan(path, candidates, on_event) {
+            on_event(WatchEvent::Failed {
+                path: path.to_owned(),
+                error: error.into(),
+            });
+        }
+    }
+
+    fn try_scan(
+        &self,
+        path: &Path,
+        candidates: &mut HashMap<PathBuf, Candidate>,
+        on_event: &mut dyn FnMut(WatchEvent),
+    ) -> io::Result<()> {
+        if self.is_ignored(path) {
+            return Ok(());
+        }
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            // The file was removed or renamed since the notification
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        if metadata.is_dir() {
+            for entry in fs::read_dir(path)? {
+                self.scan(&entry?.path(), candidates, on_event);
+            }
+        } else if metadata.is_file() {
+            let size = metadata.len();
+            let modified = metadata.modified()?;
+            let is_unchanged = candidates
+                .get(path)
+                .is_some_and(|candidate| candidate.size == size && candidate.modified == modified);
+            if !is_unchanged {
+                candidates.insert(
+                    path.to_owned(),
+                    Candidate {
+                        size,
+                        modified,
+                        since: Instant::now(),
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `path` is one of the files written by the watcher itself.
+    fn is_ignored(&self, path: &Path) -> bool {
+        let state_file = &self.config.state_file;
+        if path == state_file || path == state::temp_path(state_file) {
+            return true;
+        }
+        match &self.config.after_upload {
+            AfterUpload::MoveTo(directory) => path.starts_with(directory),
+            _ => false,
+        }
+    }
+
+    /// Removes the candidates which have been stable long enough, and returns them. A candidate which can't be read is reported as failed, and checked again after another period.
+    fn stable_files(
+        &self,
+        candidates: &mut HashMap<PathBuf, Candidate>,
+        on_event: &mut dyn FnMut(WatchEvent),
+    ) -> Vec<PathBuf> {
+        let mut stable = Vec::new();
+        let mut removed = Vec::new();
+        for (path, candidate) in candidates.iter_mut() {
+            let metadata = fs::metadata(path).and_then(|metadata| {
+                let modified = metadata.modified()?;
+                Ok((metadata, modified))
+            });
+            let (metadata, modified) = match metadata {
+                Ok(metadata) => metadata,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                    removed.push(path.clone());
+                    continue;
+                }
+                Err(error) => {
+                    candidate.since = Instant::now();
+                    on_event(WatchEvent::Failed {
+                        path: path.clone(),
+                        error: error.into(),
+                    });
+                    continue;
+                }
+            };
+            if metadata.len() != candidate.size || modified != candidate.modified {
+                candidate.size = metadata.len();
+                candidate.modified = modified;
+                candidate.since = Instant::now();
+            } else if candidate.since.elapsed() >= self.config.stable_for {
+                removed.push(path.clone());
+                stable.push(path.clone());
+            }
+        }
+        for path in removed {
+            candidates.remove(&path);
+        }
+        stable.sort();
+        stable
+    }
+
+    /// Uploads a file, resuming an earlier upload of it if possible, then does the `AfterUpload` action.
+    fn upload(&self, path: &Path, key: &str, state: &mut State) -> Result<String, Error> {
+        let metadata = fs::metadata(path)?;
+        let file = FileState {
+            size: metadata.len(),
+            modified: metadata.modified()?,
+            upload_url: String::new(),
+            completed: false,
+        };
+
+        let previous = state
+            .files
+            .get(key)
+            .filter(|previous| previous.size == file.size && previous.modified == file.modified);
+        let upload_url = match previous {
+            Some(previous) if self.client.can_resume(&previous.upload_url, path)? => {
+                previous.upload_url.clone()
+            }
+            _ => {
+                let mut upload_metadata = self.config.metadata.clone();
+                if let Some(name) = path.file_name() {
+                    upload_metadata.insert(
+                        metadata::FILENAME_KEY.to_owned(),
+                        name.to_string_lossy().into(),
+                    );
+                }
+                upload_metadata.insert(RELATIVE_PATH_KEY.to_owned(), key.to_owned());
+                self.client
+                    .create_with_metadata(&self.config.endpoint, path, upload_metadata)?
+            }
+        };
+        state.files.insert(
+            key.to_owned(),
+            FileState {
+                upload_url: upload_url.clone(),
+                ..file.clone()
+            },
+        );
+        state::save(&self.config.state_file, state)?;
+
+        self.client.upload(&upload_url, path)?;
+
+        state.files.insert(
+            key.to_owned(),
+            FileState {
+                upload_url: upload_url.clone(),
+                completed: true,
+                ..file
+            },
+        );
+        state::save(&self.config.state_file, state)?;
+
+        self.after_upload(path, key, state)?;
+        Ok(upload_url)
+    }
+
+    /// Moves or deletes an uploaded file, as configured.
+    fn after_upload(&self, path: &Path, key: &str, state: &mut State) -> io::Result<()> {
+        match &self.config.after_upload {
+            AfterUpload::Keep => return Ok(()),
+            AfterUpload::Delete => fs::remove_file(path)?,
+            AfterUpload::MoveTo(directory) => {
+                let dest = directory.join(key);
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                // Renaming fails when the directory is on another filesystem
+                if fs::rename(path, &dest).is_err() {
+                    fs::copy(path, &dest)?;
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+        state.files.remove(key);
+        state::save(&self.config.state_file, state)
+    }
+}
+
+impl State {
+    /// Get the upload URL of the file, if it was uploaded completely and hasn't changed since.
+    fn uploaded_url(&self, key: &str, path: &Path) -> Option<String> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?;
+        self.files
+            .get(key)
+            .filter(|file| {
+                file.completed && file.size == metadata.len() && file.modified == modified
+            })
+            .map(|file| file.upload_url.clone())
+    }
+}
+
+fn notify_error(error: notify::Error) -> Error {
+    match error.kind {
+        notify::ErrorKind::Io(error) => Error::IoError(error),
+        _ => Error::IoError(io::Error::other(error.to_string())),
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::NamedTempFile;
+use tus_client::http::{Headers, HttpHandler, HttpMethod, HttpRequest, HttpResponse};
+use tus_client::{ClientBuilder, Error, RetryPolicy};
+
+/// Responds to `HEAD` and `PATCH` requests like a server holding a single upload, failing the first `failures` requests with a `503`.
+#[derive(Default)]
+struct RecordingHandler {
+    total_size: usize,
+    offset: AtomicUsize,
+    failures: AtomicUsize,
+    requests: Mutex<Vec<(HttpMethod, Headers)>>,
+    timeouts: Mutex<Vec<Option<Duration>>>,
+}
+
+impl HttpHandler for &RecordingHandler {
+    fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
+        self.requests
+            .lock()
+            .unwrap()
+            .push((req.method, req.headers.clone()));
+        self.timeouts.lock().unwrap().push(req.timeout);
+
+        let mut headers = HashMap::new();
+        if self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |f| f.checked_sub(1))
+            .is_ok()
+        {
+            return Ok(HttpResponse {
+                status_code: 503,
+                headers,
+            });
+        }
+
+        let status_code = match req.method {
+            HttpMethod::Head => 200,
+            HttpMethod::Patch => {
+                self.offset
+                    .fetch_add(req.body.unwrap().len(), Ordering::SeqCst);
+                204
+            }
+            _ => 204,
+        };
+        headers.insert(
+            "upload-offset".to_owned(),
+            self.offset.load(Ordering::SeqCst).to_string(),
+        );
+        headers.insert("upload-length".to_owned(), self.total_size.to_string());
+
+        Ok(HttpResponse {
+            status_code,
+            headers,
+        })
+    }
+}
+
+fn create_temp_file(size: usize) -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    let buffer: Vec<u8> = (0..size).map(|_| rand::random::<u8>()).collect();
+    temp_file.write_all(&buffer[..]).unwrap();
+    temp_file
+}
+
+#[test]
+fn should_send_configured_headers() {
+    let handler = RecordingHandler::default();
+    let client = ClientBuilder::new()
+        .header("Authorization", "Bearer abc123")
+        .tus_version("0.2.2")
+        .before_request(|req| {
+            req.headers
+                .insert("x-request-hook".to_owned(), "called".to_owned());
+        })
+        .build(&handler);
+
+    client
+        .get_info("/something")
+        .expect("'get_info' call failed");
+
+    let requests = handler.requests.lock().unwrap();
+    let (_, headers) = &requests[0];
+    assert_eq!("Bearer abc123", headers["authorization"]);
+    assert_eq!("0.2.2", headers["tus-resumable"]);
+    assert_eq!("called", headers["x-request-hook"]);
+}
+
+#[test]
+fn should_pass_timeout_to_handler() {
+    let handler = RecordingHandler::default();
+    let client = ClientBuilder::new()
+        .timeout(Duration::from_secs(5))
+        .build(&handler);
+
+    client
+        .get_info("/something")
+        .expect("'get_info' call failed");
+
+    assert_eq!(
+        vec![Some(Duration::from_secs(5))],
+        *handler.timeouts.lock().unwrap()
+    );
+}
+
+#[test]
+fn should_upload_with_configured_chunk_size() {
+    let temp_file = create_temp_file(10_000);
+    let handler = RecordingHandler {
+        total_size: 10_000,
+        ..RecordingHandler::default()
+    };
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let client = {
+        let progress = Arc::clone(&progress);
+        ClientBuilder::new()
+            .chunk_size(3_000)
+            .on_progress(move |uploaded, total| progress.lock().unwrap().push((uploaded, total)))
+            .build(&handler)
+    };
+
+    client
+        .upload("/something", temp_file.path())
+        .expect("'upload' call failed");
+
+    assert_eq!(
+        vec![
+            (3_000, 10_000),
+            (6_000, 10_000),
+            (9_000, 10_000),
+            (10_000, 10_000)
+        ],
+        *progress.lock().unwrap()
+    );
+}
+
+#[test]
+#[should_panic(expected = "The chunk size must be greater than 0")]
+fn should_reject_empty_chunks() {
+    let _ = ClientBuilder::new().chunk_size(0);
+}
+
+#[test]
+fn should_retry_failed_requests() {
+    let temp_file = create_temp_file(10_000);
+    let handler = RecordingHandler {
+        total_size: 10_000,
+        failures: AtomicUsize::new(2),
+        ..RecordingHandler::default()
+    };
+    let client = ClientBuilder::new()
+        .retry_policy(RetryPolicy::new(2, Duration::from_millis(1)))
+        .build(&handler);
+
+    client
+        .upload("/something", temp_file.path())
+        .expect("'upload' call failed");
+
+    let methods: Vec<_> = handler
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(method, _)| *method)
+        .collect();
+    assert_eq!(
+        vec![
+            HttpMethod::Head,
+            HttpMethod::Head,
+            HttpMethod::Head,
+            HttpMethod::Patch
+        ],
+        methods
+    );
+}
+
+#[test]
+fn should_fail_when_retries_are_exhausted() {
+    let handler = RecordingHandler {
+        failures: AtomicUsize::new(3),
+        ..RecordingHandler::default()
+    };
+    let client = ClientBuilder::new()
+        .retry_policy(RetryPolicy::new(2, Duration::from_millis(1)))
+        .build(&handler);
+
+    let result = client.get_server_info("/something");
+
+    match result {
+        Err(Error::UnexpectedStatusCode(503)) => {}
+        _ => panic!("Expected 'Error::UnexpectedStatusCode(503)'"),
+    }
+}
+
+#[test]
+fn should_not_retry_creation() {
+    should_not_retry_creation_with(ClientBuilder::new());
+}
+
+#[test]
+fn should_not_retry_creation_with_method_override() {
+    should_not_retry_creation_with(ClientBuilder::new().method_override(true));
+}
+
+fn should_not_retry_creation_with(builder: ClientBuilder) {
+    let temp_file = create_temp_file(1_000);
+    let handler = RecordingHandler {
+        failures: AtomicUsize::new(1),
+        ..RecordingHandler::default()
+    };
+    let client = builder
+        .retry_policy(RetryPolicy::new(2, Duration::from_millis(1)))
+        .build(&handler);
+
+    match client.create("/files/", temp_file.path()) {
+        Err(Error::UnexpectedStatusCode(503)) => {}
+        result => panic!(
+            "Expected 'Error::UnexpectedStatusCode(503)', got {:?}",
+            result
+        ),
+    }
+    assert_eq!(1, handler.requests.lock().unwrap().len());
+}
@@ -0,0 +1,319 @@
+#![cfg(feature = "cli")]
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Output};
+use std::sync::Arc;
+use tempfile::NamedTempFile;
+use tokio::runtime::Runtime;
+use tus_client::http::{HttpMethod, HttpRequest};
+use tus_client::server::{DataStore, MemoryStore, Server, TusService};
+
+fn create_temp_file(size: usize) -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    let buffer: Vec<u8> = (0..size).map(|_| rand::random::<u8>()).collect();
+    temp_file.write_all(&buffer[..]).unwrap();
+    temp_file
+}
+
+/// Serves `server` on a random local port, returning its creation endpoint.
+fn serve(runtime: &Runtime, server: Arc<Server<MemoryStore>>) -> String {
+    let listener = runtime
+        .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+        .unwrap();
+    let address = listener.local_addr().unwrap();
+    let router = axum::Router::new().nest_service("/files", TusService::new(server));
+    runtime.spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{}/files/", address)
+}
+
+fn tus(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tus"))
+        .arg("--json")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout_json(output: &Output) -> Value {
+    assert!(
+        output.status.success(),
+        "tus failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+fn upload_id(upload_url: &str) -> &str {
+    upload_url.rsplit('/').next().unwrap()
+}
+
+#[test]
+fn should_upload_download_and_delete_file() {
+    let runtime = Runtime::new().unwrap();
+    let server = Arc::new(Server::new(MemoryStore::new()));
+    let endpoint = serve(&runtime, Arc::clone(&server));
+    let temp_file = create_temp_file(100_000);
+    let directory = tempfile::tempdir().unwrap();
+    let state_file = directory.path().join("uploads.json");
+    let path = temp_file.path().to_str().unwrap();
+
+    let info = stdout_json(&tus(&["server-info", &endpoint]));
+    assert_eq!("1.0.0", info["versions"][0]);
+    assert!(info["extensions"]
+        .as_array()
+        .unwrap()
+        .contains(&Value::from("creation")));
+
+    let upload = stdout_json(&tus(&[
+        "upload",
+        &endpoint,
+        path,
+        "--metadata",
+        "filename=file.bin",
+        "--chunk-size",
+        "30000",
+        "--state-file",
+        state_file.to_str().unwrap(),
+    ]));
+    let upload_url = upload["url"].as_str().unwrap();
+    assert_eq!(false, upload["resumed"]);
+    let stored = server.store().get(upload_id(upload_url)).unwrap().unwrap();
+    assert_eq!(100_000, stored.offset);
+    assert_eq!("file.bin", stored.metadata["filename"]);
+
+    let info = stdout_json(&tus(&["info", upload_url]));
+    assert_eq!(100_000, info["offset"]);
+    assert_eq!(100_000, info["size"]);
+    assert_eq!("file.bin", info["metadata"]["filename"]);
+
+    let download = directory.path().join("download.bin");
+    stdout_json(&tus(&["download", upload_url, download.to_str().unwrap()]));
+    assert_eq!(
+        std::fs::read(temp_file.path()).unwrap(),
+        std::fs::read(&download).unwrap()
+    );
+
+    stdout_json(&tus(&["delete", upload_url]));
+    let output = tus(&["info", upload_url]);
+    assert_eq!(Some(3), output.status.code());
+    let error: Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!("not_found", error["error"]["kind"]);
+}
+
+#[test]
+fn should_resume_unfinished_upload() {
+    let runtime = Runtime::new().unwrap();
+    let server = Arc::new(Server::new(MemoryStore::new()));
+    let endpoint = serve(&runtime, Arc::clone(&server));
+    let temp_file = create_temp_file(1_000);
+    let data = std::fs::read(temp_file.path()).unwrap();
+    let directory = tempfile::tempdir().unwrap();
+    let state_file = directory.path().join("uploads.json");
+
+    // An earlier run created the upload, and sent part of the file
+    let mut headers = HashMap::new();
+    headers.insert("tus-resumable".to_owned(), "1.0.0".to_owned());
+    headers.insert("upload-length".to_owned(), "1000".to_owned());
+    let response = server.handle(HttpRequest {
+        method: HttpMethod::Post,
+        headers: headers.clone(),
+        url: endpoint.clone(),
+        body: None,
+        timeout: None,
+    });
+    let upload_url = response.headers["location"].clone();
+    headers.insert("upload-offset".to_owned(), "0".to_owned());
+    headers.insert(
+        "content-type".to_owned(),
+        "application/offset+octet-stream".to_owned(),
+    );
+    server.handle(HttpRequest {
+        method: HttpMethod::Patch,
+        headers,
+        url: upload_url.clone(),
+        body: Some(&data[..400]),
+        timeout: None,
+    });
+    let key = format!(
+        "{} {}",
+        endpoint,
+        std::fs::canonicalize(temp_file.path()).unwrap().display()
+    );
+    std::fs::write(
+        &state_file,
+        serde_json::json!({ "uploads": { key: upload_url } }).to_string(),
+    )
+    .unwrap();
+
+    let upload = stdout_json(&tus(&[
+        "upload",
+        &endpoint,
+        temp_file.path().to_str().unwrap(),
+        "--resume",
+        "--state-file",
+        state_file.to_str().unwrap(),
+    ]));
+    assert_eq!(upload_url, upload["url"]);
+    assert_eq!(true, upload["resumed"]);
+    assert_eq!(Some(data), server.store().data(upload_id(&upload_url)));
+    assert_eq!(1, server.store().list().unwrap().len());
+
+    // Finished uploads are removed from the state file
+    let state: Value = serde_json::from_slice(&std::fs::read(&state_file).unwrap()).unwrap();
+    assert!(state["uploads"].as_object().unwrap().is_empty());
+}
+
+#[test]
+fn should_map_errors_to_exit_codes() {
+    let temp_file = create_temp_file(10);
+    let directory = tempfile::tempdir().unwrap();
+    let state_file = directory.path().join("uploads.json");
+    let output = tus(&[
+        "upload",
+        "http://127.0.0.1:1/files/",
+        temp_file.path().to_str().unwrap(),
+        "--retries",
+        "0",
+        "--state-file",
+        state_file.to_str().unwrap(),
+    ]);
+    assert_eq!(Some(5), output.status.code());
+
+    let output = tus(&["upload", "http://127.0.0.1:1/files/", "missing.bin"]);
+    assert_eq!(Some(4), output.status.code());
+
+    let output = tus(&["upload", "http://127.0.0.1:1/files/", "file", "-m", "a b=c"]);
+    assert_eq!(Some(2), output.status.code());
+}
+
+#[test]
+fn should_upload_directory_in_batch() {
+    let runtime = Runtime::new().unwrap();
+    let server = Arc::new(Server::new(MemoryStore::new()));
+    let endpoint = serve(&runtime, Arc::clone(&server));
+    let directory = tempfile::tempdir().unwrap();
+    let files = directory.path().join("files");
+    std::fs::create_dir_all(files.join("nested")).unwrap();
+    std::fs::write(files.join("a.bin"), vec![1; 1_000]).unwrap();
+    std::fs::write(files.join("nested").join("b.bin"), vec![2; 2_000]).unwrap();
+    let manifest = directory.path().join("manifest.json");
+    let report_path = directory.path().join("report.json");
+
+    let report = stdout_json(&tus(&[
+        "batch",
+        &endpoint,
+        files.to_str().unwrap(),
+        "-m",
+        "owner=test",
+        "--concurrency",
+        "2",
+        "--manifest",
+        manifest.to_str().unwrap(),
+        "--report",
+        report_path.to_str().unwrap(),
+    ]));
+    assert_eq!(2, report["total"]);
+    assert_eq!(2, report["completed"]);
+    assert_eq!(0, report["failed"]);
+    assert_eq!(3_000, report["bytes_uploaded"]);
+    let written: Value = serde_json::from_slice(&std::fs::read(&report_path).unwrap()).unwrap();
+    assert_eq!(report, written);
+
+    let mut relative_paths = Vec::new();
+    for file in report["files"].as_array().unwrap() {
+        let stored = server
+            .store()
+            .get(upload_id(file["upload_url"].as_str().unwrap()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(file["size"], stored.offset);
+        assert_eq!("test", stored.metadata["owner"]);
+        relative_paths.push(stored.metadata["relativePath"].clone());
+    }
+    assert_eq!(vec!["a.bin", "nested/b.bin"], relative_paths);
+}
+
+#[test]
+fn should_skip_and_resume_files_of_manifest() {
+    let runtime = Runtime::new().unwrap();
+    let server = Arc::new(Server::new(MemoryStore::new()));
+    let endpoint = serve(&runtime, Arc::clone(&server));
+    let directory = tempfile::tempdir().unwrap();
+    let files = directory.path().join("files");
+    std::fs::create_dir_all(&files).unwrap();
+    std::fs::write(files.join("a.csv"), vec![1; 1_000]).unwrap();
+    std::fs::write(files.join("b.txt"), vec![2; 1_000]).unwrap();
+    let manifest = directory.path().join("manifest.json");
+    let pattern = format!("{}/*.csv", files.display());
+    let args = |source: &str| {
+        tus(&[
+            "batch",
+            &endpoint,
+            source,
+            "--manifest",
+            manifest.to_str().unwrap(),
+        ])
+    };
+
+    let report = stdout_json(&args(&pattern));
+    assert_eq!(1, report["completed"]);
+    assert_eq!("a.csv", report["files"][0]["path"]);
+
+    // A file is added, whose upload was interrupted by an earlier run
+    let data = vec![3; 1_000];
+    std::fs::write(files.join("c.csv"), &data).unwrap();
+    let mut headers = HashMap::new();
+    headers.insert("tus-resumable".to_owned(), "1.0.0".to_owned());
+    headers.insert("upload-length".to_owned(), "1000".to_owned());
+    let response = server.handle(HttpRequest {
+        method: HttpMethod::Post,
+        headers: headers.clone(),
+        url: endpoint.clone(),
+        body: None,
+        timeout: None,
+    });
+    let upload_url = response.headers["location"].clone();
+    headers.insert("upload-offset".to_owned(), "0".to_owned());
+    headers.insert(
+        "content-type".to_owned(),
+        "application/offset+octet-stream".to_owned(),
+    );
+    server.handle(HttpRequest {
+        method: HttpMethod::Patch,
+        headers,
+        url: upload_url.clone(),
+        body: Some(&data[..400]),
+        timeout: None,
+    });
+    let mut contents: Value = serde_json::from_slice(&std::fs::read(&manifest).unwrap()).unwrap();
+    let key = std::fs::canonicalize(files.join("c.csv"))
+        .unwrap()
+        .display()
+        .to_string();
+    contents["files"][key] = serde_json::json!({
+        "relative_path": "c.csv",
+        "endpoint": endpoint,
+        "size": 1000,
+        "upload_url": upload_url,
+        "status": "uploading",
+    });
+    std::fs::write(&manifest, contents.to_string()).unwrap();
+
+    let report = stdout_json(&args(&pattern));
+    assert_eq!(2, report["total"]);
+    assert_eq!(1, report["skipped"]);
+    assert_eq!(1, report["completed"]);
+    assert_eq!("skipped", report["files"][0]["status"]);
+    assert_eq!(upload_url, report["files"][1]["upload_url"]);
+    assert_eq!(Some(data), server.store().data(upload_id(&upload_url)));
+    assert_eq!(2, server.store().list().unwrap().len());
+
+    let contents: Value = serde_json::from_slice(&std::fs::read(&manifest).unwrap()).unwrap();
+    assert!(contents["files"]
+        .as_object()
+        .unwrap()
+        .values()
+        .all(|entry| entry["status"] != "uploading"));
+}
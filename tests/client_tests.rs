@@ -1,8 +1,10 @@
 use std::collections::HashMap;
-use std::io::Write;
+use std::io::{Cursor, Write};
+use std::thread;
 use tempfile::NamedTempFile;
+#[allow(clippy::single_component_path_imports)]
 use tus_client;
-use tus_client::http::{HttpHandler, HttpMethod, HttpRequest, HttpResponse};
+use tus_client::http::{HttpHandler, HttpMethod, HttpRequest, HttpResponse, ResponseBody};
 use tus_client::{Error, TusExtension};
 
 struct TestHandler {
@@ -49,7 +51,9 @@ impl HttpHandler for TestHandler {
             }
             HttpMethod::Options => {
                 let mut headers = HashMap::new();
-                headers.insert("tus-version".to_owned(), self.tus_version.clone());
+                if !self.tus_version.is_empty() {
+                    headers.insert("tus-version".to_owned(), self.tus_version.clone());
+                }
                 headers.insert("tus-extension".to_owned(), self.extensions.clone());
                 headers.insert("tus-max-size".to_owned(), self.max_upload_size.to_string());
 
@@ -97,9 +101,49 @@ impl HttpHandler for TestHandler {
                     headers,
                 })
             }
-            _ => unreachable!(),
+            HttpMethod::Get => {
+                let (start, end) = self.download_range(&req);
+                let mut headers = HashMap::new();
+                headers.insert(
+                    "content-range".to_owned(),
+                    format!("bytes {}-{}/{}", start, end - 1, self.total_upload_size),
+                );
+
+                Ok(HttpResponse {
+                    status_code: if start > 0 { 206 } else { 200 },
+                    headers,
+                })
+            }
         }
     }
+
+    fn handle_download(&self, req: HttpRequest) -> Result<(HttpResponse, ResponseBody<'_>), Error> {
+        let (start, end) = self.download_range(&req);
+        let response = self.handle_request(req)?;
+        let body = download_data(self.total_upload_size)[start..end].to_vec();
+        Ok((response, Box::new(Cursor::new(body))))
+    }
+}
+
+impl TestHandler {
+    /// Get the range of the data sent for a `GET` request. At most 1000 bytes of the requested range are sent, like an interrupted response.
+    fn download_range(&self, req: &HttpRequest) -> (usize, usize) {
+        let start = req
+            .headers
+            .get("range")
+            .map(|range| {
+                range["bytes=".len()..]
+                    .trim_end_matches('-')
+                    .parse()
+                    .unwrap()
+            })
+            .unwrap_or(0);
+        (start, self.total_upload_size.min(start + 1000))
+    }
+}
+
+fn download_data(size: usize) -> Vec<u8> {
+    (0..size).map(|i| i as u8).collect()
 }
 
 fn create_temp_file() -> NamedTempFile {
@@ -172,6 +216,49 @@ fn should_return_server_info() {
     assert_eq!(12345, result.max_upload_size.unwrap());
 }
 
+#[test]
+fn should_preserve_unknown_extensions() {
+    let client = tus_client::Client::new(TestHandler {
+        status_code: 204,
+        extensions: String::from(
+            "creation,creation-with-upload,creation-defer-length,checksum-trailer,x-custom",
+        ),
+        ..TestHandler::default()
+    });
+
+    let result = client
+        .get_server_info("/something")
+        .expect("'get_server_info' call failed");
+
+    assert_eq!(
+        vec![
+            TusExtension::Creation,
+            TusExtension::CreationWithUpload,
+            TusExtension::CreationDeferLength,
+            TusExtension::ChecksumTrailer,
+            TusExtension::Other(String::from("x-custom"))
+        ],
+        result.extensions
+    );
+    assert_eq!("x-custom", result.extensions[4].to_string());
+}
+
+#[test]
+fn should_return_missing_header_without_tus_version() {
+    let client = tus_client::Client::new(TestHandler {
+        status_code: 204,
+        tus_version: String::new(),
+        ..TestHandler::default()
+    });
+
+    let result = client.get_server_info("/something");
+
+    match result {
+        Err(Error::MissingHeader(header)) => assert_eq!("tus-version", header),
+        _ => panic!("Expected 'Error::MissingHeader'"),
+    }
+}
+
 #[test]
 fn should_upload_file() {
     let temp_file = create_temp_file();
@@ -253,3 +340,143 @@ fn should_receive_204_after_deleting_file() {
 
     client.delete("/something").expect("'delete' call failed");
 }
+
+#[test]
+fn should_share_client_between_threads() {
+    let client = tus_client::Client::new_shared(TestHandler {
+        status_code: 204,
+        ..TestHandler::default()
+    });
+
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let client = client.clone();
+            thread::spawn(move || client.get_info("/something").map(|i| i.bytes_uploaded))
+        })
+        .collect();
+
+    for worker in workers {
+        assert_eq!(
+            1234,
+            worker.join().unwrap().expect("'get_info' call failed")
+        );
+    }
+}
+
+#[test]
+fn should_cache_server_info_when_connecting() {
+    let client = tus_client::Client::new(TestHandler {
+        status_code: 204,
+        extensions: String::from("creation,termination"),
+        ..TestHandler::default()
+    });
+
+    assert!(client.server_info("/something").is_none());
+
+    client.connect("/something").expect("'connect' call failed");
+
+    let info = client.server_info("/something").unwrap();
+    assert_eq!(vec!["1.0.0"], info.supported_versions);
+    assert_eq!(Some(12345), info.max_upload_size);
+}
+
+#[test]
+fn should_cache_server_info_per_endpoint() {
+    let client = tus_client::Client::new(TestHandler {
+        status_code: 204,
+        extensions: String::from("creation"),
+        ..TestHandler::default()
+    });
+
+    client.connect("/something").expect("'connect' call failed");
+
+    assert!(client.server_info("/something/upload").is_some());
+    assert!(client.server_info("/other").is_none());
+    client
+        .delete("/other")
+        .expect("'delete' call failed for another endpoint");
+}
+
+#[test]
+fn should_fail_to_connect_to_server_with_unsupported_version() {
+    let client = tus_client::Client::new(TestHandler {
+        status_code: 204,
+        tus_version: String::from("0.2.2"),
+        ..TestHandler::default()
+    });
+
+    let result = client.connect("/something");
+
+    match result {
+        Err(Error::UnsupportedVersion(version)) => assert_eq!("1.0.0", version),
+        _ => panic!("Expected 'Error::UnsupportedVersion'"),
+    }
+    assert!(client.server_info("/something").is_none());
+}
+
+#[test]
+fn should_fail_early_when_extension_is_unsupported() {
+    let client = tus_client::Client::new(TestHandler {
+        status_code: 204,
+        extensions: String::from("creation"),
+        ..TestHandler::default()
+    });
+    client.connect("/something").expect("'connect' call failed");
+
+    let result = client.delete("/something");
+
+    match result {
+        Err(Error::UnsupportedExtension(TusExtension::Termination)) => {}
+        _ => panic!("Expected 'Error::UnsupportedExtension(TusExtension::Termination)'"),
+    }
+}
+
+#[test]
+fn should_fail_early_when_file_is_too_large() {
+    let temp_file = create_temp_file();
+    let client = tus_client::Client::new(TestHandler {
+        status_code: 204,
+        extensions: String::from("creation"),
+        ..TestHandler::default()
+    });
+    client.connect("/something").expect("'connect' call failed");
+
+    let result = client.create("/something", temp_file.path());
+
+    match result {
+        Err(Error::FileTooLarge) => {}
+        _ => panic!("Expected 'Error::FileTooLarge'"),
+    }
+}
+
+#[test]
+fn should_resume_download() {
+    let client = tus_client::Client::new(TestHandler::default());
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(&download_data(2345)[..500]).unwrap();
+
+    client
+        .download("/something", temp_file.path())
+        .expect("'download' call failed");
+
+    assert_eq!(
+        download_data(2345),
+        std::fs::read(temp_file.path()).unwrap()
+    );
+}
+
+#[test]
+fn should_restart_download_longer_than_upload() {
+    let client = tus_client::Client::new(TestHandler::default());
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(&[1; 3000]).unwrap();
+
+    client
+        .download("/something", temp_file.path())
+        .expect("'download' call failed");
+
+    assert_eq!(
+        download_data(2345),
+        std::fs::read(temp_file.path()).unwrap()
+    );
+}
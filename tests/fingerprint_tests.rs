@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+use tus_client::fingerprint::{Fingerprint, FingerprintMode, FINGERPRINT_KEY};
+use tus_client::http::{HttpHandler, HttpMethod, HttpRequest};
+use tus_client::testing::MockServer;
+use tus_client::{ClientBuilder, Error};
+
+fn create_temp_file(size: usize) -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    let buffer: Vec<u8> = (0..size).map(|_| rand::random::<u8>()).collect();
+    temp_file.write_all(&buffer[..]).unwrap();
+    temp_file
+}
+
+/// Sends the first `len` bytes of the file, as an earlier, interrupted upload would have.
+fn upload_part(server: &MockServer, upload_url: &str, path: &Path, len: usize) {
+    let data = std::fs::read(path).unwrap();
+    let mut headers = HashMap::new();
+    headers.insert("tus-resumable".to_owned(), "1.0.0".to_owned());
+    headers.insert("upload-offset".to_owned(), "0".to_owned());
+    headers.insert(
+        "content-type".to_owned(),
+        "application/offset+octet-stream".to_owned(),
+    );
+    let response = server
+        .handle_request(HttpRequest {
+            method: HttpMethod::Patch,
+            headers,
+            url: upload_url.to_owned(),
+            body: Some(&data[..len]),
+            timeout: None,
+        })
+        .unwrap();
+    assert_eq!(204, response.status_code);
+}
+
+/// Overwrites part of a file in place, keeping its size and modification time.
+fn overwrite_keeping_mtime(path: &Path, offset: u64, data: &[u8]) {
+    let modified = std::fs::metadata(path).unwrap().modified().unwrap();
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(data).unwrap();
+    file.set_modified(modified).unwrap();
+}
+
+#[test]
+fn should_store_fingerprint_in_metadata() {
+    let temp_file = create_temp_file(3 * 1024 * 1024);
+    let server = MockServer::new();
+    let client = ClientBuilder::new()
+        .fingerprint(FingerprintMode::Sampled)
+        .build(server.clone());
+
+    let upload_url = client
+        .create("/files/", temp_file.path())
+        .expect("'create' call failed");
+
+    let stored = Fingerprint::parse(&server.upload(&upload_url).unwrap().metadata[FINGERPRINT_KEY])
+        .expect("The fingerprint is malformed");
+    assert_eq!(
+        Fingerprint::compute(temp_file.path(), FingerprintMode::Sampled).unwrap(),
+        stored
+    );
+    assert_eq!(3 * 1024 * 1024, stored.size);
+    assert!(stored.modified.is_some());
+
+    // Changes between the sampled parts of the file aren't noticed
+    overwrite_keeping_mtime(temp_file.path(), 1024 * 1024 + 10, &[0; 10]);
+    assert!(stored.matches(temp_file.path()).unwrap());
+    overwrite_keeping_mtime(temp_file.path(), 10, &[0; 10]);
+    assert!(!stored.matches(temp_file.path()).unwrap());
+}
+
+#[test]
+fn should_refuse_to_resume_modified_file() {
+    let temp_file = create_temp_file(10_000);
+    let server = MockServer::new();
+    let client = ClientBuilder::new()
+        .fingerprint(FingerprintMode::Full)
+        .chunk_size(3_000)
+        .build(server.clone());
+
+    let upload_url = client
+        .create("/files/", temp_file.path())
+        .expect("'create' call failed");
+    upload_part(&server, &upload_url, temp_file.path(), 4_000);
+
+    // The file is rewritten in place, with the same size and modification time
+    overwrite_keeping_mtime(temp_file.path(), 0, &[0; 100]);
+    match client.upload(&upload_url, temp_file.path()) {
+        Err(Error::FileChanged) => {}
+        result => panic!("Expected 'Error::FileChanged', got {:?}", result),
+    }
+    assert_eq!(4_000, server.upload(&upload_url).unwrap().data.len());
+}
+
+#[test]
+fn should_fail_when_file_is_modified_during_upload() {
+    let temp_file = create_temp_file(10_000);
+    let path = temp_file.path().to_owned();
+    let server = MockServer::new();
+    let client = ClientBuilder::new()
+        .fingerprint(FingerprintMode::Metadata)
+        .chunk_size(3_000)
+        .on_progress(move |uploaded, _| {
+            // The file is replaced by a shorter one, after the first chunk
+            if uploaded == 3_000 {
+                File::create(&path).unwrap().write_all(&[0; 5_000]).unwrap();
+            }
+        })
+        .build(server.clone());
+
+    let upload_url = client
+        .create("/files/", temp_file.path())
+        .expect("'create' call failed");
+    match client.upload(&upload_url, temp_file.path()) {
+        Err(Error::FileChanged) => {}
+        result => panic!("Expected 'Error::FileChanged', got {:?}", result),
+    }
+    assert_eq!(3_000, server.upload(&upload_url).unwrap().data.len());
+}
+
+#[test]
+fn should_resume_unmodified_file() {
+    let temp_file = create_temp_file(10_000);
+    let server = MockServer::new();
+    let client = ClientBuilder::new()
+        .fingerprint(FingerprintMode::Full)
+        .build(server.clone());
+
+    let upload_url = client
+        .create("/files/", temp_file.path())
+        .expect("'create' call failed");
+    upload_part(&server, &upload_url, temp_file.path(), 4_000);
+
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+    assert!(server.upload(&upload_url).unwrap().is_finished());
+}
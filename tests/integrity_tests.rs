@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+use tus_client::http::{HttpHandler, HttpMethod, HttpRequest};
+use tus_client::integrity::{Digest, DigestAlgorithm, DIGEST_KEY};
+use tus_client::testing::MockServer;
+use tus_client::{ClientBuilder, Error};
+
+fn create_temp_file(size: usize) -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    let buffer: Vec<u8> = (0..size).map(|_| rand::random::<u8>()).collect();
+    temp_file.write_all(&buffer[..]).unwrap();
+    temp_file
+}
+
+/// Sends the first `len` bytes of the file, as an earlier, interrupted upload would have.
+fn upload_part(server: &MockServer, upload_url: &str, path: &Path, len: usize) {
+    let data = std::fs::read(path).unwrap();
+    let mut headers = HashMap::new();
+    headers.insert("tus-resumable".to_owned(), "1.0.0".to_owned());
+    headers.insert("upload-offset".to_owned(), "0".to_owned());
+    headers.insert(
+        "content-type".to_owned(),
+        "application/offset+octet-stream".to_owned(),
+    );
+    let response = server
+        .handle_request(HttpRequest {
+            method: HttpMethod::Patch,
+            headers,
+            url: upload_url.to_owned(),
+            body: Some(&data[..len]),
+            timeout: None,
+        })
+        .unwrap();
+    assert_eq!(204, response.status_code);
+}
+
+#[test]
+fn should_upload_with_digest_and_verify_it() {
+    let temp_file = create_temp_file(10_000);
+    let server = MockServer::new();
+    let client = ClientBuilder::new()
+        .digest(DigestAlgorithm::Sha256)
+        .chunk_size(3_000)
+        .build(server.clone());
+    let expected = Digest::compute(temp_file.path(), DigestAlgorithm::Sha256).unwrap();
+
+    let upload_url = client
+        .create("/files/", temp_file.path())
+        .expect("'create' call failed");
+    assert_eq!(
+        Some(expected.clone()),
+        Digest::parse(&server.upload(&upload_url).unwrap().metadata[DIGEST_KEY])
+    );
+
+    let digest = client
+        .upload_with_digest(&upload_url, temp_file.path(), DigestAlgorithm::Sha256)
+        .expect("'upload_with_digest' call failed");
+    assert_eq!(expected, digest);
+    client
+        .verify_upload(&upload_url, &digest)
+        .expect("'verify_upload' call failed");
+}
+
+#[test]
+fn should_hash_uploaded_part_when_resuming() {
+    let temp_file = create_temp_file(10_000);
+    let server = MockServer::new();
+    let client = ClientBuilder::new()
+        .digest(DigestAlgorithm::Blake3)
+        .chunk_size(3_000)
+        .build(server.clone());
+
+    let upload_url = client
+        .create("/files/", temp_file.path())
+        .expect("'create' call failed");
+    upload_part(&server, &upload_url, temp_file.path(), 4_000);
+
+    let digest = client
+        .upload_with_digest(&upload_url, temp_file.path(), DigestAlgorithm::Blake3)
+        .expect("'upload_with_digest' call failed");
+    assert_eq!(
+        Digest::compute(temp_file.path(), DigestAlgorithm::Blake3).unwrap(),
+        digest
+    );
+    let sent: usize = server
+        .requests()
+        .iter()
+        .filter(|request| request.method == HttpMethod::Patch)
+        .map(|request| request.body_len)
+        .sum();
+    assert_eq!(10_000, sent);
+
+    // The mock server only computes SHA-256 digests
+    match client.verify_upload(&upload_url, &digest) {
+        Err(Error::MissingHeader(header)) => assert_eq!("repr-digest", header),
+        result => panic!("Expected 'Error::MissingHeader', got {:?}", result),
+    }
+}
+
+#[test]
+fn should_fail_when_uploaded_data_does_not_match_digest() {
+    let temp_file = create_temp_file(10_000);
+    let server = MockServer::new();
+    let client = ClientBuilder::new()
+        .digest(DigestAlgorithm::Sha256)
+        .build(server.clone());
+    let original = Digest::compute(temp_file.path(), DigestAlgorithm::Sha256).unwrap();
+
+    let upload_url = client
+        .create("/files/", temp_file.path())
+        .expect("'create' call failed");
+    // The file is modified after its digest was stored
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(temp_file.path())
+        .unwrap();
+    file.seek(SeekFrom::Start(5_000)).unwrap();
+    file.write_all(&[0; 100]).unwrap();
+
+    match client.upload_with_digest(&upload_url, temp_file.path(), DigestAlgorithm::Sha256) {
+        Err(Error::DigestMismatch) => {}
+        result => panic!("Expected 'Error::DigestMismatch', got {:?}", result),
+    }
+    match client.verify_upload(&upload_url, &original) {
+        Err(Error::DigestMismatch) => {}
+        result => panic!("Expected 'Error::DigestMismatch', got {:?}", result),
+    }
+}
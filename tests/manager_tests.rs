@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tempfile::NamedTempFile;
+use tus_client::http::{HttpHandler, HttpMethod, HttpRequest, HttpResponse};
+use tus_client::manager::{JobStatus, UploadJob, UploadManager};
+use tus_client::Error;
+
+#[derive(Default)]
+struct MemoryHandler {
+    uploads: Mutex<HashMap<String, (usize, usize)>>,
+    created: Mutex<Vec<String>>,
+    patch_delay: Duration,
+}
+
+impl HttpHandler for MemoryHandler {
+    fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
+        let mut uploads = self.uploads.lock().unwrap();
+        let mut headers = HashMap::new();
+
+        let status_code = match req.method {
+            HttpMethod::Post => {
+                let url = format!("{}{}", req.url, uploads.len());
+                let length = req.headers["upload-length"].parse().unwrap();
+                uploads.insert(url.clone(), (0, length));
+                self.created.lock().unwrap().push(
+                    req.headers
+                        .get("upload-metadata")
+                        .cloned()
+                        .unwrap_or_default(),
+                );
+                headers.insert("location".to_owned(), url);
+                201
+            }
+            HttpMethod::Head => match uploads.get(&req.url) {
+                Some((offset, length)) => {
+                    headers.insert("upload-offset".to_owned(), offset.to_string());
+                    headers.insert("upload-length".to_owned(), length.to_string());
+                    200
+                }
+                None => 404,
+            },
+            HttpMethod::Patch => {
+                drop(uploads);
+                thread::sleep(self.patch_delay);
+                let mut uploads = self.uploads.lock().unwrap();
+                let upload = uploads.get_mut(&req.url).unwrap();
+                upload.0 += req.body.unwrap().len();
+                headers.insert("upload-offset".to_owned(), upload.0.to_string());
+                204
+            }
+            _ => unreachable!(),
+        };
+
+        Ok(HttpResponse {
+            status_code,
+            headers,
+        })
+    }
+}
+
+/// Panics on every request, like a buggy handler.
+struct PanickingHandler;
+
+impl HttpHandler for PanickingHandler {
+    fn handle_request(&self, _: HttpRequest) -> Result<HttpResponse, Error> {
+        panic!("the handler is broken")
+    }
+}
+
+fn create_temp_file(size: usize) -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    let buffer: Vec<u8> = (0..size).map(|_| rand::random::<u8>()).collect();
+    temp_file.write_all(&buffer[..]).unwrap();
+    temp_file
+}
+
+fn job_with_name(file: &NamedTempFile, name: &str, priority: i32) -> UploadJob {
+    let mut job = UploadJob::new("/files/", file.path());
+    job.metadata.insert("name".to_owned(), name.to_owned());
+    job.priority = priority;
+    job
+}
+
+#[test]
+fn should_upload_all_jobs() {
+    let files: Vec<_> = (0..8).map(|i| create_temp_file(1024 * (i + 1))).collect();
+    let client = tus_client::Client::new_shared(MemoryHandler::default());
+    let manager = UploadManager::new(client, 3);
+
+    let ids: Vec<_> = files
+        .iter()
+        .map(|file| {
+            manager
+                .submit(UploadJob::new("/files/", file.path()))
+                .expect("'submit' call failed")
+        })
+        .collect();
+    manager.wait();
+
+    for id in ids {
+        match manager.status(id) {
+            Some(JobStatus::Completed { upload_url }) => assert!(upload_url.starts_with("/files/")),
+            status => panic!("Expected 'JobStatus::Completed', got {:?}", status),
+        }
+    }
+
+    let progress = manager.progress();
+    assert_eq!(8, progress.completed);
+    assert_eq!(1024 * 36, progress.total_size);
+    assert_eq!(progress.total_size, progress.bytes_uploaded);
+}
+
+#[test]
+fn should_start_jobs_by_priority() {
+    let handler = Arc::new(MemoryHandler {
+        patch_delay: Duration::from_millis(50),
+        ..MemoryHandler::default()
+    });
+    let file = create_temp_file(1024);
+    let client = tus_client::Client::new_shared(SharedHandler(Arc::clone(&handler)));
+    let manager = UploadManager::new(client, 1);
+
+    let first = manager.submit(job_with_name(&file, "first", 0)).unwrap();
+    while let Some(JobStatus::Queued) = manager.status(first) {
+        thread::yield_now();
+    }
+    manager.submit(job_with_name(&file, "low", -1)).unwrap();
+    manager.submit(job_with_name(&file, "high", 1)).unwrap();
+    manager.wait();
+
+    let names: Vec<_> = handler
+        .created
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|metadata| String::from_utf8(base64::decode(&metadata[5..]).unwrap()).unwrap())
+        .collect();
+    assert_eq!(vec!["first", "high", "low"], names);
+}
+
+#[test]
+fn should_cancel_queued_job() {
+    let file = create_temp_file(1024);
+    let client = tus_client::Client::new_shared(MemoryHandler {
+        patch_delay: Duration::from_millis(50),
+        ..MemoryHandler::default()
+    });
+    let manager = UploadManager::new(client, 1);
+
+    let first = manager.submit(job_with_name(&file, "first", 0)).unwrap();
+    let second = manager.submit(job_with_name(&file, "second", 0)).unwrap();
+
+    assert!(manager.cancel(second));
+    manager.wait();
+
+    assert!(!manager.cancel(second));
+    match manager.status(first) {
+        Some(JobStatus::Completed { .. }) => {}
+        status => panic!("Expected 'JobStatus::Completed', got {:?}", status),
+    }
+    match manager.status(second) {
+        Some(JobStatus::Cancelled) => {}
+        status => panic!("Expected 'JobStatus::Cancelled', got {:?}", status),
+    }
+    assert_eq!(1, manager.progress().cancelled);
+}
+
+#[test]
+fn should_reject_missing_file() {
+    let client = tus_client::Client::new_shared(MemoryHandler::default());
+    let manager = UploadManager::new(client, 1);
+
+    let result = manager.submit(UploadJob::new("/files/", "/does/not/exist"));
+
+    match result {
+        Err(Error::IoError(_)) => {}
+        _ => panic!("Expected 'Error::IoError'"),
+    }
+}
+
+struct SharedHandler(Arc<MemoryHandler>);
+
+impl HttpHandler for SharedHandler {
+    fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
+        self.0.handle_request(req)
+    }
+}
+
+#[test]
+fn should_resume_job_with_upload_url() {
+    let file = create_temp_file(1000);
+    let handler = Arc::new(MemoryHandler::default());
+    {
+        let mut uploads = handler.uploads.lock().unwrap();
+        uploads.insert("/files/partial".to_owned(), (400, 1000));
+        uploads.insert("/files/complete".to_owned(), (1000, 1000));
+        uploads.insert("/files/other-size".to_owned(), (0, 2000));
+    }
+    let manager = UploadManager::new(tus_client::Client::from_arc(handler.clone()), 2);
+
+    let submit = |upload_url: &str| {
+        let mut job = UploadJob::new("/files/", file.path());
+        job.upload_url = Some(upload_url.to_owned());
+        manager.submit(job).expect("'submit' call failed")
+    };
+    let partial = submit("/files/partial");
+    let complete = submit("/files/complete");
+    let other_size = submit("/files/other-size");
+    let missing = submit("/files/missing");
+    manager.wait();
+
+    let upload_url = |id| match manager.status(id) {
+        Some(JobStatus::Completed { upload_url }) => upload_url,
+        status => panic!("Expected 'JobStatus::Completed', got {:?}", status),
+    };
+    assert_eq!("/files/partial", upload_url(partial));
+    assert_eq!("/files/complete", upload_url(complete));
+    assert_ne!("/files/other-size", upload_url(other_size));
+    assert_ne!("/files/missing", upload_url(missing));
+
+    // Only the uploads which couldn't be resumed were created
+    assert_eq!(2, handler.created.lock().unwrap().len());
+    assert_eq!(
+        (1000, 1000),
+        handler.uploads.lock().unwrap()["/files/partial"]
+    );
+}
+
+#[test]
+fn should_fail_jobs_which_panic() {
+    let file = create_temp_file(1_000);
+    let manager = UploadManager::new(tus_client::Client::new_shared(PanickingHandler), 1);
+
+    let first = manager
+        .submit(UploadJob::new("/files/", file.path()))
+        .unwrap();
+    let second = manager
+        .submit(UploadJob::new("/files/", file.path()))
+        .unwrap();
+    manager.wait();
+
+    // The worker survives the first panic, and runs the second job
+    for id in &[first, second] {
+        match manager.status(*id) {
+            Some(JobStatus::Failed(error)) => match &*error {
+                Error::Panicked(message) => assert_eq!("the handler is broken", message),
+                error => panic!("Expected 'Error::Panicked', got {:?}", error),
+            },
+            status => panic!("Expected 'JobStatus::Failed', got {:?}", status),
+        }
+    }
+}
+
+#[test]
+fn should_remove_finished_jobs() {
+    let file = create_temp_file(1_000);
+    let handler = MemoryHandler {
+        patch_delay: Duration::from_millis(200),
+        ..MemoryHandler::default()
+    };
+    let manager = UploadManager::new(tus_client::Client::new_shared(handler), 1);
+
+    let running = manager
+        .submit(UploadJob::new("/files/", file.path()))
+        .unwrap();
+    let cancelled = manager
+        .submit(UploadJob::new("/files/", file.path()))
+        .unwrap();
+    assert!(manager.cancel(cancelled));
+    assert!(manager.remove(running).is_none());
+    match manager.remove(cancelled) {
+        Some(JobStatus::Cancelled) => {}
+        status => panic!("Expected 'JobStatus::Cancelled', got {:?}", status),
+    }
+    assert!(manager.status(cancelled).is_none());
+
+    let later = manager
+        .submit(UploadJob::new("/files/", file.path()))
+        .unwrap();
+    manager.wait();
+    let finished = manager.take_finished();
+    assert_eq!(
+        vec![running, later],
+        finished.iter().map(|(id, _)| *id).collect::<Vec<_>>()
+    );
+    assert!(finished
+        .iter()
+        .all(|(_, status)| matches!(status, JobStatus::Completed { .. })));
+    assert_eq!(0, manager.progress().completed);
+}
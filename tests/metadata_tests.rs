@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::UNIX_EPOCH;
+use tus_client::metadata::{MetadataBuilder, FILENAME_KEY, FILETYPE_KEY, LAST_MODIFIED_KEY};
+use tus_client::testing::MockServer;
+use tus_client::ClientBuilder;
+
+#[test]
+fn should_derive_metadata_when_creating_upload() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("Holiday Photo.JPG");
+    fs::write(&path, vec![1; 1_000]).unwrap();
+    let server = MockServer::new();
+    let client = ClientBuilder::new()
+        .derive_metadata(
+            MetadataBuilder::new()
+                .uppy_aliases(true)
+                .insert("album", "summer"),
+        )
+        .build(server.clone());
+    let mut metadata = HashMap::new();
+    metadata.insert("album".to_owned(), "winter".to_owned());
+    metadata.insert(FILETYPE_KEY.to_owned(), "image/x-custom".to_owned());
+
+    let upload_url = client
+        .create_with_metadata("/files/", &path, metadata)
+        .expect("'create_with_metadata' call failed");
+
+    let modified = fs::metadata(&path)
+        .unwrap()
+        .modified()
+        .unwrap()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let mut expected = HashMap::new();
+    expected.insert(FILENAME_KEY.to_owned(), "Holiday Photo.JPG".to_owned());
+    expected.insert("name".to_owned(), "Holiday Photo.JPG".to_owned());
+    expected.insert(FILETYPE_KEY.to_owned(), "image/x-custom".to_owned());
+    expected.insert("type".to_owned(), "image/jpeg".to_owned());
+    expected.insert(LAST_MODIFIED_KEY.to_owned(), modified.to_string());
+    expected.insert("album".to_owned(), "winter".to_owned());
+    assert_eq!(expected, server.upload(&upload_url).unwrap().metadata);
+}
+
+#[test]
+fn should_sniff_filetype_of_unknown_extension() {
+    let directory = tempfile::tempdir().unwrap();
+    let png = directory.path().join("scan");
+    fs::write(&png, b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR").unwrap();
+    let webp = directory.path().join("image.dat");
+    fs::write(&webp, b"RIFF\x24\x00\x00\x00WEBPVP8 ").unwrap();
+    let unknown = directory.path().join("data.bin");
+    fs::write(&unknown, vec![0; 100]).unwrap();
+
+    let builder = MetadataBuilder::new().last_modified(false);
+    assert_eq!("image/png", builder.build(&png).unwrap()[FILETYPE_KEY]);
+    assert_eq!("image/webp", builder.build(&webp).unwrap()[FILETYPE_KEY]);
+    assert!(!builder.build(&unknown).unwrap().contains_key(FILETYPE_KEY));
+
+    let metadata = builder.sniff_content(false).build(&png).unwrap();
+    assert_eq!(1, metadata.len());
+    assert_eq!("scan", metadata[FILENAME_KEY]);
+}
+
+#[cfg(unix)]
+#[test]
+fn should_convert_non_utf8_filename_lossily() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join(OsStr::from_bytes(b"caf\xe9.txt"));
+    fs::write(&path, b"hello").unwrap();
+
+    let metadata = MetadataBuilder::new().build(&path).unwrap();
+    assert_eq!("caf\u{fffd}.txt", metadata[FILENAME_KEY]);
+    assert_eq!("text/plain", metadata[FILETYPE_KEY]);
+}
@@ -0,0 +1,160 @@
+use std::io::{self, Write};
+use std::sync::Arc;
+use tempfile::NamedTempFile;
+use tus_client::http::{HttpHandler, HttpMethod, HttpRequest};
+use tus_client::record::{Recorder, Replayer, REDACTED};
+use tus_client::testing::MockServer;
+use tus_client::{ClientBuilder, Error};
+
+fn create_temp_file(size: usize) -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    let buffer: Vec<u8> = (0..size).map(|_| rand::random::<u8>()).collect();
+    temp_file.write_all(&buffer[..]).unwrap();
+    temp_file
+}
+
+fn record_upload(file: &NamedTempFile, recording: &NamedTempFile) -> String {
+    let recorder = Recorder::create(MockServer::new(), recording.path()).unwrap();
+    let client = ClientBuilder::new().chunk_size(1_000).build(recorder);
+
+    let upload_url = client.create("/files/", file.path()).unwrap();
+    client.upload(&upload_url, file.path()).unwrap();
+    upload_url
+}
+
+#[test]
+fn recorder_should_record_exchanges_without_bodies() {
+    let file = create_temp_file(2_500);
+    let recording = NamedTempFile::new().unwrap();
+
+    record_upload(&file, &recording);
+
+    let replayer = Replayer::open(recording.path()).expect("'open' call failed");
+    let exchanges = replayer.exchanges();
+    let methods: Vec<_> = exchanges
+        .iter()
+        .map(|e| e.request.method.as_str())
+        .collect();
+    assert_eq!(vec!["POST", "HEAD", "PATCH", "PATCH", "PATCH"], methods);
+
+    let body = exchanges[4].request.body.as_ref().unwrap();
+    assert_eq!(500, body.size);
+    assert_eq!(64, body.sha256.len());
+    assert_eq!(201, exchanges[0].response.as_ref().unwrap().status_code);
+
+    let contents = std::fs::read_to_string(recording.path()).unwrap();
+    assert_eq!(5, contents.lines().count());
+    assert!(contents.len() < 2_500);
+}
+
+#[test]
+fn replayer_should_replay_recorded_session() {
+    let file = create_temp_file(2_500);
+    let recording = NamedTempFile::new().unwrap();
+    let upload_url = record_upload(&file, &recording);
+
+    let replayer = Arc::new(
+        Replayer::open(recording.path())
+            .unwrap()
+            .verify_bodies(true),
+    );
+    let client = ClientBuilder::new()
+        .chunk_size(1_000)
+        .build_from_arc(replayer.clone());
+
+    assert_eq!(upload_url, client.create("/files/", file.path()).unwrap());
+    client
+        .upload(&upload_url, file.path())
+        .expect("'upload' call failed");
+    assert_eq!(0, replayer.remaining());
+}
+
+#[test]
+fn replayer_should_reject_unexpected_request() {
+    let file = create_temp_file(2_500);
+    let recording = NamedTempFile::new().unwrap();
+    record_upload(&file, &recording);
+
+    let replayer = Arc::new(Replayer::open(recording.path()).unwrap());
+    let client = ClientBuilder::new().build_from_arc(replayer.clone());
+
+    let result = client.delete("/files/1");
+
+    match result {
+        Err(Error::HttpHandlerError(_)) => {}
+        _ => panic!("Expected 'Error::HttpHandlerError'"),
+    }
+    assert_eq!(5, replayer.remaining());
+}
+
+#[test]
+fn replayer_should_reject_downloads() {
+    let file = create_temp_file(2_500);
+    let recording = NamedTempFile::new().unwrap();
+    let upload_url = record_upload(&file, &recording);
+    let replayer = Replayer::open(recording.path()).unwrap();
+
+    let result = replayer.handle_download(HttpRequest {
+        method: HttpMethod::Get,
+        url: upload_url,
+        headers: Default::default(),
+        body: None,
+        timeout: None,
+    });
+
+    match result {
+        Err(Error::HttpHandlerError(message)) => assert!(message.contains("GET")),
+        _ => panic!("Expected 'Error::HttpHandlerError'"),
+    }
+    assert_eq!(5, replayer.remaining());
+}
+
+#[test]
+fn recorder_should_redact_credentials() {
+    let file = create_temp_file(100);
+    let recording = NamedTempFile::new().unwrap();
+    let recorder = Recorder::create(MockServer::new(), recording.path()).unwrap();
+    let client = ClientBuilder::new()
+        .header("Authorization", "Bearer secret")
+        .header("X-Api-Key", "key")
+        .build(recorder);
+    client.create("/files/", file.path()).unwrap();
+
+    let replayer = Replayer::open(recording.path()).unwrap();
+    let headers = &replayer.exchanges()[0].request.headers;
+    assert_eq!(REDACTED, headers["authorization"]);
+    assert_eq!("key", headers["x-api-key"]);
+    assert!(!std::fs::read_to_string(recording.path())
+        .unwrap()
+        .contains("secret"));
+}
+
+#[test]
+fn recorder_should_return_response_when_recording_fails() {
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let file = create_temp_file(100);
+    let recorder = Arc::new(Recorder::with_writer(MockServer::new(), FailingWriter));
+    let client = ClientBuilder::new().build_from_arc(recorder.clone());
+
+    let upload_url = client
+        .create("/files/", file.path())
+        .expect("'create' call failed");
+
+    assert!(recorder.inner().upload(&upload_url).is_some());
+    assert_eq!(
+        "disk full",
+        recorder.take_write_error().unwrap().to_string()
+    );
+    assert!(recorder.take_write_error().is_none());
+}
@@ -3,6 +3,7 @@
 use std::collections::HashMap;
 use std::io::Write;
 use tempfile::NamedTempFile;
+#[allow(clippy::single_component_path_imports)]
 use tus_client;
 
 const TUS_ENDPOINT: &str = "http://localhost:1080/files/";
@@ -55,3 +56,18 @@ fn reqwest_should_upload_file() {
         .upload(&upload_path, temp_file.path())
         .expect("'client.upload' call failed");
 }
+
+#[test]
+fn reqwest_should_time_out() {
+    // The connection is accepted by the operating system, but never answered
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/files/", listener.local_addr().unwrap());
+    let client = tus_client::ClientBuilder::new()
+        .timeout(std::time::Duration::from_millis(100))
+        .build(reqwest::Client::new());
+
+    match client.get_server_info(&url) {
+        Err(tus_client::Error::HttpHandlerError(_)) => {}
+        result => panic!("Expected 'Error::HttpHandlerError', got {:?}", result),
+    }
+}
@@ -0,0 +1,1326 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::NamedTempFile;
+use tus_client::http::{HttpHandler, HttpMethod, HttpRequest, HttpResponse};
+use tus_client::integrity::{Digest, DigestAlgorithm};
+use tus_client::server::{
+    Collector, Cors, DataStore, FileHook, FileInfo, FileStore, HookEvent, HookResponse, HookType,
+    HttpHook, LockConflict, MemoryStore, ResponseBody, Server, ServerBuilder, SyncPolicy,
+};
+use tus_client::{Client, ClientBuilder, Error, TailOptions, TusExtension};
+
+fn create_temp_file(size: usize) -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    let buffer: Vec<u8> = (0..size).map(|_| rand::random::<u8>()).collect();
+    temp_file.write_all(&buffer[..]).unwrap();
+    temp_file
+}
+
+fn create_request<'a>(method: HttpMethod, url: &str, body: Option<&'a [u8]>) -> HttpRequest<'a> {
+    let mut headers = HashMap::new();
+    headers.insert("tus-resumable".to_owned(), "1.0.0".to_owned());
+    HttpRequest {
+        method,
+        headers,
+        url: url.to_owned(),
+        body,
+        timeout: None,
+    }
+}
+
+fn create_patch_request<'a>(url: &str, offset: usize, body: &'a [u8]) -> HttpRequest<'a> {
+    let mut req = create_request(HttpMethod::Patch, url, Some(body));
+    req.headers
+        .insert("upload-offset".to_owned(), offset.to_string());
+    req.headers.insert(
+        "content-type".to_owned(),
+        "application/offset+octet-stream".to_owned(),
+    );
+    req
+}
+
+/// A store whose first `append` blocks while reading the body, until the body fails or it's told to continue.
+struct BlockingStore {
+    inner: MemoryStore,
+    entered: Mutex<Option<Sender<()>>>,
+    resume: Mutex<Receiver<()>>,
+}
+
+impl BlockingStore {
+    fn new() -> (Self, Receiver<()>, Sender<()>) {
+        let (entered_sender, entered) = mpsc::channel();
+        let (resume, resume_receiver) = mpsc::channel();
+        let store = BlockingStore {
+            inner: MemoryStore::new(),
+            entered: Mutex::new(Some(entered_sender)),
+            resume: Mutex::new(resume_receiver),
+        };
+        (store, entered, resume)
+    }
+}
+
+impl DataStore for BlockingStore {
+    fn create(&self, info: FileInfo) -> io::Result<()> {
+        self.inner.create(info)
+    }
+
+    fn get(&self, id: &str) -> io::Result<Option<FileInfo>> {
+        self.inner.get(id)
+    }
+
+    fn list(&self) -> io::Result<Vec<FileInfo>> {
+        self.inner.list()
+    }
+
+    fn update(&self, info: &FileInfo) -> io::Result<()> {
+        self.inner.update(info)
+    }
+
+    fn read(&self, id: &str, offset: usize, len: usize) -> io::Result<Vec<u8>> {
+        self.inner.read(id, offset, len)
+    }
+
+    fn truncate(&self, id: &str, offset: usize) -> io::Result<()> {
+        self.inner.truncate(id, offset)
+    }
+
+    fn concatenate(&self, id: &str, partial_ids: &[String]) -> io::Result<()> {
+        self.inner.concatenate(id, partial_ids)
+    }
+
+    fn append(&self, id: &str, data: &mut dyn Read) -> io::Result<usize> {
+        if let Some(entered) = self.entered.lock().unwrap().take() {
+            entered.send(()).unwrap();
+            let resume = self.resume.lock().unwrap();
+            while resume.try_recv().is_err() {
+                assert_eq!(0, data.read(&mut [])?);
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+        self.inner.append(id, data)
+    }
+
+    fn delete(&self, id: &str) -> io::Result<bool> {
+        self.inner.delete(id)
+    }
+}
+
+fn upload_id(upload_url: &str) -> &str {
+    upload_url.rsplit('/').next().unwrap()
+}
+
+#[test]
+fn should_receive_upload_from_client() {
+    let temp_file = create_temp_file(10_000);
+    let server = Arc::new(Server::new(MemoryStore::new()));
+    let client = ClientBuilder::new()
+        .chunk_size(3_000)
+        .build_from_arc(server.clone());
+    let mut metadata = HashMap::new();
+    metadata.insert("filename".to_owned(), "file.bin".to_owned());
+
+    let upload_url = client
+        .create_with_metadata(
+            "http://localhost/files/",
+            temp_file.path(),
+            metadata.clone(),
+        )
+        .expect("'create_with_metadata' call failed");
+    assert!(upload_url.starts_with("http://localhost/files/"));
+
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+
+    let info = client
+        .get_info(&upload_url)
+        .expect("'get_info' call failed");
+    assert_eq!(10_000, info.bytes_uploaded);
+    assert_eq!(Some(10_000), info.total_size);
+    assert_eq!(Some(metadata), info.metadata);
+    assert_eq!(
+        std::fs::read(temp_file.path()).unwrap(),
+        server.store().data(upload_id(&upload_url)).unwrap()
+    );
+}
+
+#[test]
+fn should_advertise_configuration() {
+    let server = Arc::new(
+        ServerBuilder::new()
+            .max_size(1_000)
+            .build(MemoryStore::new()),
+    );
+    let client = Client::from_arc(server.clone());
+
+    let info = client
+        .get_server_info("http://localhost/files/")
+        .expect("'get_server_info' call failed");
+
+    assert_eq!(vec!["1.0.0"], info.supported_versions);
+    assert_eq!(
+        vec![
+            TusExtension::Creation,
+            TusExtension::CreationDeferLength,
+            TusExtension::Checksum,
+            TusExtension::Termination,
+            TusExtension::Concatenation
+        ],
+        info.extensions
+    );
+    assert_eq!(Some(1_000), info.max_upload_size);
+}
+
+#[test]
+fn should_reject_file_larger_than_max_size() {
+    let temp_file = create_temp_file(2_000);
+    let server = Arc::new(
+        ServerBuilder::new()
+            .max_size(1_000)
+            .build(MemoryStore::new()),
+    );
+    let client = Client::from_arc(server.clone());
+
+    let result = client.create("http://localhost/files/", temp_file.path());
+
+    match result {
+        Err(Error::FileTooLarge) => {}
+        _ => panic!("Expected 'Error::FileTooLarge'"),
+    }
+}
+
+#[test]
+fn should_delete_upload() {
+    let temp_file = create_temp_file(100);
+    let server = Arc::new(Server::new(MemoryStore::new()));
+    let client = Client::from_arc(server.clone());
+
+    let upload_url = client
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+    client.delete(&upload_url).expect("'delete' call failed");
+
+    assert!(server
+        .store()
+        .get(upload_id(&upload_url))
+        .unwrap()
+        .is_none());
+    match client.get_info(&upload_url) {
+        Err(Error::NotFoundError) => {}
+        _ => panic!("Expected 'Error::NotFoundError'"),
+    }
+}
+
+#[test]
+fn should_ignore_forwarded_origin_unless_behind_proxy() {
+    let server = Server::new(MemoryStore::new());
+    let mut req = create_request(HttpMethod::Post, "/files/", None);
+    req.headers
+        .insert("upload-length".to_owned(), "10".to_owned());
+    req.headers
+        .insert("host".to_owned(), "internal:8080".to_owned());
+    req.headers.insert(
+        "forwarded".to_owned(),
+        "proto=https;host=evil.com".to_owned(),
+    );
+    req.headers
+        .insert("x-forwarded-host".to_owned(), "evil.com".to_owned());
+
+    let response = server.handle(req);
+    assert!(response.headers["location"].starts_with("http://internal:8080/files/"));
+}
+
+#[test]
+fn should_use_forwarded_origin_in_location() {
+    let server = ServerBuilder::new()
+        .behind_proxy(true)
+        .build(MemoryStore::new());
+    let mut req = create_request(HttpMethod::Post, "/files/", None);
+    req.headers
+        .insert("upload-length".to_owned(), "10".to_owned());
+    req.headers
+        .insert("host".to_owned(), "internal:8080".to_owned());
+
+    let mut forwarded = req.clone();
+    forwarded.headers.insert(
+        "forwarded".to_owned(),
+        "for=192.0.2.1;proto=https;host=\"example.com\", for=10.0.0.1".to_owned(),
+    );
+    let response = server.handle(forwarded);
+    assert!(response.headers["location"].starts_with("https://example.com/files/"));
+
+    let mut forwarded = req.clone();
+    forwarded
+        .headers
+        .insert("x-forwarded-proto".to_owned(), "https".to_owned());
+    let response = server.handle(forwarded);
+    assert!(response.headers["location"].starts_with("https://internal:8080/files/"));
+
+    req.headers.remove("host");
+    let response = server.handle(req);
+    assert!(response.headers["location"].starts_with("/files/"));
+}
+
+#[test]
+fn should_reject_invalid_requests() {
+    let server = ServerBuilder::new()
+        .base_path("uploads")
+        .build(MemoryStore::new());
+
+    let mut req = create_request(HttpMethod::Post, "/uploads/", None);
+    req.headers
+        .insert("upload-length".to_owned(), "10".to_owned());
+    req.headers
+        .insert("host".to_owned(), "example.com".to_owned());
+    let response = server.handle(req.clone());
+    assert_eq!(201, response.status_code);
+    let location = response.headers["location"].clone();
+    assert!(location.starts_with("http://example.com/uploads/"));
+
+    req.headers.remove("tus-resumable");
+    let response = server.handle(req);
+    assert_eq!(412, response.status_code);
+    assert_eq!("1.0.0", response.headers["tus-version"]);
+
+    let mut req = create_patch_request(&location, 5, b"12345");
+    assert_eq!(409, server.handle(req.clone()).status_code);
+
+    req.headers
+        .insert("upload-offset".to_owned(), "0".to_owned());
+    req.body = Some(b"12345678901");
+    assert_eq!(413, server.handle(req.clone()).status_code);
+
+    req.headers
+        .insert("content-type".to_owned(), "text/plain".to_owned());
+    assert_eq!(415, server.handle(req).status_code);
+
+    let req = create_request(HttpMethod::Head, "/uploads/unknown", None);
+    assert_eq!(404, server.handle(req).status_code);
+}
+
+#[test]
+fn file_store_should_share_directory_between_processes() {
+    let directory = tempfile::tempdir().unwrap();
+    let locks = tempfile::tempdir().unwrap();
+    let temp_file = create_temp_file(10_000);
+    let data = std::fs::read(temp_file.path()).unwrap();
+    let first = ServerBuilder::new()
+        .lock_directory(locks.path())
+        .build(FileStore::open(directory.path()).unwrap());
+    let second = Arc::new(
+        ServerBuilder::new()
+            .lock_directory(locks.path())
+            .build(FileStore::open(directory.path()).unwrap()),
+    );
+
+    let upload_url = Client::from_arc(second.clone())
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+    let req = create_patch_request(&upload_url, 0, &data[..4_000]);
+    assert_eq!(204, first.handle(req).status_code);
+
+    Client::from_arc(second.clone())
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+    let info = first.store().get(upload_id(&upload_url)).unwrap().unwrap();
+    assert_eq!(10_000, info.offset);
+
+    let req = create_request(HttpMethod::Delete, &upload_url, None);
+    assert_eq!(204, second.handle(req).status_code);
+    let req = create_request(HttpMethod::Head, &upload_url, None);
+    assert_eq!(404, first.handle(req).status_code);
+}
+
+#[test]
+fn file_store_should_resume_upload_after_restart() {
+    let directory = tempfile::tempdir().unwrap();
+    let temp_file = create_temp_file(10_000);
+    let data = std::fs::read(temp_file.path()).unwrap();
+
+    let upload_url = {
+        let server = Arc::new(Server::new(FileStore::open(directory.path()).unwrap()));
+        let client = Client::from_arc(server.clone());
+        let upload_url = client
+            .create("http://localhost/files/", temp_file.path())
+            .expect("'create' call failed");
+
+        let req = create_patch_request(&upload_url, 0, &data[..4_000]);
+        assert_eq!(204, server.handle(req).status_code);
+        upload_url
+    };
+
+    let store = FileStore::with_sync_policy(directory.path(), SyncPolicy::Always).unwrap();
+    assert_eq!(
+        4_000,
+        store.get(upload_id(&upload_url)).unwrap().unwrap().offset
+    );
+    let server = Arc::new(Server::new(store));
+    let client = Client::from_arc(server.clone());
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+
+    assert_eq!(
+        data,
+        std::fs::read(server.store().data_path(upload_id(&upload_url))).unwrap()
+    );
+    client.delete(&upload_url).expect("'delete' call failed");
+    assert_eq!(0, std::fs::read_dir(directory.path()).unwrap().count());
+}
+
+#[test]
+fn file_store_should_load_uploads_written_by_tusd() {
+    let directory = tempfile::tempdir().unwrap();
+    std::fs::write(
+        directory.path().join("abc.info"),
+        r#"{"ID":"abc","Size":10,"SizeIsDeferred":false,"Offset":0,"MetaData":{"filename":"a.txt"},"IsPartial":false,"IsFinal":false,"PartialUploads":null,"Storage":{"Type":"filestore","Path":"abc"}}"#,
+    )
+    .unwrap();
+    std::fs::write(directory.path().join("abc"), b"123456789012").unwrap();
+    std::fs::write(directory.path().join("def.info.tmp"), b"{").unwrap();
+
+    let store = FileStore::open(directory.path()).unwrap();
+
+    let mut metadata = HashMap::new();
+    metadata.insert("filename".to_owned(), "a.txt".to_owned());
+    assert_eq!(
+        Some(FileInfo {
+            id: "abc".to_owned(),
+            size: 10,
+            offset: 10,
+            metadata,
+            ..FileInfo::default()
+        }),
+        store.get("abc").unwrap()
+    );
+    assert_eq!(
+        b"1234567890",
+        &std::fs::read(store.data_path("abc")).unwrap()[..]
+    );
+    assert!(!directory.path().join("def.info.tmp").exists());
+}
+
+#[test]
+fn should_reject_concurrent_patch_with_locked() {
+    let temp_file = create_temp_file(100);
+    let (store, entered, resume) = BlockingStore::new();
+    let server = Arc::new(Server::new(store));
+    let upload_url = Client::from_arc(server.clone())
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+
+    let first = {
+        let server = Arc::clone(&server);
+        let upload_url = upload_url.clone();
+        thread::spawn(move || {
+            server
+                .handle(create_patch_request(&upload_url, 0, b"12345"))
+                .status_code
+        })
+    };
+    entered.recv().unwrap();
+
+    let req = create_patch_request(&upload_url, 0, b"12345");
+    assert_eq!(423, server.handle(req).status_code);
+    let req = create_request(HttpMethod::Delete, &upload_url, None);
+    assert_eq!(423, server.handle(req).status_code);
+
+    resume.send(()).unwrap();
+    assert_eq!(204, first.join().unwrap());
+    let req = create_patch_request(&upload_url, 5, b"12345");
+    assert_eq!(204, server.handle(req).status_code);
+}
+
+#[test]
+fn should_stop_patch_holding_lock_when_release_is_requested() {
+    let temp_file = create_temp_file(100);
+    let (store, entered, _resume) = BlockingStore::new();
+    let server = Arc::new(
+        ServerBuilder::new()
+            .on_lock_conflict(LockConflict::RequestRelease(Duration::from_secs(10)))
+            .build(store),
+    );
+    let upload_url = Client::from_arc(server.clone())
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+
+    let first = {
+        let server = Arc::clone(&server);
+        let upload_url = upload_url.clone();
+        thread::spawn(move || {
+            server
+                .handle(create_patch_request(&upload_url, 0, b"12345"))
+                .status_code
+        })
+    };
+    entered.recv().unwrap();
+
+    let req = create_patch_request(&upload_url, 0, b"67890");
+    assert_eq!(204, server.handle(req).status_code);
+    assert_eq!(400, first.join().unwrap());
+    assert_eq!(
+        b"67890",
+        &server.store().inner.data(upload_id(&upload_url)).unwrap()[..]
+    );
+}
+
+#[test]
+fn should_respect_lock_files_of_running_processes() {
+    let directory = tempfile::tempdir().unwrap();
+    let temp_file = create_temp_file(100);
+    let server = Arc::new(
+        ServerBuilder::new()
+            .lock_directory(directory.path())
+            .build(MemoryStore::new()),
+    );
+    let upload_url = Client::from_arc(server.clone())
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+    let lock_path = directory
+        .path()
+        .join(format!("{}.lock", upload_id(&upload_url)));
+
+    std::fs::write(&lock_path, std::process::id().to_string()).unwrap();
+    let req = create_patch_request(&upload_url, 0, b"12345");
+    assert_eq!(423, server.handle(req).status_code);
+
+    std::fs::remove_file(&lock_path).unwrap();
+    let req = create_patch_request(&upload_url, 0, b"12345");
+    assert_eq!(204, server.handle(req).status_code);
+    assert!(!lock_path.exists());
+}
+
+#[test]
+fn should_call_hooks_at_each_stage() {
+    let temp_file = create_temp_file(100);
+    let (sender, events) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let server = Arc::new(
+        ServerBuilder::new()
+            .hook(move |event: &HookEvent| {
+                sender.lock().unwrap().send(event.clone()).unwrap();
+                if event.hook_type != HookType::PreCreate {
+                    return Ok(HookResponse::default());
+                }
+                match event.upload.metadata.get("filename") {
+                    Some(filename) if filename.ends_with(".exe") => {
+                        Ok(HookResponse::reject(403, "Executables are not allowed"))
+                    }
+                    _ => {
+                        let mut metadata = event.upload.metadata.clone();
+                        metadata.insert("checked".to_owned(), "true".to_owned());
+                        Ok(HookResponse {
+                            id: Some("custom-id".to_owned()),
+                            metadata: Some(metadata),
+                            ..HookResponse::default()
+                        })
+                    }
+                }
+            })
+            .build(MemoryStore::new()),
+    );
+    let client = Client::from_arc(server.clone());
+
+    let mut metadata = HashMap::new();
+    metadata.insert("filename".to_owned(), "virus.exe".to_owned());
+    match client.create_with_metadata("http://localhost/files/", temp_file.path(), metadata) {
+        Err(Error::UnexpectedStatusCode(403)) => {}
+        _ => panic!("Expected 'Error::UnexpectedStatusCode(403)'"),
+    }
+    assert_eq!(HookType::PreCreate, events.recv().unwrap().hook_type);
+
+    let upload_url = client
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+    assert_eq!("http://localhost/files/custom-id", upload_url);
+    assert_eq!(HookType::PreCreate, events.recv().unwrap().hook_type);
+    assert_eq!(
+        Some(&"true".to_owned()),
+        server
+            .store()
+            .get("custom-id")
+            .unwrap()
+            .unwrap()
+            .metadata
+            .get("checked")
+    );
+
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+    client.delete(&upload_url).expect("'delete' call failed");
+
+    let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(HookType::PostReceive, event.hook_type);
+    assert_eq!(100, event.upload.offset);
+    let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(HookType::PostFinish, event.hook_type);
+    assert_eq!("custom-id", event.upload.id);
+    let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(HookType::PostTerminate, event.hook_type);
+    assert_eq!(HttpMethod::Delete, event.request.method);
+}
+
+#[test]
+fn should_reject_upload_with_http_hook() {
+    struct HookHandler {
+        events: Mutex<Vec<serde_json::Value>>,
+    }
+
+    impl HttpHandler for HookHandler {
+        fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
+            self.handle_download(req).map(|(response, _)| response)
+        }
+
+        fn handle_download(
+            &self,
+            req: HttpRequest,
+        ) -> Result<(HttpResponse, tus_client::http::ResponseBody<'_>), Error> {
+            self.events
+                .lock()
+                .unwrap()
+                .push(serde_json::from_slice(req.body.unwrap()).unwrap());
+            let body: &[u8] =
+                br#"{"RejectUpload":true,"HTTPResponse":{"StatusCode":422,"Body":"No quota left"}}"#;
+            Ok((
+                HttpResponse {
+                    headers: HashMap::new(),
+                    status_code: 200,
+                },
+                Box::new(body),
+            ))
+        }
+    }
+
+    let hook_handler = Arc::new(HookHandler {
+        events: Mutex::new(Vec::new()),
+    });
+    let server = ServerBuilder::new()
+        .hook(HttpHook::new(
+            "http://localhost/hooks",
+            hook_handler.clone(),
+        ))
+        .build(MemoryStore::new());
+
+    let mut req = create_request(HttpMethod::Post, "/files/", None);
+    req.headers
+        .insert("upload-length".to_owned(), "100".to_owned());
+    let response = server.handle(req);
+
+    assert_eq!(422, response.status_code);
+    assert_eq!(
+        ResponseBody::Bytes(b"No quota left".to_vec()),
+        response.body
+    );
+    let events = hook_handler.events.lock().unwrap();
+    assert_eq!("pre-create", events[0]["Type"]);
+    assert_eq!(100, events[0]["Event"]["Upload"]["Size"]);
+    assert_eq!("POST", events[0]["Event"]["HTTPRequest"]["Method"]);
+}
+
+#[test]
+fn should_fail_http_hook_which_doesnt_respond_in_time() {
+    struct HangingHandler;
+
+    impl HttpHandler for HangingHandler {
+        fn handle_request(&self, _: HttpRequest) -> Result<HttpResponse, Error> {
+            thread::sleep(Duration::from_secs(1));
+            Err(Error::HttpHandlerError("timed out".to_owned()))
+        }
+    }
+
+    let server = ServerBuilder::new()
+        .hook(
+            HttpHook::new("http://localhost/hooks", Arc::new(HangingHandler))
+                .timeout(Duration::from_millis(50)),
+        )
+        .build(MemoryStore::new());
+
+    let mut req = create_request(HttpMethod::Post, "/files/", None);
+    req.headers
+        .insert("upload-length".to_owned(), "100".to_owned());
+    let started = Instant::now();
+    let response = server.handle(req);
+
+    assert_eq!(500, response.status_code);
+    assert!(started.elapsed() < Duration::from_millis(900));
+}
+
+#[cfg(unix)]
+#[test]
+fn should_change_upload_with_file_hook() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let directory = tempfile::tempdir().unwrap();
+    let hook_path = directory.path().join("pre-create");
+    std::fs::write(
+        &hook_path,
+        "#!/bin/sh\ncat > /dev/null\necho '{\"ChangeFileInfo\":{\"ID\":\"from-file-hook\"}}'\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&hook_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let temp_file = create_temp_file(100);
+    let server = Arc::new(
+        ServerBuilder::new()
+            .hook(FileHook::new(directory.path()))
+            .build(MemoryStore::new()),
+    );
+
+    let upload_url = Client::from_arc(server.clone())
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+
+    assert_eq!("http://localhost/files/from-file-hook", upload_url);
+}
+
+#[test]
+fn should_kill_file_hook_after_timeout() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let directory = tempfile::tempdir().unwrap();
+    let hook_path = directory.path().join("pre-create");
+    std::fs::write(&hook_path, "#!/bin/sh\nexec sleep 10\n").unwrap();
+    std::fs::set_permissions(&hook_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let temp_file = create_temp_file(100);
+    let (sender, errors) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let server = Arc::new(
+        ServerBuilder::new()
+            .hook(FileHook::new(directory.path()).timeout(Duration::from_millis(200)))
+            .on_hook_error(move |hook_type, error| {
+                sender
+                    .lock()
+                    .unwrap()
+                    .send((hook_type, error.kind()))
+                    .unwrap();
+            })
+            .build(MemoryStore::new()),
+    );
+
+    let started = Instant::now();
+    match Client::from_arc(server.clone()).create("http://localhost/files/", temp_file.path()) {
+        Err(Error::UnexpectedStatusCode(500)) => {}
+        result => panic!(
+            "Expected 'Error::UnexpectedStatusCode(500)', got {:?}",
+            result
+        ),
+    }
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(
+        (HookType::PreCreate, io::ErrorKind::TimedOut),
+        errors.try_recv().unwrap()
+    );
+}
+
+#[test]
+fn should_report_hook_errors() {
+    let temp_file = create_temp_file(100);
+    let (sender, errors) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let server = Arc::new(
+        ServerBuilder::new()
+            .hook(|event: &HookEvent| match event.hook_type {
+                HookType::PostFinish => Err(io::Error::other("Webhook unreachable")),
+                HookType::PostTerminate => panic!("Hook bug"),
+                _ => Ok(HookResponse::default()),
+            })
+            .on_hook_error(move |hook_type, error| {
+                sender
+                    .lock()
+                    .unwrap()
+                    .send((hook_type, error.to_string()))
+                    .unwrap();
+            })
+            .build(MemoryStore::new()),
+    );
+    let client = Client::from_arc(server.clone());
+
+    let upload_url = client
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+    client.delete(&upload_url).expect("'delete' call failed");
+
+    assert_eq!(
+        (HookType::PostFinish, "Webhook unreachable".to_owned()),
+        errors.recv_timeout(Duration::from_secs(5)).unwrap()
+    );
+    assert_eq!(
+        (
+            HookType::PostTerminate,
+            "Hook panicked: Hook bug".to_owned()
+        ),
+        errors.recv_timeout(Duration::from_secs(5)).unwrap()
+    );
+}
+
+#[test]
+fn should_reject_hook_id_clashing_with_store_files() {
+    let directory = tempfile::tempdir().unwrap();
+    let temp_file = create_temp_file(100);
+    let server = Arc::new(
+        ServerBuilder::new()
+            .hook(|_: &HookEvent| {
+                Ok(HookResponse {
+                    id: Some("abc.info".to_owned()),
+                    ..HookResponse::default()
+                })
+            })
+            .build(FileStore::open(directory.path()).unwrap()),
+    );
+
+    match Client::from_arc(server.clone()).create("http://localhost/files/", temp_file.path()) {
+        Err(Error::UnexpectedStatusCode(500)) => {}
+        result => panic!(
+            "Expected 'Error::UnexpectedStatusCode(500)', got {:?}",
+            result
+        ),
+    }
+
+    assert!(!directory.path().join("abc.info").exists());
+    assert!(server.store().list().unwrap().is_empty());
+}
+
+#[test]
+fn should_expire_unfinished_uploads() {
+    let server = Arc::new(
+        ServerBuilder::new()
+            .expiration(Duration::from_millis(100))
+            .build(MemoryStore::new()),
+    );
+    let client = Client::from_arc(server.clone());
+    let unfinished = create_temp_file(100);
+    let finished = create_temp_file(100);
+
+    let info = client
+        .get_server_info("http://localhost/files/")
+        .expect("'get_server_info' call failed");
+    assert!(info.extensions.contains(&TusExtension::Expiration));
+
+    let unfinished_url = client
+        .create("http://localhost/files/", unfinished.path())
+        .expect("'create' call failed");
+    let finished_url = client
+        .create("http://localhost/files/", finished.path())
+        .expect("'create' call failed");
+    client
+        .upload(&finished_url, finished.path())
+        .expect("'upload' call failed");
+
+    let response = server.handle(create_request(HttpMethod::Head, &unfinished_url, None));
+    assert!(response.headers.contains_key("upload-expires"));
+    let response = server.handle(create_request(HttpMethod::Head, &finished_url, None));
+    assert!(!response.headers.contains_key("upload-expires"));
+
+    thread::sleep(Duration::from_millis(200));
+    let response = server.handle(create_request(HttpMethod::Head, &unfinished_url, None));
+    assert_eq!(410, response.status_code);
+
+    let report = server.collect_expired(true).unwrap();
+    assert!(report.dry_run);
+    assert_eq!(1, report.expired.len());
+    assert_eq!(upload_id(&unfinished_url), report.expired[0].id);
+    assert_eq!(2, server.store().list().unwrap().len());
+
+    let report = server.collect_expired(false).unwrap();
+    assert_eq!(1, report.expired.len());
+    assert_eq!(
+        vec![upload_id(&finished_url).to_owned()],
+        server
+            .store()
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|info| info.id)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn should_skip_expired_upload_locked_by_a_request() {
+    let temp_file = create_temp_file(100);
+    let (store, entered, resume) = BlockingStore::new();
+    let server = Arc::new(
+        ServerBuilder::new()
+            .expiration(Duration::from_millis(500))
+            .on_lock_conflict(LockConflict::RequestRelease(Duration::from_secs(10)))
+            .build(store),
+    );
+    let upload_url = Client::from_arc(server.clone())
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+
+    let first = {
+        let server = Arc::clone(&server);
+        let upload_url = upload_url.clone();
+        thread::spawn(move || {
+            server
+                .handle(create_patch_request(&upload_url, 0, b"12345"))
+                .status_code
+        })
+    };
+    entered.recv().unwrap();
+    thread::sleep(Duration::from_millis(600));
+
+    let started = Instant::now();
+    let report = server.collect_expired(false).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(report.expired.is_empty());
+    assert_eq!(vec![upload_id(&upload_url).to_owned()], report.skipped);
+
+    resume.send(()).unwrap();
+    assert_eq!(204, first.join().unwrap());
+    let report = server.collect_expired(false).unwrap();
+    assert!(report.expired.is_empty());
+    assert!(report.skipped.is_empty());
+}
+
+#[test]
+fn collector_should_delete_expired_uploads_periodically() {
+    let server = Arc::new(
+        ServerBuilder::new()
+            .expiration(Duration::from_millis(10))
+            .build(MemoryStore::new()),
+    );
+    let temp_file = create_temp_file(100);
+    Client::from_arc(server.clone())
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+
+    let (sender, reports) = mpsc::channel();
+    let collector = Collector::spawn(
+        Arc::clone(&server),
+        Duration::from_millis(20),
+        false,
+        move |report| {
+            let _ = sender.send(report.unwrap());
+        },
+    );
+
+    let report = reports.recv_timeout(Duration::from_secs(5)).unwrap();
+    drop(collector);
+
+    assert_eq!(1, report.expired.len());
+    assert!(server.store().list().unwrap().is_empty());
+}
+
+#[test]
+fn should_verify_checksum() {
+    let server = Arc::new(Server::new(MemoryStore::new()));
+    let temp_file = create_temp_file(5);
+    let upload_url = Client::from_arc(server.clone())
+        .create("http://localhost/files/", temp_file.path())
+        .unwrap();
+    let req = create_patch_request(&upload_url, 0, b"hello");
+
+    let mut mismatch = req.clone();
+    mismatch.headers.insert(
+        "upload-checksum".to_owned(),
+        "sha1 AAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned(),
+    );
+    assert_eq!(460, server.handle(mismatch).status_code);
+    assert!(server
+        .store()
+        .data(upload_id(&upload_url))
+        .unwrap()
+        .is_empty());
+
+    let mut unsupported = req.clone();
+    unsupported
+        .headers
+        .insert("upload-checksum".to_owned(), "md5 AAAA".to_owned());
+    assert_eq!(400, server.handle(unsupported).status_code);
+
+    let mut req = req;
+    req.headers.insert(
+        "upload-checksum".to_owned(),
+        "sha1 qvTGHdzF6KLavt4PO0gs2a6pQ00=".to_owned(),
+    );
+    assert_eq!(204, server.handle(req).status_code);
+    assert_eq!(
+        b"hello".to_vec(),
+        server.store().data(upload_id(&upload_url)).unwrap()
+    );
+}
+
+#[test]
+fn should_concatenate_partial_uploads() {
+    let directory = tempfile::tempdir().unwrap();
+    let server = Arc::new(Server::new(FileStore::open(directory.path()).unwrap()));
+    let client = Client::from_arc(server.clone());
+    let parts = [create_temp_file(1000), create_temp_file(500)];
+
+    let mut urls = Vec::new();
+    for part in &parts {
+        let mut req = create_request(HttpMethod::Post, "http://localhost/files/", None);
+        req.headers
+            .insert("upload-concat".to_owned(), "partial".to_owned());
+        req.headers.insert(
+            "upload-length".to_owned(),
+            part.as_file().metadata().unwrap().len().to_string(),
+        );
+        let response = server.handle(req);
+        let url = response.headers["location"].clone();
+        client
+            .upload(&url, part.path())
+            .expect("'upload' call failed");
+        urls.push(url);
+    }
+
+    let mut req = create_request(HttpMethod::Post, "http://localhost/files/", None);
+    req.headers.insert(
+        "upload-concat".to_owned(),
+        format!("final;{}", urls.join(" ")),
+    );
+    let response = server.handle(req);
+    assert_eq!(201, response.status_code);
+    let final_url = response.headers["location"].clone();
+
+    let mut expected = std::fs::read(parts[0].path()).unwrap();
+    expected.extend(std::fs::read(parts[1].path()).unwrap());
+    assert_eq!(
+        expected,
+        std::fs::read(server.store().data_path(upload_id(&final_url))).unwrap()
+    );
+
+    let response = server.handle(create_request(HttpMethod::Head, &final_url, None));
+    assert_eq!("1500", response.headers["upload-offset"]);
+    assert_eq!(
+        format!("final;{}", urls.join(" ")),
+        response.headers["upload-concat"]
+    );
+    let req = create_patch_request(&final_url, 1500, b"");
+    assert_eq!(403, server.handle(req).status_code);
+
+    let reopened = FileStore::open(directory.path()).unwrap();
+    let info = reopened.get(upload_id(&final_url)).unwrap().unwrap();
+    assert!(info.is_final());
+    assert!(
+        reopened
+            .get(upload_id(&urls[0]))
+            .unwrap()
+            .unwrap()
+            .is_partial
+    );
+}
+
+#[test]
+fn should_honour_method_override() {
+    struct OverrideChecker<'a>(&'a Server<MemoryStore>);
+
+    impl<'a> HttpHandler for OverrideChecker<'a> {
+        fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
+            assert_eq!(HttpMethod::Post, req.method);
+            let method = &req.headers["x-http-method-override"];
+            assert_eq!(&method.to_uppercase(), method);
+            self.0.handle_request(req)
+        }
+    }
+
+    let temp_file = create_temp_file(1_000);
+    let server = Server::new(MemoryStore::new());
+    let client = Client::with_method_override(OverrideChecker(&server));
+
+    let upload_url = client
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+    assert_eq!(
+        std::fs::read(temp_file.path()).unwrap(),
+        server.store().data(upload_id(&upload_url)).unwrap()
+    );
+    client.delete(&upload_url).expect("'delete' call failed");
+    assert!(server.store().list().unwrap().is_empty());
+
+    let mut req = create_request(HttpMethod::Post, &upload_url, None);
+    req.headers
+        .insert("X-HTTP-Method-Override".to_owned(), "TRACE".to_owned());
+    assert_eq!(400, server.handle(req).status_code);
+}
+
+#[test]
+fn should_send_cors_headers() {
+    let server = ServerBuilder::new()
+        .cors(
+            Cors::with_origins(&["https://app.example.com"])
+                .allow_credentials(true)
+                .max_age(Duration::from_secs(600)),
+        )
+        .build(MemoryStore::new());
+
+    let mut preflight = create_request(HttpMethod::Options, "/files/", None);
+    preflight.headers.remove("tus-resumable");
+    preflight
+        .headers
+        .insert("Origin".to_owned(), "https://app.example.com".to_owned());
+    preflight.headers.insert(
+        "Access-Control-Request-Method".to_owned(),
+        "POST".to_owned(),
+    );
+    let response = server.handle(preflight.clone());
+    assert_eq!(204, response.status_code);
+    assert_eq!(
+        "https://app.example.com",
+        response.headers["access-control-allow-origin"]
+    );
+    assert_eq!("true", response.headers["access-control-allow-credentials"]);
+    assert_eq!("600", response.headers["access-control-max-age"]);
+    assert!(response.headers["access-control-allow-headers"].contains("Upload-Offset"));
+
+    let mut req = create_request(HttpMethod::Post, "/files/", None);
+    req.headers
+        .insert("upload-length".to_owned(), "10".to_owned());
+    req.headers
+        .insert("origin".to_owned(), "https://app.example.com".to_owned());
+    let response = server.handle(req);
+    assert_eq!(201, response.status_code);
+    let exposed = &response.headers["access-control-expose-headers"];
+    assert!(exposed.contains("Location"));
+    assert!(exposed.contains("Upload-Offset"));
+    assert!(exposed.contains("Content-Range"));
+    assert!(exposed.contains("Content-Disposition"));
+
+    preflight
+        .headers
+        .insert("Origin".to_owned(), "https://evil.example.com".to_owned());
+    let response = server.handle(preflight);
+    assert!(!response.headers.contains_key("access-control-allow-origin"));
+
+    let server = ServerBuilder::new()
+        .cors(Cors::new())
+        .build(MemoryStore::new());
+    let mut req = create_request(HttpMethod::Head, "/files/unknown", None);
+    req.headers
+        .insert("origin".to_owned(), "https://any.example.com".to_owned());
+    let response = server.handle(req);
+    assert_eq!(404, response.status_code);
+    assert_eq!("*", response.headers["access-control-allow-origin"]);
+}
+
+#[test]
+fn should_round_trip_upload_with_download() {
+    let temp_file = create_temp_file(10_000);
+    let directory = tempfile::tempdir().unwrap();
+    let server = Arc::new(Server::new(FileStore::open(directory.path()).unwrap()));
+    let client = ClientBuilder::new()
+        .chunk_size(3_000)
+        .build_from_arc(server.clone());
+    let mut metadata = HashMap::new();
+    metadata.insert("filename".to_owned(), "résumé \"final\".pdf".to_owned());
+    metadata.insert("filetype".to_owned(), "application/pdf".to_owned());
+
+    let upload_url = client
+        .create_with_metadata("http://localhost/files/", temp_file.path(), metadata)
+        .expect("'create_with_metadata' call failed");
+
+    // Unfinished uploads can't be downloaded
+    let response = server.handle(create_request(HttpMethod::Get, &upload_url, None));
+    assert_eq!(409, response.status_code);
+
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+
+    let response = server.handle(HttpRequest {
+        headers: HashMap::new(),
+        ..create_request(HttpMethod::Get, &upload_url, None)
+    });
+    assert_eq!(200, response.status_code);
+    assert_eq!("application/pdf", response.headers["content-type"]);
+    assert_eq!(
+        "attachment; filename=\"r_sum_ _final_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9%20%22final%22.pdf",
+        response.headers["content-disposition"]
+    );
+
+    let download = NamedTempFile::new().unwrap();
+    client
+        .download(&upload_url, download.path())
+        .expect("'download' call failed");
+    assert_eq!(
+        std::fs::read(temp_file.path()).unwrap(),
+        std::fs::read(download.path()).unwrap()
+    );
+}
+
+#[test]
+fn should_check_whether_upload_can_be_resumed() {
+    let server = Arc::new(Server::new(MemoryStore::new()));
+    let client = Client::from_arc(server.clone());
+    let temp_file = create_temp_file(100);
+    let other_file = create_temp_file(50);
+    let upload_url = client
+        .create("http://localhost/files/", temp_file.path())
+        .unwrap();
+
+    assert!(client.can_resume(&upload_url, temp_file.path()).unwrap());
+    assert!(!client.can_resume(&upload_url, other_file.path()).unwrap());
+
+    client.delete(&upload_url).unwrap();
+    assert!(!client.can_resume(&upload_url, temp_file.path()).unwrap());
+}
+
+#[test]
+fn should_serve_ranges_of_upload() {
+    let server = Arc::new(Server::new(MemoryStore::new()));
+    let client = Client::from_arc(server.clone());
+    let temp_file = create_temp_file(100);
+    let upload_url = client
+        .create("http://localhost/files/", temp_file.path())
+        .unwrap();
+    client.upload(&upload_url, temp_file.path()).unwrap();
+    let data = std::fs::read(temp_file.path()).unwrap();
+
+    let get = |range: &str| {
+        let mut req = create_request(HttpMethod::Get, &upload_url, None);
+        req.headers.insert("range".to_owned(), range.to_owned());
+        let response = server.handle(req);
+        let mut body = Vec::new();
+        server
+            .read_body(response.body)
+            .read_to_end(&mut body)
+            .unwrap();
+        (response.status_code, response.headers, body)
+    };
+
+    let (status_code, headers, body) = get("bytes=10-19");
+    assert_eq!(206, status_code);
+    assert_eq!("bytes 10-19/100", headers["content-range"]);
+    assert_eq!(&data[10..20], &body[..]);
+
+    let (status_code, _, body) = get("bytes=90-");
+    assert_eq!(206, status_code);
+    assert_eq!(&data[90..], &body[..]);
+
+    let (status_code, headers, body) = get("bytes=-5");
+    assert_eq!(206, status_code);
+    assert_eq!("bytes 95-99/100", headers["content-range"]);
+    assert_eq!(&data[95..], &body[..]);
+
+    let (status_code, _, body) = get("bytes=50-500");
+    assert_eq!(206, status_code);
+    assert_eq!(&data[50..], &body[..]);
+
+    // Multiple ranges aren't supported, so the whole upload is sent
+    let (status_code, _, body) = get("bytes=0-1,5-6");
+    assert_eq!(200, status_code);
+    assert_eq!(data, body);
+
+    let (status_code, headers, _) = get("bytes=100-");
+    assert_eq!(416, status_code);
+    assert_eq!("bytes */100", headers["content-range"]);
+
+    let response = server.handle(create_request(
+        HttpMethod::Get,
+        "http://localhost/files/unknown",
+        None,
+    ));
+    assert_eq!(404, response.status_code);
+}
+
+#[test]
+fn should_upload_growing_file_with_deferred_length() {
+    let directory = tempfile::tempdir().unwrap();
+    let server = Arc::new(Server::new(
+        FileStore::open(directory.path().join("uploads")).unwrap(),
+    ));
+    let client = ClientBuilder::new()
+        .chunk_size(1_000)
+        .build_from_arc(server.clone());
+    let path = directory.path().join("recording.bin");
+    std::fs::write(&path, b"").unwrap();
+
+    let upload_url = client
+        .create_with_deferred_length("http://localhost/files/", HashMap::new())
+        .expect("'create_with_deferred_length' call failed");
+    let response = server.handle(create_request(HttpMethod::Head, &upload_url, None));
+    assert_eq!("1", response.headers["upload-defer-length"]);
+    assert!(!response.headers.contains_key("upload-length"));
+    let reopened = FileStore::open(directory.path().join("uploads")).unwrap();
+    assert!(
+        reopened
+            .get(upload_id(&upload_url))
+            .unwrap()
+            .unwrap()
+            .size_is_deferred
+    );
+
+    let options = TailOptions {
+        poll_interval: Duration::from_millis(10),
+        idle_timeout: None,
+        finished: Arc::new(AtomicBool::new(false)),
+    };
+    let writer = {
+        let path = path.clone();
+        let finished = Arc::clone(&options.finished);
+        thread::spawn(move || {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap();
+            for i in 0..5 {
+                file.write_all(&[i; 700]).unwrap();
+                thread::sleep(Duration::from_millis(30));
+            }
+            finished.store(true, Ordering::SeqCst);
+        })
+    };
+
+    let size = client
+        .upload_growing(&upload_url, &path, &options)
+        .expect("'upload_growing' call failed");
+    writer.join().unwrap();
+
+    assert_eq!(3_500, size);
+    let info = client
+        .get_info(&upload_url)
+        .expect("'get_info' call failed");
+    assert_eq!(Some(3_500), info.total_size);
+    assert_eq!(3_500, info.bytes_uploaded);
+    assert_eq!(
+        std::fs::read(&path).unwrap(),
+        std::fs::read(server.store().data_path(upload_id(&upload_url))).unwrap()
+    );
+    assert!(server
+        .store()
+        .get(upload_id(&upload_url))
+        .unwrap()
+        .unwrap()
+        .is_finished());
+}
+
+#[test]
+fn should_send_digest_of_finished_upload() {
+    let temp_file = create_temp_file(2_500_000);
+    let server = Arc::new(Server::new(MemoryStore::new()));
+    let client = ClientBuilder::new().build_from_arc(server.clone());
+    let digest = Digest::compute(temp_file.path(), DigestAlgorithm::Sha256).unwrap();
+
+    let upload_url = client
+        .create("http://localhost/files/", temp_file.path())
+        .expect("'create' call failed");
+    // The digest is only sent once the upload is finished
+    match client.verify_upload(&upload_url, &digest) {
+        Err(Error::MissingHeader(header)) => assert_eq!("repr-digest", header),
+        result => panic!("Expected 'Error::MissingHeader', got {:?}", result),
+    }
+
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+    client
+        .verify_upload(&upload_url, &digest)
+        .expect("'verify_upload' call failed");
+
+    let response = server.handle(create_request(HttpMethod::Head, &upload_url, None));
+    assert!(!response.headers.contains_key("repr-digest"));
+}
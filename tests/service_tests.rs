@@ -0,0 +1,325 @@
+use bytes::Bytes;
+use http_body::{Body, Frame};
+use http_body_util::{BodyExt, Full};
+use hyper_util::client::legacy::Client as HyperClient;
+use hyper_util::rt::TokioExecutor;
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tempfile::NamedTempFile;
+use tokio::runtime::Runtime;
+use tokio::sync::{mpsc, oneshot};
+use tower_service::Service;
+use tus_client::http::{HttpHandler, HttpMethod, HttpRequest, HttpResponse, ResponseBody};
+use tus_client::server::{
+    DataStore, FileStore, MemoryStore, Server, ServerBuilder, ShutdownHandle, TusService,
+};
+use tus_client::{Client, Error};
+
+fn create_temp_file(size: usize) -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    let buffer: Vec<u8> = (0..size).map(|_| rand::random::<u8>()).collect();
+    temp_file.write_all(&buffer[..]).unwrap();
+    temp_file
+}
+
+/// A blocking `HttpHandler` sending requests with hyper, on the runtime of the test server.
+struct HyperHandler {
+    runtime: Arc<Runtime>,
+    client: HyperClient<hyper_util::client::legacy::connect::HttpConnector, Full<Bytes>>,
+}
+
+impl HyperHandler {
+    fn new(runtime: Arc<Runtime>) -> Self {
+        HyperHandler {
+            runtime,
+            client: HyperClient::builder(TokioExecutor::new()).build_http(),
+        }
+    }
+}
+
+impl HttpHandler for HyperHandler {
+    fn handle_request(&self, req: HttpRequest) -> Result<HttpResponse, Error> {
+        self.handle_download(req).map(|(response, _)| response)
+    }
+
+    fn handle_download(&self, req: HttpRequest) -> Result<(HttpResponse, ResponseBody<'_>), Error> {
+        let mut builder = hyper::Request::builder()
+            .method(req.method.to_string().to_uppercase().as_str())
+            .uri(&req.url);
+        for (key, value) in &req.headers {
+            builder = builder.header(key.as_str(), value.as_str());
+        }
+        let request = builder
+            .body(Full::new(Bytes::copy_from_slice(
+                req.body.unwrap_or_default(),
+            )))
+            .map_err(|e| Error::HttpHandlerError(e.to_string()))?;
+
+        self.runtime.block_on(async {
+            let response = self
+                .client
+                .request(request)
+                .await
+                .map_err(|e| Error::HttpHandlerError(e.to_string()))?;
+            let status_code = response.status().as_u16() as usize;
+            let headers = response
+                .headers()
+                .iter()
+                .map(|(key, value)| {
+                    (
+                        key.to_string(),
+                        value.to_str().map(String::from).unwrap_or_default(),
+                    )
+                })
+                .collect();
+            let body = response
+                .into_body()
+                .collect()
+                .await
+                .map_err(|e| Error::HttpHandlerError(e.to_string()))?
+                .to_bytes();
+
+            Ok((
+                HttpResponse {
+                    status_code,
+                    headers,
+                },
+                Box::new(Cursor::new(body)) as ResponseBody,
+            ))
+        })
+    }
+}
+
+/// A request body sending the chunks received from a channel, so a test controls when it ends.
+struct ChannelBody(mpsc::Receiver<Bytes>);
+
+impl Body for ChannelBody {
+    type Data = Bytes;
+    type Error = std::convert::Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        self.0
+            .poll_recv(cx)
+            .map(|chunk| chunk.map(|c| Ok(Frame::data(c))))
+    }
+}
+
+/// Serves `router` on a random local port. Once told to stop, the server shuts down gracefully, completing the shutdown of the service first.
+fn serve(
+    runtime: &Runtime,
+    router: axum::Router,
+    shutdown: ShutdownHandle,
+) -> (SocketAddr, oneshot::Sender<()>, tokio::task::JoinHandle<()>) {
+    let listener = runtime
+        .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+        .unwrap();
+    let address = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel();
+    let task = runtime.spawn(async move {
+        axum::serve(listener, router)
+            .with_graceful_shutdown(async move {
+                let _ = stopped.await;
+                shutdown.shutdown().await
+            })
+            .await
+            .unwrap();
+    });
+    (address, stop, task)
+}
+
+fn create_server<S: DataStore>(store: S) -> Arc<Server<S>> {
+    Arc::new(ServerBuilder::new().base_path("/api/files/").build(store))
+}
+
+#[test]
+fn should_receive_upload_in_nested_router() {
+    let runtime = Arc::new(Runtime::new().unwrap());
+    let server = create_server(MemoryStore::new());
+    let service = TusService::new(Arc::clone(&server));
+    let shutdown = service.shutdown_handle();
+    let router = axum::Router::new().nest_service("/api/files", service);
+    let (address, _stop, _task) = serve(&runtime, router, shutdown);
+
+    let temp_file = create_temp_file(100_000);
+    let client = Client::new(HyperHandler::new(Arc::clone(&runtime)));
+    let mut metadata = HashMap::new();
+    metadata.insert("filename".to_owned(), "file.bin".to_owned());
+
+    let upload_url = client
+        .create_with_metadata(
+            &format!("http://{}/api/files/", address),
+            temp_file.path(),
+            metadata.clone(),
+        )
+        .expect("'create_with_metadata' call failed");
+    assert!(upload_url.starts_with(&format!("http://{}/api/files/", address)));
+
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+
+    let info = client
+        .get_info(&upload_url)
+        .expect("'get_info' call failed");
+    assert_eq!(100_000, info.bytes_uploaded);
+    assert_eq!(Some(metadata), info.metadata);
+    assert_eq!(
+        std::fs::read(temp_file.path()).unwrap(),
+        server
+            .store()
+            .data(upload_url.rsplit('/').next().unwrap())
+            .unwrap()
+    );
+}
+
+#[test]
+fn should_stream_download_in_chunks() {
+    let runtime = Runtime::new().unwrap();
+    let server = create_server(MemoryStore::new());
+    let mut service = TusService::new(Arc::clone(&server));
+    let temp_file = create_temp_file(1_000_000);
+    let client = Client::from_arc(server.clone());
+    let upload_url = client
+        .create("http://localhost/api/files/", temp_file.path())
+        .expect("'create' call failed");
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+
+    let request = hyper::Request::get(upload_url)
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let (content_length, chunks) = runtime.block_on(async {
+        let response = service.call(request).await.unwrap();
+        assert_eq!(200, response.status());
+        let content_length = response.headers()["content-length"].clone();
+
+        let mut body = response.into_body();
+        let mut chunks = Vec::new();
+        while let Some(frame) = body.frame().await {
+            chunks.push(frame.unwrap().into_data().unwrap());
+        }
+        (content_length, chunks)
+    });
+
+    assert_eq!("1000000", content_length);
+    assert!(chunks.len() > 1);
+    assert_eq!(std::fs::read(temp_file.path()).unwrap(), chunks.concat());
+}
+
+#[test]
+fn should_use_forwarded_headers_in_location() {
+    let server = ServerBuilder::new()
+        .base_path("/api/files/")
+        .behind_proxy(true)
+        .build(MemoryStore::new());
+
+    let (_, location) = create_with_forwarded_headers(Arc::new(server));
+    assert!(location.starts_with("https://example.com/api/files/"));
+}
+
+#[test]
+fn should_ignore_forwarded_headers_unless_behind_proxy() {
+    let (address, location) = create_with_forwarded_headers(create_server(MemoryStore::new()));
+    assert!(location.starts_with(&format!("http://{}/api/files/", address)));
+}
+
+/// Creates an upload with `X-Forwarded-*` headers claiming the request was sent to `https://example.com`, returning the address of the service and the URL of the upload.
+fn create_with_forwarded_headers(server: Arc<Server<MemoryStore>>) -> (SocketAddr, String) {
+    let runtime = Arc::new(Runtime::new().unwrap());
+    let service = TusService::new(server);
+    let shutdown = service.shutdown_handle();
+    // The prefix isn't stripped by a fallback service
+    let router = axum::Router::new().fallback_service(service);
+    let (address, _stop, _task) = serve(&runtime, router, shutdown);
+
+    let handler = HyperHandler::new(Arc::clone(&runtime));
+    let mut headers = HashMap::new();
+    headers.insert("tus-resumable".to_owned(), "1.0.0".to_owned());
+    headers.insert("upload-length".to_owned(), "10".to_owned());
+    headers.insert("x-forwarded-proto".to_owned(), "https".to_owned());
+    headers.insert("x-forwarded-host".to_owned(), "example.com".to_owned());
+
+    let response = handler
+        .handle_request(HttpRequest {
+            method: HttpMethod::Post,
+            headers,
+            url: format!("http://{}/api/files/", address),
+            body: None,
+            timeout: None,
+        })
+        .unwrap();
+    assert_eq!(201, response.status_code);
+    (address, response.headers["location"].clone())
+}
+
+#[test]
+fn should_interrupt_uploads_when_shutting_down() {
+    let runtime = Arc::new(Runtime::new().unwrap());
+    let directory = tempfile::tempdir().unwrap();
+    let store = FileStore::open(directory.path()).unwrap();
+    let server = create_server(store);
+    let mut service = TusService::new(Arc::clone(&server));
+    let shutdown = service.shutdown_handle();
+    let router = axum::Router::new().nest_service("/api/files", service.clone());
+    let (address, stop, task) = serve(&runtime, router, shutdown.clone());
+
+    let temp_file = create_temp_file(100);
+    let client = Client::new(HyperHandler::new(Arc::clone(&runtime)));
+    let upload_url = client
+        .create(&format!("http://{}/api/files/", address), temp_file.path())
+        .expect("'create' call failed");
+
+    // Start an upload which sends part of its body, then stalls
+    let (sender, receiver) = mpsc::channel(1);
+    let request = hyper::Request::patch(&upload_url)
+        .header("tus-resumable", "1.0.0")
+        .header("upload-offset", "0")
+        .header("content-type", "application/offset+octet-stream")
+        .header("content-length", "100")
+        .body(ChannelBody(receiver))
+        .unwrap();
+    let patch_client: HyperClient<_, ChannelBody> =
+        HyperClient::builder(TokioExecutor::new()).build_http();
+    let patch = runtime.spawn(async move { patch_client.request(request).await });
+    runtime
+        .block_on(sender.send(Bytes::from(vec![0; 40])))
+        .unwrap();
+    let id = upload_url.rsplit('/').next().unwrap().to_owned();
+    let data_path = server.store().data_path(&id);
+    while std::fs::metadata(&data_path).unwrap().len() != 40 {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    runtime
+        .block_on(async { tokio::time::timeout(Duration::from_secs(5), shutdown.shutdown()).await })
+        .expect("The shutdown didn't complete");
+    assert!(shutdown.is_shutting_down());
+
+    // The upload in progress failed before its body ended, keeping the data it received
+    let response = runtime.block_on(patch).unwrap().unwrap();
+    assert_eq!(500, response.status().as_u16());
+    drop(sender);
+    assert_eq!(40, server.store().get(&id).unwrap().unwrap().offset);
+
+    // New requests are rejected, until the server stops
+    stop.send(()).unwrap();
+    let request = hyper::Request::head(&upload_url)
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let response = runtime.block_on(service.call(request)).unwrap();
+    assert_eq!(503, response.status().as_u16());
+
+    runtime
+        .block_on(async { tokio::time::timeout(Duration::from_secs(5), task).await })
+        .expect("The server didn't stop")
+        .unwrap();
+}
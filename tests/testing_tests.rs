@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tempfile::NamedTempFile;
+use tus_client::http::{HttpHandler, HttpMethod, HttpRequest};
+use tus_client::testing::{Fault, FaultInjector, FaultSchedule, InjectedFault, MockServer};
+use tus_client::{Client, ClientBuilder, Error, RetryPolicy, TailOptions, TusExtension};
+
+fn create_temp_file(size: usize) -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    let buffer: Vec<u8> = (0..size).map(|_| rand::random::<u8>()).collect();
+    temp_file.write_all(&buffer[..]).unwrap();
+    temp_file
+}
+
+fn create_request<'a>(method: HttpMethod, url: &str, body: Option<&'a [u8]>) -> HttpRequest<'a> {
+    let mut headers = HashMap::new();
+    headers.insert("tus-resumable".to_owned(), "1.0.0".to_owned());
+    HttpRequest {
+        method,
+        headers,
+        url: url.to_owned(),
+        body,
+        timeout: None,
+    }
+}
+
+#[test]
+fn mock_should_store_uploaded_file() {
+    let temp_file = create_temp_file(10_000);
+    let server = MockServer::new();
+    let client = ClientBuilder::new().chunk_size(3_000).build(server.clone());
+    let mut metadata = HashMap::new();
+    metadata.insert("filename".to_owned(), "file.bin".to_owned());
+
+    let upload_url = client
+        .create_with_metadata("/files/", temp_file.path(), metadata.clone())
+        .expect("'create_with_metadata' call failed");
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+
+    let upload = server.upload(&upload_url).unwrap();
+    assert_eq!(std::fs::read(temp_file.path()).unwrap(), upload.data);
+    assert_eq!(metadata, upload.metadata);
+    assert!(upload.is_finished());
+
+    let info = client
+        .get_info(&upload_url)
+        .expect("'get_info' call failed");
+    assert_eq!(10_000, info.bytes_uploaded);
+    assert_eq!(Some(metadata), info.metadata);
+
+    let patches = server
+        .requests()
+        .iter()
+        .filter(|req| req.method == HttpMethod::Patch)
+        .count();
+    assert_eq!(4, patches);
+}
+
+#[test]
+fn mock_should_advertise_configuration() {
+    let client = Client::new(
+        MockServer::new()
+            .with_max_size(1024)
+            .without_extension(TusExtension::Termination),
+    );
+
+    let info = client.connect("/files/").expect("'connect' call failed");
+
+    assert_eq!(Some(1024), info.max_upload_size);
+    assert_eq!(
+        vec![
+            TusExtension::Creation,
+            TusExtension::CreationDeferLength,
+            TusExtension::Checksum,
+            TusExtension::Concatenation
+        ],
+        info.extensions
+    );
+    match client.delete("/files/1") {
+        Err(Error::UnsupportedExtension(TusExtension::Termination)) => {}
+        _ => panic!("Expected 'Error::UnsupportedExtension(TusExtension::Termination)'"),
+    }
+}
+
+#[test]
+fn mock_should_configure_clone_separately() {
+    let temp_file = create_temp_file(2048);
+    let server = MockServer::new();
+    let client = Client::new(server.clone().with_max_size(1024));
+
+    let result = client.create("/files/", temp_file.path());
+
+    match result {
+        Err(Error::FileTooLarge) => {}
+        _ => panic!("Expected 'Error::FileTooLarge'"),
+    }
+    let upload_url = Client::new(server.clone())
+        .create("/files/", temp_file.path())
+        .expect("'create' call failed");
+    assert!(server.upload(&upload_url).is_some());
+}
+
+#[test]
+fn mock_should_reject_file_larger_than_max_size() {
+    let temp_file = create_temp_file(2048);
+    let client = Client::new(MockServer::new().with_max_size(1024));
+
+    let result = client.create("/files/", temp_file.path());
+
+    match result {
+        Err(Error::FileTooLarge) => {}
+        _ => panic!("Expected 'Error::FileTooLarge'"),
+    }
+}
+
+#[test]
+fn mock_should_delete_upload_with_method_override() {
+    let temp_file = create_temp_file(1024);
+    let server = MockServer::new();
+    let client = Client::with_method_override(server.clone());
+
+    let upload_url = client.create("/files/", temp_file.path()).unwrap();
+    client.delete(&upload_url).expect("'delete' call failed");
+
+    assert!(server.uploads().is_empty());
+    assert_eq!(HttpMethod::Delete, server.requests()[1].method);
+    match client.get_info(&upload_url) {
+        Err(Error::NotFoundError) => {}
+        _ => panic!("Expected 'Error::NotFoundError'"),
+    }
+}
+
+#[test]
+fn mock_should_verify_checksum() {
+    let server = MockServer::new();
+    let client = Client::new(server.clone());
+    let temp_file = create_temp_file(5);
+    let upload_url = client.create("/files/", temp_file.path()).unwrap();
+
+    let mut req = create_request(HttpMethod::Patch, &upload_url, Some(b"hello"));
+    req.headers.insert(
+        "content-type".to_owned(),
+        "application/offset+octet-stream".to_owned(),
+    );
+    req.headers
+        .insert("upload-offset".to_owned(), "0".to_owned());
+
+    let mut mismatch = req.clone();
+    mismatch.headers.insert(
+        "upload-checksum".to_owned(),
+        "sha1 AAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned(),
+    );
+    assert_eq!(460, server.handle_request(mismatch).unwrap().status_code);
+    assert!(server.upload(&upload_url).unwrap().data.is_empty());
+
+    req.headers.insert(
+        "upload-checksum".to_owned(),
+        "sha1 qvTGHdzF6KLavt4PO0gs2a6pQ00=".to_owned(),
+    );
+    assert_eq!(204, server.handle_request(req).unwrap().status_code);
+    assert_eq!(b"hello".to_vec(), server.upload(&upload_url).unwrap().data);
+}
+
+#[test]
+fn mock_should_reject_data_beyond_upload_length() {
+    let server = MockServer::new();
+    let client = Client::new(server.clone());
+    let temp_file = create_temp_file(3);
+    let upload_url = client.create("/files/", temp_file.path()).unwrap();
+
+    let mut req = create_request(HttpMethod::Patch, &upload_url, Some(b"hello"));
+    req.headers.insert(
+        "content-type".to_owned(),
+        "application/offset+octet-stream".to_owned(),
+    );
+    req.headers
+        .insert("upload-offset".to_owned(), "0".to_owned());
+
+    assert_eq!(413, server.handle_request(req).unwrap().status_code);
+    assert!(server.upload(&upload_url).unwrap().data.is_empty());
+}
+
+#[test]
+fn mock_should_expire_unfinished_uploads() {
+    let temp_file = create_temp_file(1024);
+    let server = MockServer::new().with_expiration(Duration::from_millis(50));
+    let client = Client::new(server.clone());
+
+    let upload_url = client.create("/files/", temp_file.path()).unwrap();
+    assert!(server.upload(&upload_url).unwrap().expires.is_some());
+    let response = server
+        .handle_request(create_request(HttpMethod::Head, &upload_url, None))
+        .unwrap();
+    assert!(response.headers.contains_key("upload-expires"));
+
+    thread::sleep(Duration::from_millis(100));
+
+    match client.upload(&upload_url, temp_file.path()) {
+        Err(Error::NotFoundError) => {}
+        _ => panic!("Expected 'Error::NotFoundError'"),
+    }
+}
+
+#[test]
+fn mock_should_concatenate_partial_uploads() {
+    let server = MockServer::new();
+    let client = Client::new(server.clone());
+    let parts = [create_temp_file(1000), create_temp_file(500)];
+
+    let mut urls = Vec::new();
+    for part in &parts {
+        let mut req = create_request(HttpMethod::Post, "/files/", None);
+        req.headers
+            .insert("upload-concat".to_owned(), "partial".to_owned());
+        req.headers.insert(
+            "upload-length".to_owned(),
+            part.as_file().metadata().unwrap().len().to_string(),
+        );
+        let response = server.handle_request(req).unwrap();
+        let url = response.headers["location"].clone();
+        client
+            .upload(&url, part.path())
+            .expect("'upload' call failed");
+        urls.push(url);
+    }
+
+    let mut req = create_request(HttpMethod::Post, "/files/", None);
+    req.headers.insert(
+        "upload-concat".to_owned(),
+        format!("final;{}", urls.join(" ")),
+    );
+    let response = server.handle_request(req).unwrap();
+    assert_eq!(201, response.status_code);
+
+    let upload = server.upload(&response.headers["location"]).unwrap();
+    let mut expected = std::fs::read(parts[0].path()).unwrap();
+    expected.extend(std::fs::read(parts[1].path()).unwrap());
+    assert_eq!(expected, upload.data);
+    assert_eq!(Some(urls), upload.partial_uploads);
+}
+
+#[test]
+fn fault_injector_should_truncate_scripted_request() {
+    let temp_file = create_temp_file(3_000);
+    let server = MockServer::new();
+    let upload_url = Client::new(server.clone())
+        .create("/files/", temp_file.path())
+        .unwrap();
+    let handler = Arc::new(FaultInjector::new(
+        server.clone(),
+        FaultSchedule::Scripted(vec![(2, Fault::Truncate(0.5))]),
+    ));
+    let client = ClientBuilder::new()
+        .chunk_size(1_000)
+        .retry_policy(RetryPolicy::new(1, Duration::from_millis(1)))
+        .build_from_arc(handler.clone());
+
+    client
+        .upload(&upload_url, temp_file.path())
+        .expect("'upload' call failed");
+
+    assert_eq!(
+        vec![InjectedFault {
+            request: 2,
+            method: HttpMethod::Patch,
+            fault: Fault::Truncate(0.5)
+        }],
+        handler.injected()
+    );
+    let offsets: Vec<_> = server
+        .requests()
+        .iter()
+        .filter(|req| req.method == HttpMethod::Patch)
+        .map(|req| req.headers["upload-offset"].clone())
+        .collect();
+    assert_eq!(vec!["0", "1000", "1500", "2500"], offsets);
+    assert_eq!(
+        std::fs::read(temp_file.path()).unwrap(),
+        server.upload(&upload_url).unwrap().data
+    );
+}
+
+#[test]
+fn fault_injector_should_inject_seeded_faults_deterministically() {
+    let temp_file = create_temp_file(20_000);
+    let upload = || {
+        let server = MockServer::new();
+        let upload_url = Client::new(server.clone())
+            .create("/files/", temp_file.path())
+            .unwrap();
+        let handler = Arc::new(
+            FaultInjector::new(
+                server.clone(),
+                FaultSchedule::Seeded {
+                    seed: 7,
+                    probability: 0.3,
+                    faults: vec![
+                        Fault::Drop,
+                        Fault::Latency(Duration::from_millis(1)),
+                        Fault::Truncate(0.3),
+                        Fault::Status(409),
+                        Fault::Status(429),
+                        Fault::Status(503),
+                        Fault::CorruptHeader(String::from("upload-offset")),
+                    ],
+                },
+            )
+            .with_methods(&[HttpMethod::Patch]),
+        );
+        let client = ClientBuilder::new()
+            .chunk_size(1_000)
+            .retry_policy(RetryPolicy::new(10, Duration::from_millis(1)))
+            .build_from_arc(handler.clone());
+
+        client
+            .upload(&upload_url, temp_file.path())
+            .expect("'upload' call failed");
+
+        assert_eq!(
+            std::fs::read(temp_file.path()).unwrap(),
+            server.upload(&upload_url).unwrap().data
+        );
+        handler.injected()
+    };
+
+    let injected = upload();
+
+    assert!(!injected.is_empty());
+    assert!(injected.iter().all(|f| f.method == HttpMethod::Patch));
+    assert_eq!(injected, upload());
+}
+
+#[test]
+fn mock_should_finish_growing_upload_when_idle() {
+    let temp_file = create_temp_file(2_500);
+    let server = MockServer::new();
+    let client = ClientBuilder::new().chunk_size(1_000).build(server.clone());
+
+    let upload_url = client
+        .create_with_deferred_length("/files/", HashMap::new())
+        .expect("'create_with_deferred_length' call failed");
+    assert_eq!(None, client.get_info(&upload_url).unwrap().total_size);
+
+    let options = TailOptions {
+        poll_interval: Duration::from_millis(10),
+        idle_timeout: Some(Duration::from_millis(100)),
+        ..TailOptions::default()
+    };
+    let size = client
+        .upload_growing(&upload_url, temp_file.path(), &options)
+        .expect("'upload_growing' call failed");
+    assert_eq!(2_500, size);
+
+    let upload = server.upload(&upload_url).unwrap();
+    assert!(upload.is_finished());
+    assert_eq!(std::fs::read(temp_file.path()).unwrap(), upload.data);
+
+    // The length is declared by a request without data, once the file stopped growing
+    let requests = server.requests();
+    let last = requests.last().unwrap();
+    assert_eq!(HttpMethod::Patch, last.method);
+    assert_eq!(0, last.body_len);
+    assert_eq!("2500", last.headers["upload-length"]);
+    assert_eq!(
+        4,
+        requests
+            .iter()
+            .filter(|req| req.method == HttpMethod::Patch)
+            .count()
+    );
+}
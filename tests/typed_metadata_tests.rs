@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use tempfile::NamedTempFile;
+use tus_client::metadata::{self, MAX_ENCODED_LEN};
+use tus_client::testing::MockServer;
+use tus_client::{Client, Error};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Priority {
+    Low,
+    High,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Scan {
+    filename: String,
+    page_count: u32,
+    duplex: bool,
+    priority: Priority,
+    batch_id: Option<u64>,
+}
+
+fn create_temp_file(size: usize) -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file.write_all(&vec![0; size]).unwrap();
+    temp_file
+}
+
+#[test]
+fn should_create_upload_with_typed_metadata() {
+    let temp_file = create_temp_file(100);
+    let server = MockServer::new();
+    let client = Client::new(server.clone());
+    let scan = Scan {
+        filename: "invoice.pdf".to_owned(),
+        page_count: 3,
+        duplex: true,
+        priority: Priority::High,
+        batch_id: None,
+    };
+
+    let upload_url = client
+        .create_with_typed_metadata("/files/", temp_file.path(), &scan)
+        .expect("'create_with_typed_metadata' call failed");
+
+    let mut expected = HashMap::new();
+    expected.insert("filename".to_owned(), "invoice.pdf".to_owned());
+    expected.insert("pageCount".to_owned(), "3".to_owned());
+    expected.insert("duplex".to_owned(), "true".to_owned());
+    expected.insert("priority".to_owned(), "high".to_owned());
+    assert_eq!(expected, server.upload(&upload_url).unwrap().metadata);
+
+    let info = client
+        .get_info(&upload_url)
+        .expect("'get_info' call failed");
+    assert_eq!(scan, info.metadata_as::<Scan>().unwrap());
+}
+
+#[test]
+fn should_report_metadata_which_can_not_be_converted() {
+    #[derive(Serialize)]
+    struct Nested {
+        tags: Vec<String>,
+    }
+
+    match metadata::to_map(&Nested { tags: vec![] }) {
+        Err(Error::InvalidMetadata(message)) => assert!(message.contains("tags")),
+        result => panic!("Expected 'Error::InvalidMetadata', got {:?}", result),
+    }
+
+    let mut stored = HashMap::new();
+    stored.insert("filename".to_owned(), "invoice.pdf".to_owned());
+    stored.insert("pageCount".to_owned(), "three".to_owned());
+    stored.insert("duplex".to_owned(), "true".to_owned());
+    stored.insert("priority".to_owned(), "high".to_owned());
+    match metadata::from_map::<Scan>(&stored) {
+        Err(Error::InvalidMetadata(message)) => assert!(message.contains("three")),
+        result => panic!("Expected 'Error::InvalidMetadata', got {:?}", result),
+    }
+    stored.remove("pageCount");
+    match metadata::from_map::<Scan>(&stored) {
+        Err(Error::InvalidMetadata(message)) => assert!(message.contains("pageCount")),
+        result => panic!("Expected 'Error::InvalidMetadata', got {:?}", result),
+    }
+}
+
+#[test]
+fn should_reject_invalid_keys_and_large_metadata() {
+    let temp_file = create_temp_file(100);
+    let server = MockServer::new();
+    let client = Client::new(server.clone());
+
+    for key in &["", "page count", "a,b", "caf\u{e9}"] {
+        let mut invalid = HashMap::new();
+        invalid.insert((*key).to_owned(), "value".to_owned());
+        match client.create_with_metadata("/files/", temp_file.path(), invalid) {
+            Err(Error::InvalidMetadataKey(invalid_key)) => assert_eq!(*key, invalid_key),
+            result => panic!("Expected 'Error::InvalidMetadataKey', got {:?}", result),
+        }
+    }
+
+    let mut large = HashMap::new();
+    large.insert("notes".to_owned(), "a".repeat(MAX_ENCODED_LEN));
+    match client.create_with_metadata("/files/", temp_file.path(), large) {
+        Err(Error::MetadataTooLarge(len)) => assert!(len > MAX_ENCODED_LEN),
+        result => panic!("Expected 'Error::MetadataTooLarge', got {:?}", result),
+    }
+    assert!(server.requests().is_empty());
+}
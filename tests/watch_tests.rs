@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tus_client::testing::MockServer;
+use tus_client::watch::{AfterUpload, WatchConfig, WatchEvent, Watcher};
+use tus_client::Client;
+
+/// Runs a watcher on another thread until `uploads` files have been uploaded, returning the paths and upload URLs.
+fn run_until_uploaded(
+    server: &MockServer,
+    config: WatchConfig,
+    uploads: usize,
+) -> Vec<(PathBuf, String)> {
+    let watcher = Watcher::new(Client::new_shared(server.clone()), config).unwrap();
+    let stop = watcher.stop_handle();
+    let (sender, receiver) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        watcher.run(|event| {
+            if let WatchEvent::Uploaded { path, upload_url } = event {
+                sender.send((path, upload_url)).unwrap();
+            }
+        })
+    });
+
+    let uploaded = (0..uploads)
+        .map(|_| {
+            receiver
+                .recv_timeout(Duration::from_secs(10))
+                .expect("The file wasn't uploaded")
+        })
+        .collect();
+    stop.stop();
+    worker.join().unwrap().expect("'run' call failed");
+    uploaded
+}
+
+fn config(directory: &tempfile::TempDir) -> WatchConfig {
+    let mut config = WatchConfig::new("/files/", directory.path().join("incoming"));
+    config.stable_for = Duration::from_millis(200);
+    config.state_file = directory.path().join("state.json");
+    config
+}
+
+#[test]
+fn should_upload_stable_files_and_move_them() {
+    let directory = tempfile::tempdir().unwrap();
+    let incoming = directory.path().join("incoming");
+    let done = directory.path().join("done");
+    fs::create_dir_all(&incoming).unwrap();
+    fs::write(incoming.join("a.bin"), vec![1; 1_000]).unwrap();
+    let server = MockServer::new();
+    let mut config = config(&directory);
+    config.after_upload = AfterUpload::MoveTo(done.clone());
+    config
+        .metadata
+        .insert("source".to_owned(), "scanner".to_owned());
+
+    // A file is added while the watcher is running, in a new subdirectory
+    let writer = {
+        let incoming = incoming.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            fs::create_dir_all(incoming.join("nested")).unwrap();
+            fs::write(incoming.join("nested").join("b.bin"), vec![2; 2_000]).unwrap();
+        })
+    };
+    let uploaded = run_until_uploaded(&server, config, 2);
+    writer.join().unwrap();
+
+    let mut relative_paths = Vec::new();
+    for (_, upload_url) in uploaded {
+        let upload = server.upload(&upload_url).unwrap();
+        assert!(upload.is_finished());
+        assert_eq!("scanner", upload.metadata["source"]);
+        relative_paths.push(upload.metadata["relativePath"].clone());
+    }
+    relative_paths.sort();
+    assert_eq!(vec!["a.bin", "nested/b.bin"], relative_paths);
+
+    assert!(!incoming.join("a.bin").exists());
+    assert_eq!(vec![1; 1_000], fs::read(done.join("a.bin")).unwrap());
+    assert_eq!(
+        vec![2; 2_000],
+        fs::read(done.join("nested").join("b.bin")).unwrap()
+    );
+}
+
+#[test]
+fn should_not_upload_files_again_after_restart() {
+    let directory = tempfile::tempdir().unwrap();
+    let incoming = directory.path().join("incoming");
+    fs::create_dir_all(&incoming).unwrap();
+    fs::write(incoming.join("a.bin"), vec![1; 1_000]).unwrap();
+    fs::write(incoming.join("b.bin"), vec![2; 1_000]).unwrap();
+    let server = MockServer::new();
+
+    run_until_uploaded(&server, config(&directory), 2);
+    assert_eq!(2, server.uploads().len());
+
+    // Only the file which changed while the watcher wasn't running is uploaded again
+    fs::write(incoming.join("b.bin"), vec![3; 500]).unwrap();
+    let uploaded = run_until_uploaded(&server, config(&directory), 1);
+    assert!(uploaded[0].0.ends_with("b.bin"));
+    assert_eq!(vec![3; 500], server.upload(&uploaded[0].1).unwrap().data);
+    assert_eq!(3, server.uploads().len());
+}
+
+#[cfg(unix)]
+#[test]
+fn should_report_unreadable_files_and_keep_watching() {
+    let directory = tempfile::tempdir().unwrap();
+    let incoming = directory.path().join("incoming");
+    fs::create_dir_all(&incoming).unwrap();
+    // A symlink loop can't be read, whatever the permissions of the user running the test
+    std::os::unix::fs::symlink(incoming.join("loop"), incoming.join("loop")).unwrap();
+    fs::write(incoming.join("a.bin"), vec![1; 1_000]).unwrap();
+    let server = MockServer::new();
+
+    let watcher = Watcher::new(Client::new_shared(server.clone()), config(&directory)).unwrap();
+    let stop = watcher.stop_handle();
+    let mut failed = Vec::new();
+    let result = watcher.run(|event| match event {
+        WatchEvent::Failed { path, .. } => failed.push(path),
+        WatchEvent::Uploaded { .. } => stop.stop(),
+        WatchEvent::Uploading { .. } => {}
+    });
+
+    result.expect("'run' call failed");
+    assert_eq!(1, server.uploads().len());
+    assert!(!failed.is_empty());
+    assert!(failed.iter().all(|path| path.ends_with("loop")));
+}
+
+#[test]
+fn should_write_state_files_through_separate_temp_files() {
+    let directory = tempfile::tempdir().unwrap();
+    let json = directory.path().join("a.json");
+    let state = directory.path().join("a.state");
+
+    assert_ne!(
+        tus_client::state::temp_path(&json),
+        tus_client::state::temp_path(&state)
+    );
+    tus_client::state::save(&json, &vec![1]).unwrap();
+    tus_client::state::save(&state, &vec![2]).unwrap();
+    assert_eq!(vec![1], tus_client::state::load::<Vec<u8>>(&json).unwrap());
+    assert_eq!(vec![2], tus_client::state::load::<Vec<u8>>(&state).unwrap());
+}